@@ -1,5 +1,6 @@
 use std::ptr::with_exposed_provenance;
 use crossterm::event::KeyCode;
+use crate::config::{current_settings, Settings};
 use crate::event::{EventPoster, Uuid};
 use crate::plot::Plot;
 use crate::popup::{PopUp, PopUpDimension, PopUpDimensionOption, PopUpPosition, PopUpPositionOption};
@@ -14,16 +15,22 @@ pub struct AlertSettings {
     pub background: ThemeColor
 }
 
-impl Default for AlertSettings {
-    fn default() -> AlertSettings {
+impl From<&Settings> for AlertSettings {
+    fn from(settings: &Settings) -> AlertSettings {
         Self {
-            margin: 2,
-            border: true,
-            background: ThemeColor::Background,
+            margin: settings.number("alertmargin"),
+            border: settings.bool("alertborder"),
+            background: settings.color("alertbackground"),
         }
     }
 }
 
+impl Default for AlertSettings {
+    fn default() -> AlertSettings {
+        Self::from(&*current_settings())
+    }
+}
+
 pub struct Alert {
     pub content: StyledText,
     pub options: Vec<(StyledText, Vec<WindowRequest>)>,
@@ -0,0 +1,485 @@
+//! Command lines typed at the `:` prompt, parsed against the commands windows register
+use std::path::{Path, PathBuf};
+use crate::config::{find_option, split_set_args, OptionKind, OPTIONS};
+use crate::style::themes;
+
+/// What may follow the name of a command
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ArgKind {
+    /// Nothing
+    None,
+    /// File path, `~` standing for the home directory
+    Path,
+    /// Whole number
+    Number,
+    /// Options as `:set` takes them
+    Options,
+    /// Rest of the line as typed
+    Text,
+    /// Name of an open buffer
+    Buffer,
+}
+
+/// Command a window runs
+#[derive(PartialEq, Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Shortest abbreviation of the name, `w` for `write` takes `w`, `wr` ... `write`
+    pub short: &'static str,
+    pub description: &'static str,
+    /// Takes a `!` after the name
+    pub bang: bool,
+    /// Takes a line range before the name
+    pub range: bool,
+    pub arg: ArgKind,
+    /// The argument can't be left out
+    pub required: bool,
+}
+
+impl CommandSpec {
+    /// Command taking nothing but its name
+    pub const fn new(name: &'static str, short: &'static str, description: &'static str) -> Self {
+        CommandSpec { name, short, description, bang: false, range: false, arg: ArgKind::None, required: false }
+    }
+    pub const fn bang(mut self) -> Self {
+        self.bang = true;
+        self
+    }
+    pub const fn range(mut self) -> Self {
+        self.range = true;
+        self
+    }
+    /// Takes an argument, which may be left out
+    pub const fn arg(mut self, arg: ArgKind) -> Self {
+        self.arg = arg;
+        self
+    }
+    /// Takes an argument that can't be left out
+    pub const fn required(mut self, arg: ArgKind) -> Self {
+        self.arg = arg;
+        self.required = true;
+        self
+    }
+
+    /// Name as typed, abbreviated or not
+    pub fn matches(&self, name: &str) -> bool {
+        name.starts_with(self.short) && self.name.starts_with(name)
+    }
+
+    /// Name with the part that may be left out in brackets, like `w[rite]`
+    pub fn usage(&self) -> String {
+        let rest = &self.name[self.short.len()..];
+        let name = if rest.is_empty() { self.name.to_string() } else { format!("{}[{}]", self.short, rest) };
+        let bang = if self.bang { "[!]" } else { "" };
+        let range = if self.range { "[range]" } else { "" };
+        let arg = match (self.arg, self.required) {
+            (ArgKind::None, _) => String::new(),
+            (arg, true) => format!(" {{{}}}", arg.name()),
+            (arg, false) => format!(" [{}]", arg.name()),
+        };
+        format!("{}{}{}{}", range, name, bang, arg)
+    }
+}
+
+impl ArgKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ArgKind::None => "",
+            ArgKind::Path => "file",
+            ArgKind::Number => "number",
+            ArgKind::Options => "option",
+            ArgKind::Text => "text",
+            ArgKind::Buffer => "buffer",
+        }
+    }
+}
+
+/// Argument of a parsed command, of the kind its command takes
+#[derive(Clone, PartialEq, Debug)]
+pub enum Arg {
+    Path(PathBuf),
+    Number(usize),
+    Options(Vec<String>),
+    Text(String),
+}
+
+/// Line counted from 1 like in a range, `.` the cursor's and `$` the last, moved by an offset
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Address {
+    Line(usize, isize),
+    Current(isize),
+    Last(isize),
+}
+
+impl Address {
+    /// Line index for the cursor's line and the number of lines, kept inside the buffer
+    pub fn resolve(&self, current: usize, count: usize) -> usize {
+        let (base, offset) = match *self {
+            Address::Line(line, offset) => (line as isize - 1, offset),
+            Address::Current(offset) => (current as isize, offset),
+            Address::Last(offset) => (count as isize - 1, offset),
+        };
+        (base + offset).clamp(0, count.saturating_sub(1) as isize) as usize
+    }
+}
+
+/// Lines a command acts on, like `5`, `.,+3` or `%`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LineRange {
+    pub start: Address,
+    pub end: Address,
+}
+
+impl LineRange {
+    /// First and last line index, in order
+    pub fn resolve(&self, current: usize, count: usize) -> (usize, usize) {
+        let start = self.start.resolve(current, count);
+        let end = self.end.resolve(current, count);
+        (start.min(end), start.max(end))
+    }
+}
+
+/// Command line checked against the command it names
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParsedCommand {
+    pub spec: &'static CommandSpec,
+    pub range: Option<LineRange>,
+    pub bang: bool,
+    pub arg: Option<Arg>,
+}
+
+impl ParsedCommand {
+    pub fn name(&self) -> &'static str {
+        self.spec.name
+    }
+    pub fn path(&self) -> Option<&Path> {
+        match &self.arg {
+            Some(Arg::Path(path)) => Some(path),
+            _ => None,
+        }
+    }
+    pub fn number(&self) -> Option<usize> {
+        match self.arg {
+            Some(Arg::Number(n)) => Some(n),
+            _ => None,
+        }
+    }
+    /// Options given, none if left out
+    pub fn options(&self) -> &[String] {
+        match &self.arg {
+            Some(Arg::Options(options)) => options,
+            _ => &[],
+        }
+    }
+    pub fn text(&self) -> Option<&str> {
+        match &self.arg {
+            Some(Arg::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a line like `:10`, `:w! out.txt` or `:set ts=4` against commands, the first matching
+/// an abbreviation being taken. Errors name what is wrong with the line
+pub fn parse(line: &str, commands: &[&'static CommandSpec]) -> Result<ParsedCommand, String> {
+    let mut rest = line.trim_start().trim_start_matches(':').trim_start();
+    let range = parse_range(&mut rest)?;
+    rest = rest.trim_start();
+
+    let name_len = match rest.find(|ch: char| !ch.is_ascii_alphabetic()) {
+        Some(0) if rest.starts_with(['<', '>']) => 1,
+        Some(len) => len,
+        None => rest.len(),
+    };
+    let (name, mut rest) = rest.split_at(name_len);
+    if name.is_empty() && range.is_none() {
+        return Err(if rest.trim().is_empty() { "No command".into() } else { format!("Not a command: {}", line.trim()) });
+    }
+    let spec = *commands.iter()
+        .find(|spec| spec.matches(name))
+        .ok_or_else(|| format!("Not a command: {}", line.trim()))?;
+    let shown = if spec.name.is_empty() { "Going to a line" } else { spec.name };
+
+    let bang = rest.starts_with('!');
+    if bang {
+        if !spec.bang { return Err(format!("{} takes no !", shown)); }
+        rest = &rest[1..];
+    }
+    if range.is_some() && !spec.range {
+        return Err(format!("{} takes no range", shown));
+    }
+
+    let text = rest.trim();
+    let arg = if text.is_empty() {
+        if spec.required { return Err(format!("{} needs an argument", shown)); }
+        None
+    } else {
+        Some(match spec.arg {
+            ArgKind::None => return Err(format!("Trailing characters: {}", text)),
+            ArgKind::Path => Arg::Path(expand_home(text)),
+            ArgKind::Number => Arg::Number(text.parse().map_err(|_| format!("Not a number: {}", text))?),
+            // a trailing `\ ` is part of the value
+            ArgKind::Options => Arg::Options(split_set_args(rest)),
+            ArgKind::Text | ArgKind::Buffer => Arg::Text(text.to_string()),
+        })
+    };
+
+    Ok(ParsedCommand { spec, range, bang, arg })
+}
+
+/// Words that may replace the last one of a command line
+#[derive(PartialEq, Debug)]
+pub struct Completion {
+    /// Byte index in the line where the word starts
+    pub start: usize,
+    /// Each match and its description
+    pub matches: Vec<(String, String)>,
+}
+
+/// Complete the end of line: the name of a command, then what its argument takes,
+/// buffers being the names of the open ones
+pub fn complete(line: &str, commands: &[&'static CommandSpec], buffers: &[String]) -> Completion {
+    let none = Completion { start: line.len(), matches: Vec::new() };
+    let mut rest = line.trim_start().trim_start_matches(':').trim_start();
+    if parse_range(&mut rest).is_err() { return none; }
+    rest = rest.trim_start();
+
+    let name_len = rest.find(|ch: char| !ch.is_ascii_alphabetic()).unwrap_or(rest.len());
+    let (name, after) = rest.split_at(name_len);
+    if after.is_empty() {
+        let mut matches: Vec<(String, String)> = commands.iter()
+            .filter(|spec| !spec.name.is_empty() && spec.name.starts_with(name))
+            .map(|spec| (spec.name.to_string(), spec.description.to_string()))
+            .collect();
+        matches.sort();
+        matches.dedup_by(|a, b| a.0 == b.0);
+        return Completion { start: line.len() - rest.len(), matches };
+    }
+
+    let Some(spec) = commands.iter().find(|spec| spec.matches(name)) else { return none };
+    let after = after.strip_prefix('!').unwrap_or(after);
+    if !after.starts_with(char::is_whitespace) { return none; }
+    let arg = after.trim_start();
+    let start = line.len() - arg.len();
+
+    match spec.arg {
+        ArgKind::Path => Completion { start, matches: complete_path(arg) },
+        ArgKind::Buffer => {
+            let matches = buffers.iter()
+                .filter(|buffer| buffer.starts_with(arg))
+                .map(|buffer| (buffer.clone(), String::new()))
+                .collect();
+            Completion { start, matches }
+        }
+        ArgKind::Options => {
+            // last option, after a space not escaped with `\`
+            let mut word = 0;
+            let mut escaped = false;
+            for (i, ch) in arg.char_indices() {
+                if ch == ' ' && !escaped { word = i + 1; }
+                escaped = ch == '\\' && !escaped;
+            }
+            Completion { start: start + word, matches: complete_option(&arg[word..]) }
+        }
+        _ => none,
+    }
+}
+
+/// Names of options starting with word, `no` ones for booleans, or their values after `=`
+fn complete_option(word: &str) -> Vec<(String, String)> {
+    if let Some((name, value)) = word.split_once('=') {
+        let Some((_, info)) = find_option(name) else { return Vec::new() };
+        let values: Vec<&str> = match info.kind {
+            OptionKind::Choice(choices) => choices.to_vec(),
+            _ if info.name == "colorscheme" => themes().names(),
+            _ => Vec::new(),
+        };
+        return values.into_iter()
+            .filter(|v| v.starts_with(value))
+            .map(|v| (format!("{}={}", name, v), String::new()))
+            .collect();
+    }
+
+    let mut matches: Vec<(String, String)> = Vec::new();
+    for info in OPTIONS {
+        if info.name.starts_with(word) {
+            matches.push((info.name.to_string(), info.description.to_string()));
+        }
+        let negated = format!("no{}", info.name);
+        if info.kind == OptionKind::Bool && word.starts_with("no") && negated.starts_with(word) {
+            matches.push((negated, format!("Turn off: {}", info.description)));
+        }
+    }
+    matches.sort();
+    matches
+}
+
+/// Files and directories starting with typed, directories ending with `/`.
+/// Hidden ones only when typed names a hidden file
+fn complete_path(typed: &str) -> Vec<(String, String)> {
+    let (dir, file) = typed.split_at(typed.rfind('/').map_or(0, |i| i + 1));
+    let entries = std::fs::read_dir(if dir.is_empty() { PathBuf::from(".") } else { expand_home(dir) });
+    let Ok(entries) = entries else { return Vec::new() };
+
+    let mut matches: Vec<(String, String)> = entries.flatten().filter_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(file) || (name.starts_with('.') && !file.starts_with('.')) { return None; }
+        let match_ = match entry.path().is_dir() {
+            true => (format!("{}{}/", dir, name), "directory".to_string()),
+            false => (format!("{}{}", dir, name), String::new()),
+        };
+        Some(match_)
+    }).collect();
+    matches.sort();
+    matches
+}
+
+/// Range at the start of rest, moving past it
+fn parse_range(rest: &mut &str) -> Result<Option<LineRange>, String> {
+    if let Some(after) = rest.strip_prefix('%') {
+        *rest = after;
+        return Ok(Some(LineRange { start: Address::Line(1, 0), end: Address::Last(0) }));
+    }
+
+    let Some(start) = parse_address(rest)? else { return Ok(None) };
+    let end = match rest.strip_prefix(',') {
+        Some(after) => {
+            *rest = after;
+            parse_address(rest)?.unwrap_or(Address::Current(0))
+        }
+        None => start,
+    };
+    Ok(Some(LineRange { start, end }))
+}
+
+/// Address at the start of rest, like `12`, `.`, `$-1` or `+2`, moving past it
+fn parse_address(rest: &mut &str) -> Result<Option<Address>, String> {
+    let digits = |text: &str| text.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(text.len());
+
+    let (mut address, mut after) = match rest.chars().next() {
+        Some('.') => (Address::Current(0), &rest[1..]),
+        Some('$') => (Address::Last(0), &rest[1..]),
+        Some(ch) if ch.is_ascii_digit() => {
+            let len = digits(rest);
+            let line = rest[..len].parse().map_err(|_| format!("Line number too large: {}", &rest[..len]))?;
+            (Address::Line(line, 0), &rest[len..])
+        }
+        Some('+' | '-') => (Address::Current(0), *rest),
+        _ => return Ok(None),
+    };
+
+    while let Some(sign @ ('+' | '-')) = after.chars().next() {
+        let len = digits(&after[1..]);
+        let n: isize = if len == 0 { 1 } else {
+            after[1..len + 1].parse().map_err(|_| format!("Offset too large: {}", &after[1..len + 1]))?
+        };
+        let n = if sign == '-' { -n } else { n };
+        match &mut address {
+            Address::Line(_, offset) | Address::Current(offset) | Address::Last(offset) => *offset += n,
+        }
+        after = &after[len + 1..];
+    }
+
+    *rest = after;
+    Ok(Some(address))
+}
+
+/// Path typed, with a leading `~` replaced by the home directory
+fn expand_home(text: &str) -> PathBuf {
+    let home = std::env::var_os("HOME");
+    match (text.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(text),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WRITE: CommandSpec = CommandSpec::new("write", "w", "").bang().arg(ArgKind::Path);
+    const WQ: CommandSpec = CommandSpec::new("wq", "wq", "");
+    const SET: CommandSpec = CommandSpec::new("set", "se", "").arg(ArgKind::Options);
+    const SETFILE: CommandSpec = CommandSpec::new("setfile", "setf", "").required(ArgKind::Options);
+    const GOTO: CommandSpec = CommandSpec::new("", "", "").range();
+    const INDENT: CommandSpec = CommandSpec::new(">", ">", "").range();
+    const COMMANDS: &[&CommandSpec] = &[&WRITE, &WQ, &SET, &SETFILE, &GOTO, &INDENT];
+
+    #[test]
+    fn parses_names_and_arguments() {
+        let write = parse("w", COMMANDS).unwrap();
+        assert_eq!((write.name(), write.bang, write.path()), ("write", false, None));
+        let write = parse(":wri! out.txt ", COMMANDS).unwrap();
+        assert_eq!((write.name(), write.bang, write.path()), ("write", true, Some(Path::new("out.txt"))));
+        assert_eq!(parse("wq", COMMANDS).unwrap().name(), "wq");
+
+        assert_eq!(parse("set ts=4 showbreak=>\\ ", COMMANDS).unwrap().options(), ["ts=4", "showbreak=>\\ "]);
+        assert!(parse("se", COMMANDS).unwrap().options().is_empty());
+        assert_eq!(parse("setf ts=2", COMMANDS).unwrap().name(), "setfile");
+
+        assert_eq!(parse("s", COMMANDS), Err("Not a command: s".into()));
+        assert_eq!(parse("writes", COMMANDS), Err("Not a command: writes".into()));
+        assert_eq!(parse("wq!", COMMANDS), Err("wq takes no !".into()));
+        assert_eq!(parse("wq now", COMMANDS), Err("Trailing characters: now".into()));
+        assert_eq!(parse("setfile", COMMANDS), Err("setfile needs an argument".into()));
+        assert_eq!(parse("3set", COMMANDS), Err("set takes no range".into()));
+        assert_eq!(parse("", COMMANDS), Err("No command".into()));
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let range = |line: &str| parse(line, COMMANDS).unwrap().range.unwrap();
+        let goto = parse("10", COMMANDS).unwrap();
+        assert_eq!(goto.name(), "");
+        assert_eq!(goto.range.unwrap().resolve(0, 20), (9, 9));
+
+        // cursor on line index 4 of 20
+        assert_eq!(range("%>").resolve(4, 20), (0, 19));
+        assert_eq!(range(".,+3>").resolve(4, 20), (4, 7));
+        assert_eq!(range("$-2,$").resolve(4, 20), (17, 19));
+        assert_eq!(range("-").resolve(4, 20), (3, 3));
+        assert_eq!(range("8,2").resolve(4, 20), (1, 7));
+        assert_eq!(range("50").resolve(4, 20), (19, 19));
+        assert_eq!(range("3,>").resolve(4, 20), (2, 4));
+        assert_eq!(parse("3,5>", COMMANDS).unwrap().name(), ">");
+    }
+
+    #[test]
+    fn completes_names_and_arguments() {
+        const EDIT: CommandSpec = CommandSpec::new("edit", "e", "").required(ArgKind::Path);
+        const BUFFER: CommandSpec = CommandSpec::new("buffer", "b", "").required(ArgKind::Buffer);
+        let commands: &[&CommandSpec] = &[&EDIT, &BUFFER, &SET, &SETFILE, &GOTO];
+        let texts = |completion: Completion| completion.matches.into_iter().map(|(text, _)| text).collect::<Vec<_>>();
+
+        assert_eq!(texts(complete(":se", commands, &[])), ["set", "setfile"]);
+        assert_eq!(complete("3,4se", commands, &[]).start, 3);
+        assert_eq!(texts(complete("b ma", commands, &["main.rs".into(), "lib.rs".into()])), ["main.rs"]);
+        assert!(complete("wq ", commands, &[]).matches.is_empty());
+
+        let completion = complete("set ts=2 showbreak=\\  rel", commands, &[]);
+        assert_eq!((completion.start, texts(completion)), (22, vec!["relativenumber".to_string()]));
+        assert_eq!(texts(complete("setf nonu", commands, &[])), ["nonumber"]);
+        assert_eq!(texts(complete("se fdm=i", commands, &[])), ["fdm=indent"]);
+
+        let dir = std::env::temp_dir().join(format!("demys-complete-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("scratch.txt"), "").unwrap();
+        std::fs::write(dir.join(".hidden"), "").unwrap();
+        let typed = format!("e {}/s", dir.display());
+        let completion = complete(&typed, commands, &[]);
+        assert_eq!(completion.start, 2);
+        assert_eq!(texts(completion), [format!("{}/scratch.txt", dir.display()), format!("{}/src/", dir.display())]);
+        assert_eq!(texts(complete(&format!("e {}/", dir.display()), commands, &[])).len(), 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn writes_usage() {
+        assert_eq!(WRITE.usage(), "w[rite][!] [file]");
+        assert_eq!(SETFILE.usage(), "setf[ile] {option}");
+        assert_eq!(INDENT.usage(), "[range]>");
+    }
+}
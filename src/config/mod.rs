@@ -0,0 +1,44 @@
+//! Reading settings and definition files
+mod parse;
+mod settings;
+mod write;
+
+pub use parse::*;
+pub use settings::*;
+pub use write::*;
+
+use std::path::PathBuf;
+
+/// Directory of user settings and definitions, `$XDG_CONFIG_HOME/demys` or `~/.config/demys`
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("demys"))
+}
+
+/// Directory of state kept between sessions, `$XDG_STATE_HOME/demys` or `~/.local/state/demys`
+pub fn state_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))?;
+    Some(base.join("demys"))
+}
+
+/// Contents of the `.toml` files in a directory of the config directory, sorted by path
+pub fn read_definitions(dir: &str) -> Vec<(PathBuf, std::io::Result<String>)> {
+    let Some(dir) = config_dir().map(|config| config.join(dir)) else { return Vec::new() };
+    let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+    let mut paths: Vec<PathBuf> = entries.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+
+    paths.into_iter().map(|path| {
+        let source = std::fs::read_to_string(&path);
+        (path, source)
+    }).collect()
+}
@@ -0,0 +1,366 @@
+//! Reader for the subset of TOML used by definition and settings files.
+//! Supports tables, arrays of tables, dotted headers, strings, integers, booleans and arrays
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Display;
+
+pub type Table = BTreeMap<String, Value>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        if let Value::String(s) = self { Some(s) } else { None }
+    }
+    pub fn as_integer(&self) -> Option<i64> {
+        if let Value::Integer(i) = self { Some(*i) } else { None }
+    }
+    pub fn as_bool(&self) -> Option<bool> {
+        if let Value::Bool(b) = self { Some(*b) } else { None }
+    }
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        if let Value::Array(a) = self { Some(a) } else { None }
+    }
+    pub fn as_table(&self) -> Option<&Table> {
+        if let Value::Table(t) = self { Some(t) } else { None }
+    }
+    /// Array of strings, skipping other values
+    pub fn as_strings(&self) -> Vec<String> {
+        self.as_array()
+            .map(|a| a.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    /// Line number, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+impl Error for ParseError {}
+
+/// Parse a whole file into its root table
+pub fn parse(source: &str) -> Result<Table, ParseError> {
+    Parser { chars: source.chars().collect(), pos: 0, line: 1 }.document()
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn document(&mut self) -> Result<Table, ParseError> {
+        let mut root = Table::new();
+        // path of the table keys are currently added to, and whether it's the last of an array
+        let mut current: Vec<String> = Vec::new();
+
+        loop {
+            self.skip_blank_lines();
+            let Some(ch) = self.peek() else { break };
+
+            if ch == '[' {
+                let array = self.peek_at(1) == Some('[');
+                self.pos += if array { 2 } else { 1 };
+                let path = self.key_path()?;
+                self.expect(']')?;
+                if array { self.expect(']')?; }
+
+                self.open_table(&mut root, &path, array)?;
+                current = path;
+            } else {
+                let path = self.key_path()?;
+                self.skip_space();
+                self.expect('=')?;
+                self.skip_space();
+                let value = self.value()?;
+
+                let line = self.line;
+                let table = Self::table_at(&mut root, &current)
+                    .ok_or_else(|| ParseError { line, message: "invalid table".into() })?;
+                Self::insert(table, &path, value, line)?;
+            }
+
+            self.end_of_line()?;
+        }
+        Ok(root)
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, ParseError> {
+        Err(ParseError { line: self.line, message: message.to_string() })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += 1;
+        if ch == '\n' { self.line += 1; }
+        Some(ch)
+    }
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        if self.peek() == Some(expected) {
+            self.next();
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", expected))
+        }
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(' ') | Some('\t')) { self.pos += 1; }
+    }
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while self.peek().is_some_and(|c| c != '\n') { self.pos += 1; }
+        }
+    }
+    /// Skip whitespace, linebreaks and comments
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_space();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n') | Some('\r') => { self.next(); }
+                _ => break
+            }
+        }
+    }
+    fn end_of_line(&mut self) -> Result<(), ParseError> {
+        self.skip_space();
+        self.skip_comment();
+        match self.peek() {
+            None | Some('\n') | Some('\r') => Ok(()),
+            _ => self.error("expected end of line"),
+        }
+    }
+
+    /// Dotted key like a.b."c d"
+    fn key_path(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_space();
+            let key = match self.peek() {
+                Some('"') | Some('\'') => self.string()?,
+                _ => {
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-') { self.pos += 1; }
+                    if start == self.pos { return self.error("expected key"); }
+                    self.chars[start..self.pos].iter().collect()
+                }
+            };
+            path.push(key);
+            self.skip_space();
+            if self.peek() != Some('.') { break; }
+            self.pos += 1;
+        }
+        Ok(path)
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some('"') | Some('\'') => Ok(Value::String(self.string()?)),
+            Some('[') => self.array(),
+            Some('t') | Some('f') => {
+                let word: String = self.chars[self.pos..].iter().take_while(|c| c.is_alphabetic()).collect();
+                let value = match word.as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return self.error("expected value"),
+                };
+                self.pos += word.len();
+                Ok(Value::Bool(value))
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' => {
+                let text: String = self.chars[self.pos..].iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '_'))
+                    .collect();
+                self.pos += text.chars().count();
+                let clean = text.replace('_', "");
+                let parsed = if let Some(hex) = clean.strip_prefix("0x") {
+                    i64::from_str_radix(hex, 16).ok()
+                } else {
+                    clean.parse::<i64>().ok()
+                };
+                match parsed {
+                    Some(i) => Ok(Value::Integer(i)),
+                    None => self.error("invalid number"),
+                }
+            }
+            _ => self.error("expected value"),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank_lines();
+            if self.peek() == Some(']') { break; }
+            items.push(self.value()?);
+            self.skip_blank_lines();
+            match self.peek() {
+                Some(',') => { self.pos += 1; }
+                Some(']') => break,
+                _ => return self.error("expected ',' or ']'"),
+            }
+        }
+        self.expect(']')?;
+        Ok(Value::Array(items))
+    }
+
+    /// Basic "string" with escapes, literal 'string', or either one tripled to span lines
+    fn string(&mut self) -> Result<String, ParseError> {
+        let quote = self.next().unwrap_or('"');
+        let triple = self.peek() == Some(quote) && self.peek_at(1) == Some(quote);
+        if triple {
+            self.pos += 2;
+            // linebreak right after the opening quotes is skipped
+            if self.peek() == Some('\n') { self.next(); }
+        }
+
+        let mut out = String::new();
+        loop {
+            let Some(ch) = self.next() else { return self.error("unterminated string") };
+            if ch == quote {
+                if !triple { break; }
+                if self.peek() == Some(quote) && self.peek_at(1) == Some(quote) {
+                    self.pos += 2;
+                    break;
+                }
+            }
+            if ch == '\n' && !triple { return self.error("unterminated string"); }
+
+            if ch == '\\' && quote == '"' {
+                let escaped = match self.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('\\') => '\\',
+                    Some('"') => '"',
+                    Some('u') => {
+                        let hex: String = self.chars[self.pos..].iter().take(4).collect();
+                        self.pos += 4;
+                        match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                            Some(c) => c,
+                            None => return self.error("invalid unicode escape"),
+                        }
+                    }
+                    _ => return self.error("invalid escape"),
+                };
+                out.push(escaped);
+            } else {
+                out.push(ch);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Create the table at path, or add a table to the array at path
+    fn open_table(&self, root: &mut Table, path: &[String], array: bool) -> Result<(), ParseError> {
+        let (last, parents) = path.split_last().expect("key path is never empty");
+        let Some(parent) = Self::table_at(root, parents) else { return self.error("invalid table"); };
+
+        if array {
+            match parent.entry(last.clone()).or_insert_with(|| Value::Array(Vec::new())) {
+                Value::Array(items) => items.push(Value::Table(Table::new())),
+                _ => return self.error(&format!("'{}' is not an array", last)),
+            }
+        } else {
+            match parent.entry(last.clone()).or_insert_with(|| Value::Table(Table::new())) {
+                Value::Table(_) => (),
+                _ => return self.error(&format!("'{}' is not a table", last)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Table at path, creating missing ones. Arrays of tables resolve to their last table
+    fn table_at<'a>(root: &'a mut Table, path: &[String]) -> Option<&'a mut Table> {
+        let mut table = root;
+        for key in path {
+            let value = table.entry(key.clone()).or_insert_with(|| Value::Table(Table::new()));
+            table = match value {
+                Value::Table(t) => t,
+                Value::Array(items) => match items.last_mut() {
+                    Some(Value::Table(t)) => t,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+        }
+        Some(table)
+    }
+
+    fn insert(table: &mut Table, path: &[String], value: Value, line: usize) -> Result<(), ParseError> {
+        let (last, parents) = path.split_last().expect("key path is never empty");
+        let table = Self::table_at(table, parents)
+            .ok_or_else(|| ParseError { line, message: "invalid key".into() })?;
+        if table.contains_key(last) {
+            return Err(ParseError { line, message: format!("duplicate key '{}'", last) });
+        }
+        table.insert(last.clone(), value);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let table = parse("a = \"x\\ty\" # comment\nb = 'c:\\\\path'\nc = -12\nd = true\ne = [1, 2,\n  3, # trailing\n]\n").unwrap();
+        assert_eq!(table["a"], Value::String("x\ty".into()));
+        assert_eq!(table["b"], Value::String("c:\\\\path".into()));
+        assert_eq!(table["c"], Value::Integer(-12));
+        assert_eq!(table["d"], Value::Bool(true));
+        assert_eq!(table["e"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn parses_tables() {
+        let source = "name = \"x\"\n\n[colors]\nfg = \"red\"\n\n[a.b]\nc = 1\n\n[[rule]]\nid = 1\n[[rule]]\nid = 2\n";
+        let table = parse(source).unwrap();
+        assert_eq!(table["colors"].as_table().unwrap()["fg"], Value::String("red".into()));
+        assert_eq!(table["a"].as_table().unwrap()["b"].as_table().unwrap()["c"], Value::Integer(1));
+
+        let rules = table["rule"].as_array().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1].as_table().unwrap()["id"], Value::Integer(2));
+    }
+
+    #[test]
+    fn multiline_strings() {
+        let table = parse("a = \"\"\"\nfirst\nsecond\"\"\"\n").unwrap();
+        assert_eq!(table["a"], Value::String("first\nsecond".into()));
+    }
+
+    #[test]
+    fn reports_line_of_error() {
+        assert_eq!(parse("a = 1\nb = \n").unwrap_err().line, 2);
+        assert_eq!(parse("a = 1\na = 2").unwrap_err().line, 2);
+        assert!(parse("a = \"open").is_err());
+        assert!(parse("a = 1 b").is_err());
+    }
+}
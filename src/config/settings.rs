@@ -0,0 +1,712 @@
+//! Editor settings: every option, its current value, and the files setting them
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use crate::config::{config_dir, parse, quote, set_key, state_dir, Table, Value};
+use crate::style::ThemeColor;
+
+/// Values an option takes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OptionKind {
+    Bool,
+    Number,
+    Text,
+    Color,
+    /// One of the listed names
+    Choice(&'static [&'static str]),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum OptionValue {
+    Bool(bool),
+    Number(usize),
+    /// Text and choice options
+    Text(String),
+    Color(ThemeColor),
+}
+
+impl OptionValue {
+    /// Value of kind written as in `:set`
+    pub fn parse(kind: OptionKind, text: &str) -> Result<Self, String> {
+        match kind {
+            OptionKind::Bool => match text {
+                "true" | "on" | "yes" | "1" => Ok(OptionValue::Bool(true)),
+                "false" | "off" | "no" | "0" => Ok(OptionValue::Bool(false)),
+                _ => Err(format!("expected true or false, got '{}'", text)),
+            },
+            OptionKind::Number => text.parse().map(OptionValue::Number)
+                .map_err(|_| format!("expected a number, got '{}'", text)),
+            OptionKind::Text => Ok(OptionValue::Text(text.to_string())),
+            OptionKind::Color => ThemeColor::parse(text).map(OptionValue::Color)
+                .ok_or_else(|| format!("expected a color, got '{}'", text)),
+            OptionKind::Choice(choices) => match choices.contains(&text) {
+                true => Ok(OptionValue::Text(text.to_string())),
+                false => Err(format!("expected one of {}, got '{}'", choices.join(", "), text)),
+            },
+        }
+    }
+
+    /// Value of kind read from the config file
+    pub fn from_config(kind: OptionKind, value: &Value) -> Result<Self, String> {
+        match (kind, value) {
+            (OptionKind::Bool, Value::Bool(b)) => Ok(OptionValue::Bool(*b)),
+            (OptionKind::Number, Value::Integer(i)) => usize::try_from(*i).map(OptionValue::Number)
+                .map_err(|_| format!("expected a positive number, got {}", i)),
+            (OptionKind::Color, Value::Integer(i)) => u8::try_from(*i).map(|i| OptionValue::Color(ThemeColor::Indexed(i)))
+                .map_err(|_| format!("expected a palette index, got {}", i)),
+            (OptionKind::Text | OptionKind::Color | OptionKind::Choice(_), Value::String(s)) => Self::parse(kind, s),
+            (OptionKind::Bool, _) => Err("expected true or false".into()),
+            (OptionKind::Number, _) => Err("expected a number".into()),
+            (OptionKind::Color, _) => Err("expected a color name, \"#rrggbb\" or palette index".into()),
+            (OptionKind::Text | OptionKind::Choice(_), _) => Err("expected a string".into()),
+        }
+    }
+}
+
+impl OptionValue {
+    /// Value as read from a config file
+    pub fn to_config(&self) -> Value {
+        match self {
+            OptionValue::Bool(b) => Value::Bool(*b),
+            OptionValue::Number(n) => Value::Integer(*n as i64),
+            OptionValue::Text(text) => Value::String(text.clone()),
+            OptionValue::Color(color) => Value::String(color.to_string()),
+        }
+    }
+    /// Value written in a config file
+    pub fn to_toml(&self) -> String {
+        match self {
+            OptionValue::Bool(_) | OptionValue::Number(_) => self.to_string(),
+            OptionValue::Text(_) | OptionValue::Color(_) => quote(&self.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionValue::Bool(b) => write!(f, "{}", b),
+            OptionValue::Number(n) => write!(f, "{}", n),
+            OptionValue::Text(text) => f.write_str(text),
+            OptionValue::Color(color) => write!(f, "{}", color),
+        }
+    }
+}
+
+/// Description of an option
+pub struct OptionInfo {
+    pub name: &'static str,
+    /// Short names accepted by `:set`
+    pub aliases: &'static [&'static str],
+    /// Subsystem using it, also its table in the config file
+    pub group: &'static str,
+    pub kind: OptionKind,
+    /// Default, written as in `:set`
+    pub default: &'static str,
+    pub description: &'static str,
+}
+
+impl OptionInfo {
+    pub fn default_value(&self) -> OptionValue {
+        OptionValue::parse(self.kind, self.default).expect("invalid option default")
+    }
+}
+
+const fn option(name: &'static str, aliases: &'static [&'static str], group: &'static str, kind: OptionKind, default: &'static str, description: &'static str) -> OptionInfo {
+    OptionInfo { name, aliases, group, kind, default, description }
+}
+
+use OptionKind::*;
+
+/// Every option, grouped by subsystem
+pub const OPTIONS: &[OptionInfo] = &[
+    option("colorscheme", &["colo"], "display", Text, "default", "Color theme"),
+    option("termcolors", &[], "display", Choice(&["auto", "truecolor", "256", "16", "none"]), "auto", "Colors the terminal shows, detected when auto"),
+
+    option("number", &["nu"], "editor", Bool, "true", "Show line numbers"),
+    option("relativenumber", &["rnu"], "editor", Bool, "false", "Show line numbers relative to the cursor"),
+    option("signcolumn", &["scl"], "editor", Bool, "false", "Show a column for signs"),
+    option("tabstop", &["ts"], "editor", Number, "4", "Columns a tab takes"),
+    option("expandtab", &["et"], "editor", Bool, "true", "Indent with spaces"),
+    option("shiftwidth", &["sw"], "editor", Number, "4", "Columns of one indent level"),
+    option("autoindent", &["ai"], "editor", Bool, "true", "New lines keep the indent of the previous one"),
+    option("smartindent", &["si"], "editor", Bool, "true", "Indent after opening brackets"),
+    option("autopairs", &[], "editor", Bool, "true", "Close brackets and quotes as they are typed"),
+    option("showmatch", &["sm"], "editor", Bool, "true", "Highlight the bracket matching the one at the cursor"),
+    option("scrolloff", &["so"], "editor", Number, "3", "Rows kept visible above and below the cursor"),
+    option("sidescrolloff", &["siso"], "editor", Number, "5", "Columns kept visible left and right of the cursor"),
+    option("wrap", &[], "editor", Bool, "true", "Wrap long lines"),
+    option("linebreak", &["lbr"], "editor", Bool, "true", "Wrap at word boundaries"),
+    option("breakindent", &["bri"], "editor", Bool, "true", "Indent wrapped rows like their line"),
+    option("showbreak", &["sbr"], "editor", Text, "", "Text shown at the start of wrapped rows"),
+    option("foldmethod", &["fdm"], "editor", Choice(&["manual", "indent", "marker"]), "manual", "How folds are made"),
+
+    option("dynamiccaret", &[], "caret", Bool, "true", "Caret color follows the mode"),
+    option("normalcolor", &[], "caret", Color, "gray", "Caret color in normal mode"),
+    option("insertcolor", &[], "caret", Color, "blue", "Caret color in insert mode"),
+    option("visualcolor", &[], "caret", Color, "magenta", "Caret color in visual mode"),
+    option("replacecolor", &[], "caret", Color, "yellow", "Caret color in replace mode"),
+
+    option("selectioncolor", &[], "colors", Color, "selection", "Background of selected text"),
+    option("numbercolor", &[], "colors", Color, "green", "Line number color"),
+    option("matchcolor", &[], "colors", Color, "yellow", "Matching bracket color"),
+    option("foldcolor", &[], "colors", Color, "dark_blue", "Closed fold color"),
+
+    option("showtabs", &[], "tabs", Bool, "true", "Show the tab bar"),
+
+    option("leader", &[], "keys", Text, "\\", "Keys `<leader>` stands for in bindings"),
+    option("timeoutlen", &["tm"], "keys", Number, "1000", "Milliseconds to wait for the next key of a binding"),
+    option("keyhints", &[], "keys", Bool, "true", "Show the keys that may follow a half typed sequence"),
+    option("hintdelay", &[], "keys", Number, "500", "Milliseconds before key hints show"),
+
+    option("history", &["hi"], "prompt", Number, "100", "Command lines kept in the prompt history"),
+
+    option("alertmargin", &[], "alert", Number, "2", "Space around alert text"),
+    option("alertborder", &[], "alert", Bool, "true", "Draw a border around alerts"),
+    option("alertbackground", &[], "alert", Color, "background", "Alert background color"),
+];
+
+/// Position and description of the option called name or one of its aliases
+pub fn find_option(name: &str) -> Option<(usize, &'static OptionInfo)> {
+    OPTIONS.iter().enumerate().find(|(_, info)| info.name == name || info.aliases.contains(&name))
+}
+
+/// Value of every option, in OPTIONS order, and the key bindings of the config files
+#[derive(Clone, PartialEq, Debug)]
+pub struct Settings {
+    values: Vec<OptionValue>,
+    /// `[keys.<context>]` tables, by context
+    bindings: Table,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { values: OPTIONS.iter().map(OptionInfo::default_value).collect(), bindings: Table::new() }
+    }
+}
+
+impl Settings {
+    pub fn get(&self, name: &str) -> Option<&OptionValue> {
+        find_option(name).map(|(i, _)| &self.values[i])
+    }
+    pub fn bool(&self, name: &str) -> bool {
+        matches!(self.get(name), Some(OptionValue::Bool(true)))
+    }
+    pub fn number(&self, name: &str) -> usize {
+        match self.get(name) {
+            Some(OptionValue::Number(n)) => *n,
+            _ => 0,
+        }
+    }
+    pub fn text(&self, name: &str) -> &str {
+        match self.get(name) {
+            Some(OptionValue::Text(text)) => text,
+            _ => "",
+        }
+    }
+    pub fn color(&self, name: &str) -> ThemeColor {
+        match self.get(name) {
+            Some(OptionValue::Color(color)) => *color,
+            _ => ThemeColor::Primary,
+        }
+    }
+
+    /// Set option by name, the value being of its kind
+    pub fn set(&mut self, name: &str, value: OptionValue) -> Result<(), String> {
+        let (i, info) = find_option(name).ok_or_else(|| format!("Unknown option '{}'", name))?;
+        if std::mem::discriminant(&value) != std::mem::discriminant(&info.default_value()) {
+            return Err(format!("Invalid value '{}' for {}", value, info.name));
+        }
+        self.values[i] = value;
+        Ok(())
+    }
+
+    /// Key bindings read from config files, a table of keys to actions for each context
+    pub fn bindings(&self) -> &Table {
+        &self.bindings
+    }
+
+    /// Options differing from their default
+    pub fn changed(&self) -> Vec<(&'static OptionInfo, &OptionValue)> {
+        OPTIONS.iter().zip(self.values.iter())
+            .filter(|(info, value)| info.default_value() != **value)
+            .collect()
+    }
+
+    /// Apply one argument of `:set`. Returns a message to show for queries:
+    /// - `name=value` sets an option
+    /// - `name` turns a boolean on, `noname` off, `name!` or `invname` toggles it
+    /// - `name?`, or `name` for other kinds, shows the value
+    /// - `name&` resets to the default
+    pub fn apply_set(&mut self, arg: &str) -> Result<Option<String>, String> {
+        if let Some((name, value)) = arg.split_once('=').or_else(|| arg.split_once(':')) {
+            let (_, info) = lookup(name.trim())?;
+            let value = OptionValue::parse(info.kind, &unescape(value))
+                .map_err(|e| format!("{}: {}", info.name, e))?;
+            self.set(info.name, value)?;
+            return Ok(None);
+        }
+
+        if let Some(name) = arg.strip_suffix('?') {
+            let (i, info) = lookup(name)?;
+            return Ok(Some(format!("{}={}", info.name, self.values[i])));
+        }
+        if let Some(name) = arg.strip_suffix('&') {
+            let (i, info) = lookup(name)?;
+            self.values[i] = info.default_value();
+            return Ok(None);
+        }
+
+        let toggled = arg.strip_suffix('!').or_else(|| arg.strip_prefix("inv"));
+        if let Some((i, info)) = toggled.and_then(find_option).filter(|(_, info)| info.kind == Bool) {
+            self.values[i] = match self.values[i] {
+                OptionValue::Bool(b) => OptionValue::Bool(!b),
+                _ => info.default_value(),
+            };
+            return Ok(None);
+        }
+
+        match find_option(arg) {
+            Some((i, info)) if info.kind == Bool => self.values[i] = OptionValue::Bool(true),
+            Some((i, info)) => return Ok(Some(format!("{}={}", info.name, self.values[i]))),
+            None => {
+                let (i, _) = arg.strip_prefix("no").and_then(find_option)
+                    .filter(|(_, info)| info.kind == Bool)
+                    .ok_or_else(|| format!("Unknown option '{}'", arg))?;
+                self.values[i] = OptionValue::Bool(false);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Apply the options of a parsed config file, either at the top or in their group's table.
+    /// Tables in `[keys]` add to the key bindings of their context.
+    /// Returns the options that could not be set
+    pub fn apply_table(&mut self, table: &Table) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (key, value) in table {
+            match value {
+                Value::Table(group) if OPTIONS.iter().any(|info| info.group == key) => {
+                    for (name, value) in group {
+                        if let (Value::Table(bindings), "keys") = (value, key.as_str()) {
+                            self.add_bindings(name, bindings);
+                            continue;
+                        }
+                        match find_option(name) {
+                            Some((i, info)) if info.group == key => self.apply_value(i, info, value, &mut errors),
+                            _ => errors.push(format!("unknown option '{}.{}'", key, name)),
+                        }
+                    }
+                }
+                value => match find_option(key) {
+                    Some((i, info)) => self.apply_value(i, info, value, &mut errors),
+                    None => errors.push(format!("unknown option '{}'", key)),
+                }
+            }
+        }
+        errors
+    }
+
+    fn add_bindings(&mut self, context: &str, bindings: &Table) {
+        let entry = self.bindings.entry(context.to_string()).or_insert_with(|| Value::Table(Table::new()));
+        if let Value::Table(table) = entry {
+            table.extend(bindings.iter().map(|(keys, action)| (keys.clone(), action.clone())));
+        }
+    }
+
+    fn apply_value(&mut self, i: usize, info: &OptionInfo, value: &Value, errors: &mut Vec<String>) {
+        match OptionValue::from_config(info.kind, value) {
+            Ok(value) => self.values[i] = value,
+            Err(e) => errors.push(format!("{}: {}", info.name, e)),
+        }
+    }
+}
+
+/// Option an argument of `:set` is about
+pub fn option_of_set_arg(arg: &str) -> Option<(usize, &'static OptionInfo)> {
+    let name = arg.split(['=', ':']).next().unwrap_or(arg).trim();
+    let name = name.trim_end_matches(['?', '&', '!']);
+    find_option(name)
+        .or_else(|| name.strip_prefix("no").and_then(find_option))
+        .or_else(|| name.strip_prefix("inv").and_then(find_option))
+}
+
+/// Apply one argument of a `:set` saving to a layer. Returns the option and the value to save,
+/// None to remove it from the layer:
+/// - `name` saves the current value
+/// - `name&` removes it, so the value of the layer below is used
+/// - anything else changes the value as `:set` does, then saves it
+pub fn apply_saved_set(settings: &mut Settings, arg: &str) -> Result<(&'static OptionInfo, Option<OptionValue>), String> {
+    let (i, info) = option_of_set_arg(arg).ok_or_else(|| format!("Unknown option '{}'", arg))?;
+    if arg.ends_with('&') {
+        return Ok((info, None));
+    }
+    if find_option(arg).is_none() || arg.ends_with('?') {
+        settings.apply_set(arg)?;
+    }
+    Ok((info, Some(settings.values[i].clone())))
+}
+
+fn lookup(name: &str) -> Result<(usize, &'static OptionInfo), String> {
+    find_option(name).ok_or_else(|| format!("Unknown option '{}'", name))
+}
+
+/// Trim value, `\ ` keeping a space that would otherwise be trimmed
+fn unescape(value: &str) -> String {
+    let value = value.trim_start();
+    let value = if value.ends_with("\\ ") { value } else { value.trim_end() };
+    value.replace("\\ ", " ")
+}
+
+/// Arguments of `:set`, split at spaces not escaped with `\`
+pub fn split_set_args(args: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = args.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if chars.peek() == Some(&' ') => {
+                parts.last_mut().unwrap().push_str("\\ ");
+                chars.next();
+            }
+            ' ' | '\t' => parts.push(String::new()),
+            ch => parts.last_mut().unwrap().push(ch),
+        }
+    }
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// Where settings are saved. Each layer overrides the ones before it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Layer {
+    /// The config file
+    Global,
+    /// The project file of the working directory
+    Project,
+    /// Overrides for a single file, kept in the state directory
+    File,
+}
+
+impl Layer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Global => "global",
+            Layer::Project => "project",
+            Layer::File => "file",
+        }
+    }
+}
+
+/// Groups of options a single file can override
+pub const FILE_GROUPS: [&str; 3] = ["editor", "caret", "colors"];
+
+/// Name of the project settings file
+pub const PROJECT_FILE: &str = ".demys.toml";
+
+/// Path of the global config file, `config.toml` in the config directory
+pub fn config_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// Project file in dir or the closest of its parents
+pub fn find_project_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|dir| dir.join(PROJECT_FILE)).find(|path| path.is_file())
+}
+
+/// Project file settings are saved to, in dir if there is none yet
+pub fn project_file(dir: &Path) -> PathBuf {
+    find_project_file(dir).unwrap_or_else(|| dir.join(PROJECT_FILE))
+}
+
+/// File keeping the overrides of single files, by path
+pub fn file_settings_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("file-settings.toml"))
+}
+
+/// Parsed contents of a settings file, nothing if it doesn't exist
+fn read_table(path: &Path) -> Result<Table, String> {
+    if !path.exists() { return Ok(Table::new()); }
+    let source = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Options set by each layer, as tables of a settings file, applied over the built-in defaults in order.
+/// Overrides of single files are kept by the window of the file, over these
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Layers {
+    pub global: Table,
+    pub project: Table,
+    /// Options changed with `:set` or on the settings page, for this session only
+    pub session: Table,
+}
+
+impl Layers {
+    const fn new() -> Self {
+        Layers { global: Table::new(), project: Table::new(), session: Table::new() }
+    }
+
+    /// Built-in defaults with every layer applied
+    pub fn merge(&self) -> Settings {
+        let mut settings = Settings::default();
+        for table in [&self.global, &self.project, &self.session] {
+            settings.apply_table(table);
+        }
+        settings
+    }
+
+    /// Change option for this session, or go back to the saved value with None
+    pub fn set_session(&mut self, info: &OptionInfo, value: Option<&OptionValue>) {
+        for name in std::iter::once(&info.name).chain(info.aliases) {
+            self.session.remove(*name);
+        }
+        if let Some(value) = value {
+            self.session.insert(info.name.to_string(), value.to_config());
+        }
+    }
+
+    /// Apply one argument of `:set` to this session, as [Settings::apply_set] does,
+    /// except `name&` goes back to the value of the saved layers
+    pub fn apply_set(&mut self, arg: &str) -> Result<Option<String>, String> {
+        let (_, info) = option_of_set_arg(arg).ok_or_else(|| format!("Unknown option '{}'", arg))?;
+        if arg.ends_with('&') {
+            self.set_session(info, None);
+            return Ok(None);
+        }
+
+        let mut settings = self.merge();
+        let message = settings.apply_set(arg)?;
+        if message.is_none() {
+            self.set_session(info, settings.get(info.name));
+        }
+        Ok(message)
+    }
+
+    /// Save option to the file of layer, or remove it from there when value is None.
+    /// The saved layers decide the option's value from then on, over a change for this session
+    pub fn save(&mut self, layer: Layer, dir: &Path, info: &OptionInfo, value: Option<&OptionValue>) -> Result<(), String> {
+        let path = layer_file(layer, dir)?;
+        save_to(&path, &[Some(info.group), None], info, value)?;
+        let table = read_table(&path)?;
+        match layer {
+            Layer::Global => self.global = table,
+            _ => self.project = table,
+        }
+        self.set_session(info, None);
+        Ok(())
+    }
+}
+
+/// Read the config file, then the project file found from dir, into the current layers.
+/// Returns what went wrong reading them
+pub fn load_settings(dir: &Path) -> Vec<String> {
+    let mut layers = Layers::default();
+    let mut errors = Vec::new();
+    for (path, table) in [(config_file(), &mut layers.global), (find_project_file(dir), &mut layers.project)] {
+        let Some(path) = path else { continue };
+        match read_table(&path) {
+            Ok(read) => {
+                errors.extend(Settings::default().apply_table(&read).into_iter().map(|e| format!("{}: {}", path.display(), e)));
+                *table = read;
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    set_layers(layers);
+    errors
+}
+
+/// Key a file's overrides are kept under
+fn file_key(file: &Path) -> String {
+    file.canonicalize().unwrap_or_else(|_| file.to_path_buf()).display().to_string()
+}
+
+/// Options overridden for file
+pub fn file_overrides(file: &Path) -> Result<Table, String> {
+    let Some(path) = file_settings_file() else { return Ok(Table::new()) };
+    let mut table = read_table(&path)?;
+    match table.remove(&file_key(file)) {
+        Some(Value::Table(overrides)) => Ok(overrides),
+        _ => Ok(Table::new()),
+    }
+}
+
+/// Write option's value to a settings file, or remove it when value is None
+fn save_to(path: &Path, tables: &[Option<&str>], info: &OptionInfo, value: Option<&OptionValue>) -> Result<(), String> {
+    let source = if path.exists() {
+        std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?
+    } else {
+        String::new()
+    };
+    let mut keys = vec![info.name];
+    keys.extend_from_slice(info.aliases);
+    let source = set_key(&source, tables, &keys, value.map(OptionValue::to_toml).as_deref());
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    std::fs::write(path, source).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The config file, or the project file found from dir
+fn layer_file(layer: Layer, dir: &Path) -> Result<PathBuf, String> {
+    match layer {
+        Layer::Global => config_file().ok_or_else(|| "no config directory".into()),
+        Layer::Project => Ok(project_file(dir)),
+        Layer::File => Err("saving to a file needs its path".into()),
+    }
+}
+
+/// Save option as an override for file
+pub fn save_file_option(file: &Path, info: &OptionInfo, value: Option<&OptionValue>) -> Result<(), String> {
+    if !FILE_GROUPS.contains(&info.group) {
+        return Err(format!("{} can't be set for a single file", info.name));
+    }
+    let path = file_settings_file().ok_or("no state directory")?;
+    save_to(&path, &[Some(&quote(&file_key(file)))], info, value)
+}
+
+static CURRENT: RwLock<Option<Arc<Settings>>> = RwLock::new(None);
+
+/// Settings windows are currently made with
+pub fn current_settings() -> Arc<Settings> {
+    static DEFAULT: OnceLock<Arc<Settings>> = OnceLock::new();
+    let current = CURRENT.read().ok().and_then(|current| current.clone());
+    current.unwrap_or_else(|| DEFAULT.get_or_init(|| Arc::new(Settings::default())).clone())
+}
+
+pub fn set_settings(settings: Settings) {
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Some(Arc::new(settings));
+    }
+}
+
+static LAYERS: RwLock<Layers> = RwLock::new(Layers::new());
+
+/// Layers the current settings are merged from
+pub fn current_layers() -> Layers {
+    LAYERS.read().map(|layers| layers.clone()).unwrap_or_default()
+}
+
+/// Make layers the current ones, and what they merge to the current settings
+pub fn set_layers(layers: Layers) {
+    set_settings(layers.merge());
+    if let Ok(mut current) = LAYERS.write() {
+        *current = layers;
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        let settings = Settings::default();
+        assert_eq!(settings.number("tabstop"), 4);
+        assert!(settings.bool("nu"));
+        assert_eq!(settings.text("foldmethod"), "manual");
+        assert_eq!(settings.color("alertbackground"), ThemeColor::Background);
+        assert!(settings.changed().is_empty());
+    }
+
+    #[test]
+    fn set_arguments() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.apply_set("ts=8"), Ok(None));
+        assert_eq!(settings.apply_set("nonumber"), Ok(None));
+        assert_eq!(settings.apply_set("rnu!"), Ok(None));
+        assert_eq!(settings.apply_set("showbreak=>>\\ "), Ok(None));
+        assert_eq!(settings.apply_set("numbercolor=#ff0000"), Ok(None));
+        assert_eq!(settings.number("tabstop"), 8);
+        assert!(!settings.bool("number"));
+        assert!(settings.bool("relativenumber"));
+        assert_eq!(settings.text("showbreak"), ">> ");
+        assert_eq!(settings.color("numbercolor"), ThemeColor::Rgb(255, 0, 0));
+
+        assert_eq!(settings.apply_set("tabstop?"), Ok(Some("tabstop=8".into())));
+        assert_eq!(settings.apply_set("ts"), Ok(Some("tabstop=8".into())));
+        assert_eq!(settings.apply_set("ts&"), Ok(None));
+        assert_eq!(settings.number("tabstop"), 4);
+
+        assert!(settings.apply_set("ts=four").is_err());
+        assert!(settings.apply_set("foldmethod=syntax").is_err());
+        assert!(settings.apply_set("nope").is_err());
+        assert!(settings.apply_set("nots").is_err());
+
+        assert_eq!(split_set_args(" ts=2  showbreak=+\\ x nu"), vec!["ts=2", "showbreak=+\\ x", "nu"]);
+    }
+
+    #[test]
+    fn saved_set_arguments() {
+        let mut settings = Settings::default();
+        let (info, value) = apply_saved_set(&mut settings, "ts").unwrap();
+        assert_eq!((info.name, value), ("tabstop", Some(OptionValue::Number(4))));
+        let (info, value) = apply_saved_set(&mut settings, "nowrap").unwrap();
+        assert_eq!((info.name, value), ("wrap", Some(OptionValue::Bool(false))));
+        let (info, value) = apply_saved_set(&mut settings, "sbr=>\\ ").unwrap();
+        assert_eq!((info.name, value), ("showbreak", Some(OptionValue::Text("> ".into()))));
+        // the layer below decides once it is removed
+        let (info, value) = apply_saved_set(&mut settings, "wrap&").unwrap();
+        assert_eq!((info.name, value), ("wrap", None));
+        assert!(apply_saved_set(&mut settings, "nope").is_err());
+    }
+
+    #[test]
+    fn layers_override_in_order() {
+        let mut layers = Layers {
+            global: parse("tabstop = 8\nwrap = false\n").unwrap(),
+            project: parse("[editor]\nts = 2\n").unwrap(),
+            session: Table::new(),
+        };
+        assert_eq!(layers.merge().number("tabstop"), 2);
+
+        assert_eq!(layers.apply_set("ts=3"), Ok(None));
+        assert_eq!(layers.apply_set("wrap!"), Ok(None));
+        assert_eq!((layers.merge().number("tabstop"), layers.merge().bool("wrap")), (3, true));
+        assert_eq!(layers.apply_set("ts?"), Ok(Some("tabstop=3".into())));
+
+        // back to the project's value, not the default
+        assert_eq!(layers.apply_set("ts&"), Ok(None));
+        assert_eq!(layers.merge().number("tabstop"), 2);
+        layers.project.clear();
+        assert_eq!(layers.merge().number("tabstop"), 8);
+        assert!(layers.apply_set("nope&").is_err());
+    }
+
+    #[test]
+    fn saves_to_layer() {
+        let dir = std::env::temp_dir().join(format!("demys-layers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (_, tabstop) = find_option("ts").unwrap();
+        let mut layers = Layers::default();
+        layers.apply_set("ts=3").unwrap();
+
+        layers.save(Layer::Project, &dir, tabstop, Some(&OptionValue::Number(2))).unwrap();
+        assert_eq!(read_table(&dir.join(PROJECT_FILE)).unwrap(), parse("[editor]\ntabstop = 2\n").unwrap());
+        // the saved value replaces the one of this session
+        assert_eq!(layers.merge().number("tabstop"), 2);
+
+        layers.save(Layer::Project, &dir, tabstop, None).unwrap();
+        assert_eq!(layers.merge().number("tabstop"), 4);
+        assert!(layers.save(Layer::File, &dir, tabstop, None).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn config_tables() {
+        let table = parse("tabstop = 2\ncolorscheme = \"nord\"\n\n[alert]\nalertmargin = 1\nnumber = true\n\n[tabs]\nshowtabs = \"no\"\n\n[editor]\nnope = 1\n").unwrap();
+        let mut settings = Settings::default();
+        let errors = settings.apply_table(&table);
+
+        assert_eq!(settings.number("tabstop"), 2);
+        assert_eq!(settings.text("colorscheme"), "nord");
+        assert_eq!(settings.number("alertmargin"), 1);
+        assert!(settings.bool("showtabs"));
+        assert_eq!(errors, vec![
+            "unknown option 'alert.number'".to_string(),
+            "unknown option 'editor.nope'".to_string(),
+            "showtabs: expected true or false".to_string(),
+        ]);
+    }
+}
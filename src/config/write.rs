@@ -0,0 +1,118 @@
+//! Changing single keys of a settings file, keeping the rest of it as written
+
+/// Key or table name written as a basic string
+pub fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Name of the table a header line opens, None if it isn't one
+fn header(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.starts_with("[[") { return Some(line); }
+    let inner = line.strip_prefix('[')?;
+    // a comment may follow the closing bracket
+    let end = inner.rfind(']')?;
+    Some(inner[..end].trim())
+}
+
+/// Key a line assigns to, None for blank lines, comments and headers
+fn assigned_key(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with('[') { return None; }
+    let (key, _) = line.split_once('=')?;
+    Some(key.trim().trim_matches(|c| c == '"' || c == '\''))
+}
+
+/// Set key to value, written as toml, or remove it when value is None.
+/// The key is looked for under each of tables, None being the top of the file.
+/// When it isn't found it is added to the first of tables, which is created if missing
+pub fn set_key(source: &str, tables: &[Option<&str>], keys: &[&str], value: Option<&str>) -> String {
+    let mut lines: Vec<String> = source.lines().map(str::to_string).collect();
+    let assignment = |value: &str| format!("{} = {}", keys[0], value);
+
+    // table each line is in
+    let mut table = None;
+    let mut tables_of = Vec::with_capacity(lines.len());
+    for line in &lines {
+        if let Some(name) = header(line) { table = Some(name.to_string()); }
+        tables_of.push(table.clone());
+    }
+    let in_table = |i: usize, wanted: &Option<&str>| tables_of[i].as_deref() == *wanted && header(&lines[i]).is_none();
+
+    // replace existing
+    for wanted in tables {
+        let found = (0..lines.len()).find(|i| in_table(*i, wanted) && assigned_key(&lines[*i]).is_some_and(|k| keys.contains(&k)));
+        if let Some(i) = found {
+            match value {
+                Some(value) => lines[i] = assignment(value),
+                None => { lines.remove(i); }
+            }
+            return join(lines);
+        }
+    }
+
+    let Some(value) = value else { return join(lines) };
+    let target = tables.first().copied().flatten();
+
+    // add after the last assignment of the table
+    let last = (0..lines.len()).rev().find(|i| in_table(*i, &target) && assigned_key(&lines[*i]).is_some());
+    let opened = (0..lines.len()).find(|i| header(&lines[*i]).is_some_and(|name| Some(name) == target));
+    match (last, opened, target) {
+        (Some(i), _, _) => lines.insert(i + 1, assignment(value)),
+        (None, Some(i), _) => lines.insert(i + 1, assignment(value)),
+        // top of the file, before any table
+        (None, None, None) => {
+            let first_table = (0..lines.len()).find(|i| header(&lines[*i]).is_some()).unwrap_or(lines.len());
+            lines.insert(first_table, assignment(value));
+        }
+        (None, None, Some(name)) => {
+            if lines.last().is_some_and(|line| !line.trim().is_empty()) { lines.push(String::new()); }
+            lines.push(format!("[{}]", name));
+            lines.push(assignment(value));
+        }
+    }
+    join(lines)
+}
+
+fn join(lines: Vec<String>) -> String {
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{parse, Value};
+
+    const SOURCE: &str = "# my settings\ncolorscheme = \"nord\"\n\n[editor]\ntabstop = 2 # narrow\nwrap = false\n\n[alert]\nalertmargin = 1\n";
+
+    #[test]
+    fn replaces_keys() {
+        let out = set_key(SOURCE, &[Some("editor"), None], &["tabstop", "ts"], Some("8"));
+        assert!(out.contains("# my settings\n"));
+        assert!(out.contains("[editor]\ntabstop = 8\nwrap = false\n"));
+
+        let out = set_key(SOURCE, &[Some("display"), None], &["colorscheme"], Some("\"gruvbox\""));
+        assert!(out.starts_with("# my settings\ncolorscheme = \"gruvbox\"\n"));
+
+        let out = set_key(SOURCE, &[Some("editor"), None], &["wrap"], None);
+        assert!(!out.contains("wrap"));
+    }
+
+    #[test]
+    fn adds_keys() {
+        let out = set_key(SOURCE, &[Some("editor"), None], &["number"], Some("true"));
+        assert!(out.contains("wrap = false\nnumber = true\n\n[alert]"));
+
+        let out = set_key(SOURCE, &[Some("tabs"), None], &["showtabs"], Some("false"));
+        assert!(out.ends_with("alertmargin = 1\n\n[tabs]\nshowtabs = false\n"));
+
+        let path = quote("/home/me/a \"b\".txt");
+        let out = set_key("", &[Some(&path)], &["wrap"], Some("false"));
+        let table = parse(&out).unwrap();
+        let file = table.get("/home/me/a \"b\".txt").and_then(Value::as_table).unwrap();
+        assert_eq!(file.get("wrap"), Some(&Value::Bool(false)));
+    }
+}
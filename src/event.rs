@@ -3,6 +3,8 @@
 use std::cell::{Ref, RefCell};
 use std::error::Error;
 use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::RwLock;
 
 
 
@@ -116,6 +118,34 @@ impl<T: Sized, U: UniqueNext> EventPoster<T,U> {
 }
 
 
+
+
+
+// WAKEUPS
+
+
+/// What wakes the main loop up from other threads, which can't hold an EventPoster
+pub enum Wake {
+    Input(crossterm::event::Event),
+    /// Results came in that windows take on collecting requests
+    Collect,
+}
+
+static WAKER: RwLock<Option<Sender<Wake>>> = RwLock::new(None);
+
+/// Receiver the main loop waits on, replacing any made before
+pub fn wake_channel() -> Receiver<Wake> {
+    let (sender, receiver) = channel();
+    *WAKER.write().unwrap() = Some(sender);
+    receiver
+}
+
+/// Sender to wake the main loop from another thread, None while nothing listens
+pub fn waker() -> Option<Sender<Wake>> {
+    WAKER.read().unwrap().clone()
+}
+
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -4,6 +4,7 @@ use std::io::Cursor;
 use std::path::{Path, PathBuf};
 use crossterm::event::{KeyCode, KeyModifiers};
 use crate::event::{EventPoster, Uuid};
+use crate::keymap::{Action, Feed, Key, KeyHints, KeyMatcher};
 use crate::plot::Plot;
 use crate::style::{Canvas, StyleAttribute, StyledText, ThemeColor};
 use crate::textedit::textwindow::TextWindow;
@@ -180,31 +181,49 @@ pub struct FSWindow {
     line: usize,
     dir: DirectoryRep,
     poster: Option<EventPoster<WindowRequest,Uuid>>,
+    /// Keys typed towards an explorer binding
+    keys: KeyMatcher,
     focused: bool,
 }
 
+/// Keymap of the explorer
+const EXPLORER: &str = "explorer";
+
 
 // FILE SYSTEM TAB IMPL
 // allows navigation of filesystem to open files
 impl FSWindow {
     pub fn new(dir: PathBuf) -> FSWindow {
-        FSWindow { line: 0, dir: dir.into(), poster: None, focused: false }
+        FSWindow { line: 0, dir: dir.into(), poster: None, keys: KeyMatcher::default(), focused: false }
     }
     fn input(&mut self, key: KeyCode, modifiers: KeyModifiers) {
-        match key {
-            KeyCode::Char('j') => {
+        let fed = self.keys.feed(EXPLORER, Key::new(key, modifiers));
+        self.run_keys(fed);
+    }
+    fn run_keys(&mut self, fed: Vec<Feed>) {
+        for fed in fed {
+            match fed {
+                Feed::Action(Action::Named(action)) => self.run_action(action),
+                Feed::Action(Action::Command(command)) => self.poster.as_mut().unwrap().post(WindowRequest::Command(command)),
+                Feed::Unbound(_) => (),
+            }
+        }
+    }
+    fn run_action(&mut self, action: &str) {
+        match action {
+            "explorer.down" => {
                 let target = self.line+1;
 
                 if self.dir.map_line_child(target).is_some() {
                     self.line = target;
                 }
             }
-            KeyCode::Char('k') => {
+            "explorer.up" => {
                 if self.line > 0 {
                     self.line -= 1;
                 }
             }
-            KeyCode::Enter => {
+            "explorer.open" => {
                 if let Some(item) = self.dir.map_line_child(self.line) {
                     if item.is_dir {
                         item.toggle();
@@ -260,6 +279,20 @@ impl Window for FSWindow {
     fn init(&mut self, poster: EventPoster<WindowRequest, Uuid>) {
         self.poster = Some(poster);
     }
+    fn input_bypass(&self) -> bool {
+        self.keys.is_pending()
+    }
+    fn key_hints(&self) -> Option<KeyHints> {
+        self.keys.hints(EXPLORER)
+    }
+    fn collect_requests(&mut self) -> Vec<WindowRequest> {
+        let fed = self.keys.timed_out(EXPLORER);
+        if !fed.is_empty() {
+            self.run_keys(fed);
+            self.poster.as_mut().unwrap().post(WindowRequest::Redraw);
+        }
+        Vec::new()
+    }
     fn event(&mut self, event: WindowEvent) {
         match event {
             WindowEvent::Focus => self.focused = true,
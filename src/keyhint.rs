@@ -0,0 +1,103 @@
+//! Popup listing the keys that may follow a half typed sequence
+use crate::keymap::KeyHints;
+use crate::plot::Plot;
+use crate::popup::{PopUp, PopUpDimension, PopUpDimensionOption, PopUpPosition, PopUpPositionOption};
+use crate::style::{Canvas, StyleAttribute, StyledText, ThemeColor};
+use crate::window::Window;
+
+/// Columns each key and its description take
+const ENTRY_WIDTH: usize = 30;
+
+/// Keys that may follow the typed ones in columns, at the bottom of the screen.
+/// Shown over the windows without taking keys, which go on to finish the sequence
+pub struct KeyHintPopup {
+    hints: KeyHints,
+    /// Entries on each row
+    columns: usize,
+}
+
+impl KeyHintPopup {
+    /// Popup for hints, laid out to fit in dim. Entries that don't fit are left out
+    pub fn new(mut hints: KeyHints, dim: Plot) -> Self {
+        let columns = (dim.col / ENTRY_WIDTH).max(1);
+        let max_rows = (dim.row / 2).max(1);
+        hints.next.truncate(columns * max_rows);
+        KeyHintPopup { hints, columns }
+    }
+
+    pub fn hints(&self) -> &KeyHints {
+        &self.hints
+    }
+
+    /// Rows of the popup, with the typed keys on the first
+    fn height(&self) -> usize {
+        1 + self.hints.next.len().div_ceil(self.columns)
+    }
+}
+
+impl Window for KeyHintPopup {
+    fn draw(&self, canvas: &mut Canvas) {
+        let _ = canvas.set_attribute(StyleAttribute::BgColor(ThemeColor::StatusBar),
+            Plot::new(0, 0),
+            Plot::new(canvas.last_row(), canvas.last_col() + 1));
+
+        let typed = StyledText::new(self.hints.typed.clone()).with(StyleAttribute::Bold(true));
+        let _ = canvas.write_at(&typed, Plot::new(0, 1));
+
+        let width = canvas.get_dim().col;
+        for (i, (keys, description)) in self.hints.next.iter().enumerate() {
+            let col = 1 + (i % self.columns) * ENTRY_WIDTH;
+            let room = (col + ENTRY_WIDTH).min(width.saturating_sub(1)).saturating_sub(col);
+            let keys: String = keys.chars().take(room).collect();
+            let description: String = description.chars().take(room.saturating_sub(keys.chars().count() + 1)).collect();
+            let at = Plot::new(1 + i / self.columns, col);
+
+            let _ = canvas.write_at(&StyledText::new(keys.clone()).with(StyleAttribute::Color(ThemeColor::Yellow)), at);
+            if !description.is_empty() {
+                let _ = canvas.write_at(&StyledText::new(description).with(StyleAttribute::Color(ThemeColor::Comment)),
+                    at + Plot::new(0, keys.chars().count() + 1));
+            }
+        }
+    }
+}
+
+impl PopUp for KeyHintPopup {
+    fn position(&self) -> PopUpPosition {
+        PopUpPosition {
+            row: PopUpPositionOption::PositiveBound(self.height() as isize),
+            col: PopUpPositionOption::NegativeBound(0),
+        }
+    }
+    fn dimension(&self) -> PopUpDimension {
+        PopUpDimension {
+            row: PopUpDimensionOption::Fixed(self.height()),
+            col: PopUpDimensionOption::Percent(1.0),
+        }
+    }
+    fn local(&self) -> bool {
+        false
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lays_out_in_columns() {
+        let next = (0..7).map(|i| (i.to_string(), format!("action {}", i))).collect();
+        let popup = KeyHintPopup::new(KeyHints { typed: "g".into(), next }, Plot::new(20, 100));
+        assert_eq!(popup.columns, 3);
+        assert_eq!(popup.height(), 4);
+
+        let mut canvas = Canvas::new(Plot::new(popup.height(), 100));
+        popup.draw(&mut canvas);
+        assert_eq!(popup.term_pos(&Plot::new(20, 100)), Plot::new(16, 0));
+
+        // only as many rows as half the screen
+        let next = (0..20).map(|i| (i.to_string(), String::new())).collect();
+        let popup = KeyHintPopup::new(KeyHints { typed: "g".into(), next }, Plot::new(6, 30));
+        assert_eq!(popup.hints().next.len(), 3);
+    }
+}
@@ -0,0 +1,168 @@
+//! Keys and the `<C-x>` notation bindings are written in
+use std::fmt;
+use crossterm::event::{KeyCode, KeyModifiers};
+
+/// Key pressed with its modifiers
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+/// Names of special keys inside `<>`
+const NAMES: &[(&str, KeyCode)] = &[
+    ("Esc", KeyCode::Esc),
+    ("Tab", KeyCode::Tab),
+    ("S-Tab", KeyCode::BackTab),
+    ("BS", KeyCode::Backspace),
+    ("CR", KeyCode::Enter),
+    ("Enter", KeyCode::Enter),
+    ("Space", KeyCode::Char(' ')),
+    ("lt", KeyCode::Char('<')),
+    ("Bslash", KeyCode::Char('\\')),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Del", KeyCode::Delete),
+    ("Insert", KeyCode::Insert),
+];
+
+impl Key {
+    /// Key as the terminal reports it. Shift is left out of chars, which are already upper case
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers = match code {
+            KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+            KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Key { code, modifiers }
+    }
+
+    pub fn char(ch: char) -> Self {
+        Key::new(KeyCode::Char(ch), KeyModifiers::NONE)
+    }
+
+    /// Char typed without control or alt
+    pub fn as_char(&self) -> Option<char> {
+        match self.code {
+            KeyCode::Char(ch) if !self.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => Some(ch),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut prefix = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) { prefix += "C-"; }
+        if self.modifiers.contains(KeyModifiers::ALT) { prefix += "A-"; }
+        if self.modifiers.contains(KeyModifiers::SHIFT) { prefix += "S-"; }
+
+        let name = match self.code {
+            KeyCode::Char(ch) if !matches!(ch, ' ' | '<' | '\\') => {
+                if prefix.is_empty() { return write!(f, "{}", ch); }
+                ch.to_string()
+            }
+            KeyCode::F(n) => format!("F{}", n),
+            code => NAMES.iter().find(|(_, c)| *c == code).map_or("?", |(name, _)| name).to_string(),
+        };
+        write!(f, "<{}{}>", prefix, name)
+    }
+}
+
+/// Keys written like `gg`, `<C-w>j` or `<leader>f`, the leader being replaced by its keys
+pub fn parse_keys(text: &str, leader: &[Key]) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        let special = (ch == '<').then(|| rest[1..].find('>')).flatten();
+        match special {
+            Some(end) if end > 0 => {
+                let name = &rest[1..end + 1];
+                if name.eq_ignore_ascii_case("leader") {
+                    keys.extend_from_slice(leader);
+                } else {
+                    keys.push(parse_special(name).ok_or_else(|| format!("unknown key <{}>", name))?);
+                }
+                rest = &rest[end + 2..];
+            }
+            _ => {
+                keys.push(Key::char(ch));
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+    }
+    if keys.is_empty() { return Err("no keys".into()); }
+    Ok(keys)
+}
+
+/// Key named inside `<>`, with modifiers like `C-` `A-` `M-` `S-`
+fn parse_special(name: &str) -> Option<Key> {
+    if let Some((_, code)) = NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+        return Some(Key::new(*code, KeyModifiers::NONE));
+    }
+
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    while rest.len() > 2 && rest.as_bytes()[1] == b'-' {
+        modifiers |= match rest.as_bytes()[0].to_ascii_uppercase() {
+            b'C' => KeyModifiers::CONTROL,
+            b'A' | b'M' => KeyModifiers::ALT,
+            b'S' => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        rest = &rest[2..];
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(ch), None) if modifiers.contains(KeyModifiers::CONTROL) => KeyCode::Char(ch.to_ascii_lowercase()),
+        (Some(ch), None) => KeyCode::Char(ch),
+        _ if rest.len() > 1 && (rest.starts_with('F') || rest.starts_with('f')) => KeyCode::F(rest[1..].parse().ok()?),
+        _ => NAMES.iter().find(|(n, _)| n.eq_ignore_ascii_case(rest))?.1,
+    };
+    if code == KeyCode::Tab && modifiers.contains(KeyModifiers::SHIFT) {
+        return Some(Key::new(KeyCode::BackTab, modifiers));
+    }
+    Some(Key::new(code, modifiers))
+}
+
+/// Keys written back in notation
+pub fn keys_to_string(keys: &[Key]) -> String {
+    keys.iter().map(Key::to_string).collect()
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_notation() {
+        let ctrl = |ch| Key::new(KeyCode::Char(ch), KeyModifiers::CONTROL);
+        assert_eq!(parse_keys("gg", &[]), Ok(vec![Key::char('g'), Key::char('g')]));
+        assert_eq!(parse_keys("<C-w>j", &[]), Ok(vec![ctrl('w'), Key::char('j')]));
+        assert_eq!(parse_keys("<C-X>", &[]), Ok(vec![ctrl('x')]));
+        assert_eq!(parse_keys("<leader>f", &[Key::char(' ')]), Ok(vec![Key::char(' '), Key::char('f')]));
+        assert_eq!(parse_keys("<Tab><S-Tab><esc>", &[]).map(|k| k.len()), Ok(3));
+        assert_eq!(parse_keys("<C-Right>", &[]), Ok(vec![Key::new(KeyCode::Right, KeyModifiers::CONTROL)]));
+        assert_eq!(parse_keys("<F5>", &[]), Ok(vec![Key::new(KeyCode::F(5), KeyModifiers::NONE)]));
+        assert_eq!(parse_keys("<", &[]), Ok(vec![Key::char('<')]));
+        assert!(parse_keys("<Nope>", &[]).is_err());
+        assert!(parse_keys("", &[]).is_err());
+    }
+
+    #[test]
+    fn writes_notation() {
+        for text in ["gg", "<C-w>j", "<Space>x", "<Tab>", "<C-Right>", "<lt>", "<A-x>", "<F12>"] {
+            assert_eq!(keys_to_string(&parse_keys(text, &[]).unwrap()), text);
+        }
+        // shift is part of the char
+        assert_eq!(Key::new(KeyCode::Char('G'), KeyModifiers::SHIFT), Key::char('G'));
+    }
+}
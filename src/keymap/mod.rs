@@ -0,0 +1,656 @@
+//! Named actions and the keys bound to them, for each mode and type of window
+mod key;
+
+pub use key::*;
+
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use crate::config::{current_settings, Settings, Table, Value};
+
+/// Where a keymap applies, with the actions it offers and their default keys
+pub struct Context {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Names and descriptions of the actions keys can be bound to
+    pub actions: &'static [(&'static str, &'static str)],
+    /// Keys and the action they are bound to
+    pub defaults: &'static [(&'static str, &'static str)],
+    /// Whether the keys of MOTIONS move the cursor too
+    pub motions: bool,
+}
+
+impl Context {
+    /// Default keys and actions, with the motions when the context has them
+    pub fn default_bindings(&self) -> impl Iterator<Item = &(&'static str, &'static str)> {
+        let motions: &[(&str, &str)] = if self.motions { MOTIONS } else { &[] };
+        self.defaults.iter().chain(motions)
+    }
+}
+
+const GLOBAL_ACTIONS: &[(&str, &str)] = &[
+    ("app.quit", "Ask every window to close"),
+    ("app.exit", "Exit right away"),
+    ("window.next", "Focus the next window"),
+    ("window.close", "Close the focused window"),
+    ("prompt.command", "Open the command prompt"),
+];
+
+const TAB_ACTIONS: &[(&str, &str)] = &[
+    ("tab.next", "Show the next tab"),
+    ("tab.toggle_bar", "Show or hide the tab bar"),
+    ("tab.split", "Move the tab to a window of its own"),
+];
+
+const PROMPT_ACTIONS: &[(&str, &str)] = &[
+    ("prompt.run", "Run the command"),
+    ("prompt.cancel", "Close the prompt"),
+    ("prompt.left", "Move the cursor a char left"),
+    ("prompt.right", "Move the cursor a char right"),
+    ("prompt.word_left", "Move the cursor a word left"),
+    ("prompt.word_right", "Move the cursor a word right"),
+    ("prompt.home", "Move the cursor to the start"),
+    ("prompt.end", "Move the cursor to the end"),
+    ("prompt.delete_back", "Delete the char before the cursor, or close an empty prompt"),
+    ("prompt.delete", "Delete the char at the cursor"),
+    ("prompt.delete_word", "Delete the word before the cursor"),
+    ("prompt.delete_line", "Delete the text before the cursor"),
+    ("prompt.older", "Older command starting with the typed text"),
+    ("prompt.newer", "Newer command starting with the typed text"),
+    ("prompt.complete", "Complete the word before the cursor, or take the next match"),
+    ("prompt.complete_previous", "Take the previous match"),
+];
+
+const EXPLORER_ACTIONS: &[(&str, &str)] = &[
+    ("explorer.down", "Select the next entry"),
+    ("explorer.up", "Select the previous entry"),
+    ("explorer.open", "Open the file or directory"),
+];
+
+const SETTINGS_ACTIONS: &[(&str, &str)] = &[
+    ("settings.down", "Select the next option"),
+    ("settings.up", "Select the previous option"),
+    ("settings.first", "Select the first option"),
+    ("settings.last", "Select the last option"),
+    ("settings.change", "Toggle or edit the option"),
+    ("settings.next", "Next value of the option"),
+    ("settings.previous", "Previous value of the option"),
+    ("settings.reset", "Reset the option to its saved value"),
+    ("settings.save", "Save the option to the config file"),
+    ("settings.save_project", "Save the option to the project file"),
+];
+
+const EDITOR_ACTIONS: &[(&str, &str)] = &[
+    ("editor.normal", "Back to normal mode"),
+    ("editor.insert", "Insert mode"),
+    ("editor.replace", "Replace mode"),
+    ("editor.visual", "Visual mode, or back from it"),
+    ("editor.visual_line", "Visual line mode, or back from it"),
+    ("editor.increment", "Add the count to the number at the cursor"),
+    ("editor.decrement", "Subtract the count from the number at the cursor"),
+    ("editor.scroll_down", "Scroll down by the count of rows"),
+    ("editor.scroll_up", "Scroll up by the count of rows"),
+    ("editor.half_page_down", "Scroll down half a page with the cursor"),
+    ("editor.half_page_up", "Scroll up half a page with the cursor"),
+    ("editor.page_down", "Scroll down a page"),
+    ("editor.page_up", "Scroll up a page"),
+    ("editor.undo", "Undo the last change"),
+    ("editor.indent", "Indent the line"),
+    ("editor.dedent", "Remove an indent level from the line"),
+    ("editor.toggle_case", "Toggle the case of the count of chars"),
+    ("editor.join", "Join the count of lines, removing indents"),
+    ("editor.join_raw", "Join lines without changing whitespace"),
+    ("editor.replace_char", "Replace the count of chars with the next one typed"),
+    ("editor.cursor_center", "Scroll the cursor line to the center"),
+    ("editor.cursor_top", "Scroll the cursor line to the top"),
+    ("editor.cursor_bottom", "Scroll the cursor line to the bottom"),
+    ("editor.toggle_fold", "Open or close the fold at the cursor"),
+    ("editor.open_fold", "Open the fold at the cursor"),
+    ("editor.close_fold", "Close the fold at the cursor"),
+    ("editor.delete_fold", "Delete the fold at the cursor"),
+    ("editor.open_folds", "Open every fold"),
+    ("editor.close_folds", "Close every fold"),
+    ("motion.left", "Left"),
+    ("motion.right", "Right"),
+    ("motion.up", "Up"),
+    ("motion.down", "Down"),
+    ("motion.line_start", "Line start"),
+    ("motion.first_non_blank", "First non-blank char of the line"),
+    ("motion.line_end", "Line end"),
+    ("motion.word_forward", "Next word"),
+    ("motion.word_backward", "Previous word"),
+    ("motion.word_end", "Word end"),
+    ("motion.file_start", "First line"),
+    ("motion.file_end", "Last line"),
+    ("motion.matching_bracket", "Matching bracket"),
+    ("operator.indent", "Indent, then a motion or the last key again for lines"),
+    ("operator.dedent", "Dedent, then a motion or the last key again for lines"),
+    ("operator.reindent", "Reindent, then a motion or the last key again for lines"),
+    ("operator.comment", "Toggle comments, then a motion or the last key again for lines"),
+    ("operator.lowercase", "Lowercase, then a motion or the last key again for lines"),
+    ("operator.uppercase", "Uppercase, then a motion or the last key again for lines"),
+    ("operator.toggle_case", "Toggle case, then a motion or the last key again for lines"),
+    ("operator.fold", "Fold, then a motion or the last key again for lines"),
+];
+
+/// Keys of the motions, in normal and visual modes. A count typed first repeats them
+const MOTIONS: &[(&str, &str)] = &[
+    ("h", "motion.left"),
+    ("l", "motion.right"),
+    ("k", "motion.up"),
+    ("j", "motion.down"),
+    ("0", "motion.line_start"),
+    ("^", "motion.first_non_blank"),
+    ("$", "motion.line_end"),
+    ("w", "motion.word_forward"),
+    ("b", "motion.word_backward"),
+    ("e", "motion.word_end"),
+    ("gg", "motion.file_start"),
+    ("G", "motion.file_end"),
+    ("%", "motion.matching_bracket"),
+];
+
+/// Every keymap, outer windows first
+pub const CONTEXTS: &[Context] = &[
+    Context {
+        name: "global",
+        description: "Everywhere, unless the focused window takes the key",
+        actions: GLOBAL_ACTIONS,
+        defaults: &[
+            ("<Esc>", "app.quit"),
+            ("<C-End>", "app.exit"),
+            ("<C-l>", "window.next"),
+            ("<C-w>w", "window.next"),
+            ("<C-w>q", "window.close"),
+            (":", "prompt.command"),
+        ],
+        motions: false,
+    },
+    Context {
+        name: "prompt",
+        description: "Command prompt, before typed text",
+        actions: PROMPT_ACTIONS,
+        defaults: &[
+            ("<Enter>", "prompt.run"),
+            ("<Esc>", "prompt.cancel"),
+            ("<C-c>", "prompt.cancel"),
+            ("<Left>", "prompt.left"),
+            ("<Right>", "prompt.right"),
+            ("<C-Left>", "prompt.word_left"),
+            ("<C-Right>", "prompt.word_right"),
+            ("<Home>", "prompt.home"),
+            ("<C-b>", "prompt.home"),
+            ("<End>", "prompt.end"),
+            ("<C-e>", "prompt.end"),
+            ("<BS>", "prompt.delete_back"),
+            ("<Del>", "prompt.delete"),
+            ("<C-w>", "prompt.delete_word"),
+            ("<C-u>", "prompt.delete_line"),
+            ("<Up>", "prompt.older"),
+            ("<Down>", "prompt.newer"),
+            ("<Tab>", "prompt.complete"),
+            ("<S-Tab>", "prompt.complete_previous"),
+        ],
+        motions: false,
+    },
+    Context {
+        name: "tabs",
+        description: "Windows holding tabs",
+        actions: TAB_ACTIONS,
+        defaults: &[
+            ("<Tab>", "tab.next"),
+            ("'", "tab.toggle_bar"),
+            ("<C-Right>", "tab.split"),
+        ],
+        motions: false,
+    },
+    Context {
+        name: "explorer",
+        description: "File explorer",
+        actions: EXPLORER_ACTIONS,
+        defaults: &[
+            ("j", "explorer.down"),
+            ("<Down>", "explorer.down"),
+            ("k", "explorer.up"),
+            ("<Up>", "explorer.up"),
+            ("<Enter>", "explorer.open"),
+        ],
+        motions: false,
+    },
+    Context {
+        name: "settings",
+        description: "Settings page",
+        actions: SETTINGS_ACTIONS,
+        defaults: &[
+            ("j", "settings.down"),
+            ("<Down>", "settings.down"),
+            ("k", "settings.up"),
+            ("<Up>", "settings.up"),
+            ("g", "settings.first"),
+            ("G", "settings.last"),
+            ("<Enter>", "settings.change"),
+            ("l", "settings.next"),
+            ("<Right>", "settings.next"),
+            ("h", "settings.previous"),
+            ("<Left>", "settings.previous"),
+            ("r", "settings.reset"),
+            ("s", "settings.save"),
+            ("p", "settings.save_project"),
+        ],
+        motions: false,
+    },
+    Context {
+        name: "normal",
+        description: "Editor in normal mode, after a count",
+        actions: EDITOR_ACTIONS,
+        defaults: &[
+            ("i", "editor.insert"),
+            ("R", "editor.replace"),
+            ("v", "editor.visual"),
+            ("V", "editor.visual_line"),
+            ("<C-a>", "editor.increment"),
+            ("<C-x>", "editor.decrement"),
+            ("<C-e>", "editor.scroll_down"),
+            ("<C-y>", "editor.scroll_up"),
+            ("<C-d>", "editor.half_page_down"),
+            ("<C-u>", "editor.half_page_up"),
+            ("<C-f>", "editor.page_down"),
+            ("<C-b>", "editor.page_up"),
+            ("u", "editor.undo"),
+            ("~", "editor.toggle_case"),
+            ("J", "editor.join"),
+            ("gJ", "editor.join_raw"),
+            ("r", "editor.replace_char"),
+            ("zz", "editor.cursor_center"),
+            ("zt", "editor.cursor_top"),
+            ("zb", "editor.cursor_bottom"),
+            ("za", "editor.toggle_fold"),
+            ("zo", "editor.open_fold"),
+            ("zc", "editor.close_fold"),
+            ("zd", "editor.delete_fold"),
+            ("zR", "editor.open_folds"),
+            ("zM", "editor.close_folds"),
+            (">", "operator.indent"),
+            ("<lt>", "operator.dedent"),
+            ("=", "operator.reindent"),
+            ("gc", "operator.comment"),
+            ("gu", "operator.lowercase"),
+            ("gU", "operator.uppercase"),
+            ("g~", "operator.toggle_case"),
+            ("zf", "operator.fold"),
+        ],
+        motions: true,
+    },
+    Context {
+        name: "insert",
+        description: "Editor in insert mode, before typed text",
+        actions: EDITOR_ACTIONS,
+        defaults: &[
+            ("<Esc>", "editor.normal"),
+            ("<C-[>", "editor.normal"),
+            ("<C-z>", "editor.undo"),
+            ("<C-t>", "editor.indent"),
+            ("<C-d>", "editor.dedent"),
+        ],
+        motions: false,
+    },
+    Context {
+        name: "visual",
+        description: "Editor in visual modes, operators acting on the selection",
+        actions: EDITOR_ACTIONS,
+        defaults: &[
+            ("<Esc>", "editor.normal"),
+            ("<C-[>", "editor.normal"),
+            ("v", "editor.visual"),
+            ("V", "editor.visual_line"),
+            (">", "operator.indent"),
+            ("<lt>", "operator.dedent"),
+            ("=", "operator.reindent"),
+            ("gc", "operator.comment"),
+            ("u", "operator.lowercase"),
+            ("gu", "operator.lowercase"),
+            ("U", "operator.uppercase"),
+            ("gU", "operator.uppercase"),
+            ("~", "operator.toggle_case"),
+            ("g~", "operator.toggle_case"),
+            ("zf", "operator.fold"),
+        ],
+        motions: true,
+    },
+    Context {
+        name: "replace",
+        description: "Editor in replace mode, before typed text",
+        actions: EDITOR_ACTIONS,
+        defaults: &[
+            ("<Esc>", "editor.normal"),
+            ("<C-[>", "editor.normal"),
+        ],
+        motions: false,
+    },
+];
+
+/// What keys do
+#[derive(Clone, PartialEq, Debug)]
+pub enum Action {
+    /// One of the actions of the keymap's context
+    Named(&'static str),
+    /// Command run as if typed in the command prompt
+    Command(String),
+}
+
+/// Description of action in context, the command for commands
+pub fn describe(context: &str, action: &Action) -> String {
+    match action {
+        Action::Named(name) => CONTEXTS.iter()
+            .find(|c| c.name == context)
+            .and_then(|c| c.actions.iter().find(|(action, _)| action == name))
+            .map_or(name.to_string(), |(_, description)| description.to_string()),
+        Action::Command(command) => format!(":{}", command),
+    }
+}
+
+/// Keys typed towards a binding or command, and the keys that may follow them
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyHints {
+    pub typed: String,
+    /// Following keys and what they do
+    pub next: Vec<(String, String)>,
+}
+
+/// Result of looking up keys in a keymap
+#[derive(PartialEq, Debug)]
+pub enum Lookup<'a> {
+    /// Bound to action, longer bindings starting with the keys too
+    Exact(&'a Action, bool),
+    /// Only longer bindings start with the keys
+    Prefix,
+    None,
+}
+
+/// Keys bound to actions in one context
+#[derive(Default, Debug)]
+pub struct Keymap {
+    bindings: Vec<(Vec<Key>, Action)>,
+}
+
+impl Keymap {
+    pub fn lookup(&self, keys: &[Key]) -> Lookup<'_> {
+        let exact = self.bindings.iter().find(|(bound, _)| bound == keys).map(|(_, action)| action);
+        let longer = self.bindings.iter().any(|(bound, _)| bound.len() > keys.len() && bound.starts_with(keys));
+        match (exact, longer) {
+            (Some(action), longer) => Lookup::Exact(action, longer),
+            (None, true) => Lookup::Prefix,
+            (None, false) => Lookup::None,
+        }
+    }
+
+    /// Keys bound to the named action
+    pub fn keys_of<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a [Key]> {
+        self.bindings.iter()
+            .filter(move |(_, action)| matches!(action, Action::Named(bound) if *bound == name))
+            .map(|(keys, _)| keys.as_slice())
+    }
+
+    /// Bindings longer than prefix starting with it, by the keys after it
+    pub fn continuations(&self, prefix: &[Key]) -> Vec<(&[Key], &Action)> {
+        let mut next: Vec<(&[Key], &Action)> = self.bindings.iter()
+            .filter(|(bound, _)| bound.len() > prefix.len() && bound.starts_with(prefix))
+            .map(|(bound, action)| (&bound[prefix.len()..], action))
+            .collect();
+        next.sort_by_key(|(keys, _)| keys_to_string(keys));
+        next
+    }
+
+    fn bind(&mut self, keys: Vec<Key>, action: Option<Action>) {
+        self.bindings.retain(|(bound, _)| *bound != keys);
+        if let Some(action) = action {
+            self.bindings.push((keys, action));
+        }
+    }
+}
+
+/// Keymap of every context
+#[derive(Default, Debug)]
+pub struct Keymaps {
+    maps: Vec<(&'static str, Keymap)>,
+    /// Bindings of the settings that could not be made
+    pub errors: Vec<String>,
+}
+
+impl Keymaps {
+    /// Default bindings, changed by the `[keys.<context>]` tables of settings
+    pub fn new(settings: &Settings) -> Self {
+        let mut errors = Vec::new();
+        let leader = match parse_keys(settings.text("leader"), &[]) {
+            Ok(leader) => leader,
+            Err(e) => {
+                errors.push(format!("leader: {}", e));
+                vec![Key::char('\\')]
+            }
+        };
+
+        let mut maps = Vec::new();
+        for context in CONTEXTS {
+            let mut keymap = Keymap::default();
+            for (keys, action) in context.default_bindings() {
+                let keys = parse_keys(keys, &leader).expect("invalid default binding");
+                keymap.bind(keys, Some(Action::Named(action)));
+            }
+            if let Some(Value::Table(bindings)) = settings.bindings().get(context.name) {
+                bind_table(&mut keymap, context, bindings, &leader, &mut errors);
+            }
+            maps.push((context.name, keymap));
+        }
+
+        for name in settings.bindings().keys() {
+            if !CONTEXTS.iter().any(|context| context.name == name) {
+                errors.push(format!("keys.{}: unknown keymap", name));
+            }
+        }
+        Keymaps { maps, errors }
+    }
+
+    /// Keymap of context, empty if there is no such context
+    pub fn get(&self, context: &str) -> &Keymap {
+        static EMPTY: Keymap = Keymap { bindings: Vec::new() };
+        self.maps.iter().find(|(name, _)| *name == context).map_or(&EMPTY, |(_, keymap)| keymap)
+    }
+}
+
+/// Bind the keys of a config table, `:command` running a command and `none` removing a binding
+fn bind_table(keymap: &mut Keymap, context: &Context, bindings: &Table, leader: &[Key], errors: &mut Vec<String>) {
+    for (keys, value) in bindings {
+        let error = |e: String| format!("keys.{}: {}: {}", context.name, keys, e);
+        let parsed = match parse_keys(keys, leader) {
+            Ok(parsed) => parsed,
+            Err(e) => { errors.push(error(e)); continue; }
+        };
+        let action = match value.as_str() {
+            Some("none") => None,
+            Some(command) if command.starts_with(':') => Some(Action::Command(command[1..].to_string())),
+            Some(name) => match context.actions.iter().find(|(action, _)| *action == name) {
+                Some((action, _)) => Some(Action::Named(action)),
+                None => { errors.push(error(format!("unknown action '{}'", name))); continue; }
+            },
+            None => { errors.push(error("expected an action name, \":command\" or \"none\"".into())); continue; }
+        };
+        keymap.bind(parsed, action);
+    }
+}
+
+static CURRENT: RwLock<Option<(Arc<Settings>, Arc<Keymaps>)>> = RwLock::new(None);
+
+/// Keymaps of the current settings, remade when they change
+pub fn keymaps() -> Arc<Keymaps> {
+    let settings = current_settings();
+    if let Ok(current) = CURRENT.read() {
+        if let Some((made_from, keymaps)) = current.as_ref() {
+            if Arc::ptr_eq(made_from, &settings) { return keymaps.clone(); }
+        }
+    }
+    let keymaps = Arc::new(Keymaps::new(&settings));
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Some((settings, keymaps.clone()));
+    }
+    keymaps
+}
+
+/// What typed keys come to
+#[derive(PartialEq, Debug)]
+pub enum Feed {
+    Action(Action),
+    /// The keys are bound to nothing, to be handled as they are
+    Unbound(Vec<Key>),
+}
+
+/// Keys typed towards a binding
+#[derive(Default)]
+pub struct KeyMatcher {
+    pending: Vec<Key>,
+    since: Option<Instant>,
+}
+
+impl KeyMatcher {
+    /// Add key to the pending ones, matched in the keymap of context.
+    /// Returns nothing while they may still become a binding
+    pub fn feed(&mut self, context: &str, key: Key) -> Vec<Feed> {
+        self.feed_map(keymaps().get(context), key)
+    }
+
+    fn feed_map(&mut self, keymap: &Keymap, key: Key) -> Vec<Feed> {
+        self.pending.push(key);
+        match keymap.lookup(&self.pending) {
+            Lookup::Exact(action, false) => {
+                let action = action.clone();
+                self.clear();
+                vec![Feed::Action(action)]
+            }
+            Lookup::Exact(_, true) | Lookup::Prefix => {
+                self.since = Some(Instant::now());
+                Vec::new()
+            }
+            // the keys before are done with, the last one may start another binding
+            Lookup::None if self.pending.len() > 1 => {
+                let key = self.pending.pop().unwrap();
+                let mut fed = vec![self.flush(keymap)];
+                fed.extend(self.feed_map(keymap, key));
+                fed
+            }
+            Lookup::None => vec![Feed::Unbound(std::mem::take(&mut self.pending))],
+        }
+    }
+
+    /// Pending keys, once no key was typed for `timeoutlen` milliseconds
+    pub fn timed_out(&mut self, context: &str) -> Vec<Feed> {
+        let timeout = Duration::from_millis(current_settings().number("timeoutlen") as u64);
+        match self.since {
+            Some(since) if since.elapsed() >= timeout => vec![self.flush(keymaps().get(context))],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Take the pending keys, running the action they are bound to even though longer bindings start with them
+    fn flush(&mut self, keymap: &Keymap) -> Feed {
+        let keys = std::mem::take(&mut self.pending);
+        self.since = None;
+        match keymap.lookup(&keys) {
+            Lookup::Exact(action, _) => Feed::Action(action.clone()),
+            _ => Feed::Unbound(keys),
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+    /// Bindings of context the pending keys may still become
+    pub fn hints(&self, context: &str) -> Option<KeyHints> {
+        if self.pending.is_empty() { return None; }
+        let keymaps = keymaps();
+        let next = keymaps.get(context).continuations(&self.pending).into_iter()
+            .map(|(keys, action)| (keys_to_string(keys), describe(context, action)))
+            .collect();
+        Some(KeyHints { typed: keys_to_string(&self.pending), next })
+    }
+    pub fn pending(&self) -> &[Key] {
+        &self.pending
+    }
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.since = None;
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::parse;
+
+    fn settings(source: &str) -> Settings {
+        let mut settings = Settings::default();
+        assert_eq!(settings.apply_table(&parse(source).unwrap()), Vec::<String>::new());
+        settings
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let keymaps = Keymaps::new(&Settings::default());
+        assert!(keymaps.errors.is_empty());
+        for context in CONTEXTS {
+            for (_, action) in context.default_bindings() {
+                assert!(context.actions.iter().any(|(name, _)| name == action), "{} in {}", action, context.name);
+            }
+        }
+    }
+
+    #[test]
+    fn config_changes_bindings() {
+        let keymaps = Keymaps::new(&settings(
+            "[keys]\nleader = \"<Space>\"\n\n[keys.tabs]\n\"<Tab>\" = \"none\"\n\"<leader>t\" = \"tab.next\"\n\"<C-s>\" = \":w\"\n"));
+        assert!(keymaps.errors.is_empty());
+        let tabs = keymaps.get("tabs");
+        assert_eq!(tabs.lookup(&parse_keys("<Tab>", &[]).unwrap()), Lookup::None);
+        assert_eq!(tabs.lookup(&[Key::char(' ')]), Lookup::Prefix);
+        assert_eq!(tabs.lookup(&[Key::char(' '), Key::char('t')]), Lookup::Exact(&Action::Named("tab.next"), false));
+        assert_eq!(tabs.lookup(&parse_keys("<C-s>", &[]).unwrap()), Lookup::Exact(&Action::Command("w".into()), false));
+
+        let normal = Keymaps::new(&settings("[keys.normal]\nn = \"motion.down\"\nj = \"none\"\n"));
+        assert_eq!(normal.get("normal").lookup(&[Key::char('n')]), Lookup::Exact(&Action::Named("motion.down"), false));
+        assert_eq!(normal.get("normal").lookup(&[Key::char('j')]), Lookup::None);
+
+        let keymaps = Keymaps::new(&settings("[keys.tabs]\n\"<Nope>\" = \"tab.next\"\nx = \"editor.undo\"\n\n[keys.nope]\nx = \"none\"\n"));
+        assert_eq!(keymaps.errors.len(), 3);
+    }
+
+    #[test]
+    fn matches_sequences() {
+        let keymaps = Keymaps::new(&settings("[keys.insert]\njk = \"editor.normal\"\n\n[keys.normal]\nq = \"editor.insert\"\nqx = \":x\"\n"));
+        let insert = keymaps.get("insert");
+        let mut matcher = KeyMatcher::default();
+        let (j, k, a) = (Key::char('j'), Key::char('k'), Key::char('a'));
+
+        assert_eq!(matcher.feed_map(insert, j), vec![]);
+        assert_eq!(matcher.feed_map(insert, k), vec![Feed::Action(Action::Named("editor.normal"))]);
+        assert!(!matcher.is_pending());
+
+        assert_eq!(matcher.feed_map(insert, j), vec![]);
+        assert_eq!(matcher.feed_map(insert, a), vec![Feed::Unbound(vec![j]), Feed::Unbound(vec![a])]);
+        // the second j may start jk again
+        assert_eq!(matcher.feed_map(insert, j), vec![]);
+        assert_eq!(matcher.feed_map(insert, j), vec![Feed::Unbound(vec![j])]);
+        assert_eq!(matcher.pending(), &[j]);
+
+        let hints = matcher.hints("insert");
+        assert_eq!(hints.map(|h| h.typed), Some("j".to_string()));
+
+        let normal = keymaps.get("normal");
+        let mut matcher = KeyMatcher::default();
+        assert_eq!(matcher.feed_map(normal, Key::char('q')), vec![]);
+        let next = normal.continuations(matcher.pending());
+        assert_eq!(next, vec![(&[Key::char('x')][..], &Action::Command("x".into()))]);
+        assert_eq!(describe("normal", next[0].1), ":x");
+        assert_eq!(describe("normal", &Action::Named("editor.undo")), "Undo the last change");
+        assert_eq!(matcher.feed_map(normal, Key::char('x')), vec![Feed::Action(Action::Command("x".into()))]);
+        assert_eq!(matcher.feed_map(normal, Key::char('q')), vec![]);
+        assert_eq!(matcher.feed_map(normal, a), vec![Feed::Action(Action::Named("editor.insert")), Feed::Unbound(vec![a])]);
+    }
+}
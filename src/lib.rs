@@ -6,7 +6,13 @@ pub mod textedit;
 pub mod event;
 pub mod popup;
 pub mod fswindow;
+pub mod settingswindow;
 pub mod alert;
+pub mod keyhint;
+pub mod config;
+pub mod keymap;
+pub mod cmdline;
+pub mod syntax;
 
 use std::fmt::Display;
 use std::ops::{Add, Sub};
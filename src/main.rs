@@ -4,16 +4,20 @@ use std::io::{Write, stdout};
 use std::env;
 use std::ffi::FromVecWithNulError;
 use std::path::PathBuf;
+use std::thread;
 use std::time::Duration;
 use crossterm::cursor::Hide;
 use demys::plot::Plot;
 
-use crossterm::{cursor, queue, terminal, QueueableCommand, event, execute};
+use crossterm::{cursor, queue, terminal, QueueableCommand, execute};
 use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind};
 use crossterm::event::KeyCode::Tab;
 use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen, enable_raw_mode, Clear, ClearType};
-use demys::event::{EventReceiver, Uuid};
-use demys::style::Canvas;
+use demys::event::{wake_channel, waker, EventReceiver, Uuid, Wake};
+use demys::config;
+use demys::keymap;
+use demys::style::{themes, Canvas};
+use demys::syntax;
 use demys::textedit::buffer::TextBuffer;
 use demys::window::{TestWindow, Window, WindowEvent, WindowManager, WindowRequest};
 use demys::fswindow::FSWindow;
@@ -33,6 +37,9 @@ impl Drop for TuiGuard {
 }
 
 
+/// Longest wait for a wakeup while keys are pending, so timed keys and hints still fire
+const TICK: Duration = Duration::from_millis(20);
+
 pub enum DemysEvent {
     Sys(Event),
     Request(Uuid, WindowRequest),
@@ -60,6 +67,12 @@ fn main() {
 
     let current_dir = env::current_dir().expect("");
 
+    // settings are read before windows are made with them
+    let mut config_errors = config::load_settings(&current_dir);
+    config_errors.extend(keymap::keymaps().errors.iter().cloned());
+    config_errors.extend(themes().errors.iter().cloned());
+    config_errors.extend(syntax::registry().errors.iter().cloned());
+
     let mut file_paths: Vec<PathBuf> = Vec::new();
     for p in &args[1..] {
         file_paths.push(p.into());
@@ -105,13 +118,18 @@ fn main() {
 
     let mut receiver: EventReceiver<WindowRequest, Uuid> = EventReceiver::new();
 
-
-    let mut window_container: Box<dyn WindowContainer> = Box::new(window_manager);
-
     // configure receiver
     let poster = receiver.new_poster();
     let super_uuid = poster.get_uuid().clone();
-    window_container.init(poster);
+    window_manager.init(poster);
+
+    window_manager.apply_settings();
+    if !config_errors.is_empty() {
+        window_manager.alert(config_errors.join("  "));
+    }
+
+
+    let mut window_container: Box<dyn WindowContainer> = Box::new(window_manager);
 
 
     stdout.flush().unwrap();
@@ -120,6 +138,15 @@ fn main() {
     // global queue
     let mut events: VecDeque<DemysEvent> = VecDeque::new();
 
+    // terminal input is read on its own thread, to wait on it together with wakeups of other threads
+    let wakes = wake_channel();
+    let input = waker().unwrap();
+    thread::spawn(move || {
+        while let Ok(sys_event) = read() {
+            if input.send(Wake::Input(sys_event)).is_err() { break; }
+        }
+    });
+
     loop {
         // put window request to queue
         let r = receiver.poll().into_iter().map(|e| {
@@ -127,9 +154,15 @@ fn main() {
         });
         events.extend(r);
 
-        // put sys events to queue
-        if event::poll(Duration::from_millis(0)).unwrap() {
-            let sys_event = read().unwrap();
+        // put sys events to queue, sleeping until woken when idle
+        let wake = if !events.is_empty() {
+            wakes.try_recv().ok()
+        } else if window_container.key_hints().is_some() {
+            wakes.recv_timeout(TICK).ok()
+        } else {
+            wakes.recv().ok()
+        };
+        if let Some(Wake::Input(sys_event)) = wake {
             events.push_back(DemysEvent::Sys(sys_event));
         }
 
@@ -137,12 +170,11 @@ fn main() {
 
         // match next event
         let e = events.pop_front();
-        if e.is_none() { continue; }
 
 
         // Match system events and surfaced requests
-        match e.unwrap() {
-            DemysEvent::Sys(sys_event) => {
+        match e {
+            Some(DemysEvent::Sys(sys_event)) => {
                 match sys_event {
                     Event::Key(KeyEvent { kind, code, modifiers, .. }) => {
                         match kind {
@@ -162,7 +194,7 @@ fn main() {
             }
 
 
-            DemysEvent::Request(uuid, request) => {
+            Some(DemysEvent::Request(uuid, request)) => {
                 match request {
                     WindowRequest::Redraw => {
                         let mut canvas = Canvas::new(terminal_dim);
@@ -180,15 +212,13 @@ fn main() {
                             let _ = window_container.add_window(Box::new(tab));
                         }
                     }
-                    WindowRequest::Command(cmd) => {
-                        window_container.event(WindowEvent::Command(cmd));
-                    }
                     _ => ()
                 }
             }
+            None => ()
         }
 
-        // trace from bottom up
+        // trace from bottom up, also when woken to collect results of other threads
         window_container.collect_requests();
 
         stdout.flush().unwrap();
@@ -0,0 +1,390 @@
+use std::cell::Cell;
+use std::path::PathBuf;
+use crossterm::event::{KeyCode, KeyModifiers};
+use crate::config::{current_layers, current_settings, set_layers, Layer, Layers, OptionInfo, OptionKind, OptionValue, OPTIONS};
+use crate::event::{EventPoster, Uuid};
+use crate::keymap::{Action, Feed, Key, KeyHints, KeyMatcher};
+use crate::plot::Plot;
+use crate::style::{themes, Canvas, StyleAttribute, StyledText, ThemeColor};
+use crate::window::{Window, WindowEvent, WindowRequest};
+
+const NAME_WIDTH: usize = 18;
+const VALUE_WIDTH: usize = 16;
+const DEFAULT_WIDTH: usize = 12;
+
+/// Line of the page, a group heading or an option by its position in OPTIONS
+enum Row {
+    Group(&'static str),
+    Option(usize),
+}
+
+/// Page listing every option, changing them live
+pub struct SettingsWindow {
+    rows: Vec<Row>,
+    /// Selected option, in OPTIONS
+    current: usize,
+    /// Text typed for the selected option, while editing it
+    editing: Option<String>,
+    /// Result of the last action
+    message: Option<String>,
+    /// Directory project settings are saved from
+    dir: PathBuf,
+    /// First row shown
+    scroll: Cell<usize>,
+    poster: Option<EventPoster<WindowRequest, Uuid>>,
+    /// Keys typed towards a settings page binding
+    keys: KeyMatcher,
+    focused: bool,
+}
+
+/// Keymap of the settings page
+const SETTINGS: &str = "settings";
+
+impl SettingsWindow {
+    pub fn new(dir: PathBuf) -> Self {
+        let mut rows = Vec::new();
+        for (i, info) in OPTIONS.iter().enumerate() {
+            if i == 0 || OPTIONS[i - 1].group != info.group {
+                rows.push(Row::Group(info.group));
+            }
+            rows.push(Row::Option(i));
+        }
+        SettingsWindow { rows, current: 0, editing: None, message: None, dir, scroll: Cell::new(0), poster: None, keys: KeyMatcher::default(), focused: false }
+    }
+
+    fn info(&self) -> &'static OptionInfo {
+        &OPTIONS[self.current]
+    }
+    fn value(&self) -> OptionValue {
+        current_settings().get(self.info().name).cloned().unwrap_or_else(|| self.info().default_value())
+    }
+
+    /// Make value the selected option's for this session, in every window.
+    /// None goes back to the saved value
+    fn change(&mut self, value: Option<OptionValue>) {
+        let mut layers = current_layers();
+        if let Some(value) = &value {
+            let mut settings = (*current_settings()).clone();
+            if let Err(e) = settings.set(self.info().name, value.clone()) {
+                self.message = Some(e);
+                return;
+            }
+        }
+        layers.set_session(self.info(), value.as_ref());
+        self.apply(layers);
+    }
+
+    /// Make layers the current ones, in every window
+    fn apply(&mut self, layers: Layers) {
+        set_layers(layers);
+        if let Some(poster) = self.poster.as_mut() {
+            poster.post(WindowRequest::SettingsChanged);
+        }
+    }
+
+    /// Value steps away from the current one, for options with a set of values
+    fn cycled(&self, step: isize) -> Option<OptionValue> {
+        let cycle = |names: &[&str], current: &str| {
+            let i = names.iter().position(|name| *name == current).unwrap_or(0) as isize;
+            names[(i + step).rem_euclid(names.len() as isize) as usize].to_string()
+        };
+        match (self.info().kind, self.value()) {
+            (OptionKind::Bool, OptionValue::Bool(b)) => Some(OptionValue::Bool(!b)),
+            (OptionKind::Choice(choices), OptionValue::Text(text)) => Some(OptionValue::Text(cycle(choices, &text))),
+            (OptionKind::Number, OptionValue::Number(n)) => Some(OptionValue::Number(n.saturating_add_signed(step))),
+            (_, OptionValue::Text(text)) if self.info().name == "colorscheme" => {
+                Some(OptionValue::Text(cycle(&themes().names(), &text)))
+            }
+            _ => None,
+        }
+    }
+
+    fn save(&mut self, layer: Layer) {
+        let info = self.info();
+        let mut layers = current_layers();
+        match layers.save(layer, &self.dir, info, Some(&self.value())) {
+            Ok(()) => {
+                self.message = Some(format!("Saved {} to {} settings", info.name, layer.name()));
+                self.apply(layers);
+            }
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    fn edit_input(&mut self, key: KeyCode) {
+        let Some(text) = self.editing.as_mut() else { return };
+        match key {
+            KeyCode::Char(ch) => text.push(ch),
+            KeyCode::Backspace => { text.pop(); }
+            KeyCode::Esc => self.editing = None,
+            KeyCode::Enter => {
+                let text = self.editing.take().unwrap_or_default();
+                let text = if self.info().kind == OptionKind::Text { text.as_str() } else { text.trim() };
+                match OptionValue::parse(self.info().kind, text) {
+                    Ok(value) => self.change(Some(value)),
+                    Err(e) => self.message = Some(format!("{}: {}", self.info().name, e)),
+                }
+            }
+            _ => ()
+        }
+    }
+
+    fn input(&mut self, key: KeyCode, modifiers: KeyModifiers) {
+        if self.editing.is_some() {
+            self.edit_input(key);
+            return;
+        }
+        self.message = None;
+
+        let fed = self.keys.feed(SETTINGS, Key::new(key, modifiers));
+        self.run_keys(fed);
+    }
+
+    fn run_keys(&mut self, fed: Vec<Feed>) {
+        for fed in fed {
+            match fed {
+                Feed::Action(Action::Named(action)) => self.run_action(action),
+                Feed::Action(Action::Command(command)) => {
+                    if let Some(poster) = self.poster.as_mut() {
+                        poster.post(WindowRequest::Command(command));
+                    }
+                }
+                Feed::Unbound(_) => (),
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: &str) {
+        match action {
+            "settings.down" => self.current = (self.current + 1).min(OPTIONS.len() - 1),
+            "settings.up" => self.current = self.current.saturating_sub(1),
+            "settings.first" => self.current = 0,
+            "settings.last" => self.current = OPTIONS.len() - 1,
+            "settings.change" => match self.cycled(1) {
+                // numbers are typed rather than stepped
+                Some(value) if self.info().kind != OptionKind::Number => self.change(Some(value)),
+                _ => self.editing = Some(self.value().to_string()),
+            },
+            "settings.next" => if let Some(value) = self.cycled(1) { self.change(Some(value)) },
+            "settings.previous" => if let Some(value) = self.cycled(-1) { self.change(Some(value)) },
+            "settings.reset" => self.change(None),
+            "settings.save" => self.save(Layer::Global),
+            "settings.save_project" => self.save(Layer::Project),
+            _ => ()
+        }
+    }
+
+    /// Write the row of option i at the cursor
+    fn draw_option(&self, canvas: &mut Canvas, i: usize, value: &OptionValue) {
+        let info = &OPTIONS[i];
+        let row = canvas.get_cursor().row;
+        let changed = *value != info.default_value();
+
+        write_clipped(canvas, format!("  {:<width$}", info.name, width = NAME_WIDTH), &[]);
+
+        let mut shown = match (&self.editing, i == self.current) {
+            (Some(text), true) => format!("{}_", text),
+            _ => value.to_string(),
+        };
+        if let OptionValue::Color(color) = value {
+            write_clipped(canvas, "  ".into(), &[StyleAttribute::BgColor(*color)]);
+            shown = format!(" {}", shown);
+        }
+        let width = if matches!(value, OptionValue::Color(_)) { VALUE_WIDTH - 2 } else { VALUE_WIDTH };
+        write_clipped(canvas, format!("{:<width$} ", shown, width = width), &[StyleAttribute::Bold(changed)]);
+        write_clipped(canvas, format!("{:<width$} ", info.default, width = DEFAULT_WIDTH), &[StyleAttribute::Dim(true)]);
+        write_clipped(canvas, info.description.into(), &[StyleAttribute::Color(ThemeColor::Comment)]);
+
+        if self.focused && i == self.current {
+            let _ = canvas.set_attribute(StyleAttribute::BgColor(ThemeColor::Selection),
+                Plot::new(row, 0), Plot::new(row, NAME_WIDTH + 2));
+        }
+    }
+}
+
+/// Write text at the cursor, cut off before the last column so the cursor stays on its row
+fn write_clipped(canvas: &mut Canvas, text: String, attributes: &[StyleAttribute]) {
+    let room = canvas.get_dim().col.saturating_sub(canvas.get_cursor().col + 1);
+    let mut styled = StyledText::new(text.chars().take(room).collect());
+    if styled.len() == 0 { return; }
+    for attribute in attributes {
+        styled = styled.with(*attribute);
+    }
+    canvas.write(&styled);
+}
+
+impl Window for SettingsWindow {
+    fn name(&self) -> String {
+        "Settings".into()
+    }
+    fn input_bypass(&self) -> bool {
+        self.editing.is_some() || self.keys.is_pending()
+    }
+    fn key_hints(&self) -> Option<KeyHints> {
+        self.keys.hints(SETTINGS)
+    }
+    fn collect_requests(&mut self) -> Vec<WindowRequest> {
+        let fed = self.keys.timed_out(SETTINGS);
+        if !fed.is_empty() {
+            self.run_keys(fed);
+            if let Some(poster) = self.poster.as_mut() {
+                poster.post(WindowRequest::Redraw);
+            }
+        }
+        Vec::new()
+    }
+    fn draw(&self, canvas: &mut Canvas) {
+        let dim = *canvas.get_dim();
+        if dim.row < 3 { return; }
+        // title and message take a row each
+        let shown = dim.row - 2;
+
+        let selected = self.rows.iter().position(|row| matches!(row, Row::Option(i) if *i == self.current)).unwrap_or(0);
+        let mut scroll = self.scroll.get();
+        if selected < scroll + 1 { scroll = selected.saturating_sub(1); }
+        if selected >= scroll + shown { scroll = selected + 1 - shown; }
+        self.scroll.set(scroll);
+
+        write_clipped(canvas, "Settings".into(), &[StyleAttribute::Bold(true)]);
+        write_clipped(canvas, "  Enter change  h/l cycle  r reset  s save  p save to project".into(), &[StyleAttribute::Color(ThemeColor::Comment)]);
+
+        let settings = current_settings();
+        for row in self.rows.iter().skip(scroll).take(shown) {
+            let _ = canvas.to_next_line();
+            match row {
+                Row::Group(group) => {
+                    write_clipped(canvas, group.to_string(), &[StyleAttribute::Color(ThemeColor::Green), StyleAttribute::Bold(true)]);
+                }
+                Row::Option(i) => {
+                    let value = settings.get(OPTIONS[*i].name).cloned().unwrap_or_else(|| OPTIONS[*i].default_value());
+                    self.draw_option(canvas, *i, &value);
+                }
+            }
+        }
+
+        if let Some(message) = &self.message {
+            let _ = canvas.move_to(Plot::new(dim.row - 1, 0));
+            write_clipped(canvas, message.clone(), &[StyleAttribute::Color(ThemeColor::Yellow)]);
+        }
+    }
+    fn init(&mut self, poster: EventPoster<WindowRequest, Uuid>) {
+        self.poster = Some(poster);
+    }
+    fn event(&mut self, event: WindowEvent) {
+        match event {
+            WindowEvent::Focus => self.focused = true,
+            WindowEvent::Unfocus => self.focused = false,
+
+            WindowEvent::Input { key, modifiers } => self.input(key, modifiers),
+            WindowEvent::TryQuit => self.poster.as_mut().unwrap().post(WindowRequest::RemoveSelfWindow),
+            _ => ()
+        }
+
+        if let Some(poster) = self.poster.as_mut() {
+            poster.post(WindowRequest::Redraw);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+    use crate::config::{find_option, PROJECT_FILE};
+
+    /// Taken by each test, as they all change the shared settings
+    static SHARED: Mutex<()> = Mutex::new(());
+
+    fn shared() -> MutexGuard<'static, ()> {
+        SHARED.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Page with option selected. Each test changes its own options, as the settings are shared
+    fn page(option: &str, dir: PathBuf) -> SettingsWindow {
+        let mut page = SettingsWindow::new(dir);
+        page.current = find_option(option).unwrap().0;
+        page
+    }
+    fn type_keys(page: &mut SettingsWindow, keys: &[KeyCode]) {
+        for key in keys {
+            page.event(Key::new(*key, KeyModifiers::NONE).into());
+        }
+    }
+    fn type_text(page: &mut SettingsWindow, text: &str) {
+        type_keys(page, &text.chars().map(KeyCode::Char).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn cycles_values() {
+        let _shared = shared();
+        let mut page = page("dynamiccaret", PathBuf::new());
+        type_keys(&mut page, &[KeyCode::Enter]);
+        assert!(!current_settings().bool("dynamiccaret"));
+        type_text(&mut page, "r");
+        assert!(current_settings().bool("dynamiccaret"));
+
+        page.current = find_option("termcolors").unwrap().0;
+        type_text(&mut page, "l");
+        assert_eq!(current_settings().text("termcolors"), "truecolor");
+        type_text(&mut page, "hh");
+        assert_eq!(current_settings().text("termcolors"), "none");
+        type_text(&mut page, "r");
+    }
+
+    #[test]
+    fn edits_in_place() {
+        let _shared = shared();
+        let mut page = page("sidescrolloff", PathBuf::new());
+        type_keys(&mut page, &[KeyCode::Enter, KeyCode::Backspace]);
+        type_text(&mut page, "12");
+        assert!(page.input_bypass());
+        type_keys(&mut page, &[KeyCode::Enter]);
+        assert_eq!(current_settings().number("sidescrolloff"), 12);
+        type_text(&mut page, "r");
+
+        page.current = find_option("showbreak").unwrap().0;
+        type_keys(&mut page, &[KeyCode::Enter]);
+        type_text(&mut page, "> ");
+        type_keys(&mut page, &[KeyCode::Enter]);
+        assert_eq!(current_settings().text("showbreak"), "> ");
+        type_text(&mut page, "r");
+
+        // escape leaves the value as it was
+        type_keys(&mut page, &[KeyCode::Enter, KeyCode::Char('x'), KeyCode::Esc]);
+        assert_eq!(current_settings().text("showbreak"), "");
+    }
+
+    #[test]
+    fn rejects_invalid_edit() {
+        let _shared = shared();
+        let mut page = page("foldcolor", PathBuf::new());
+        type_keys(&mut page, &[KeyCode::Enter]);
+        type_text(&mut page, "nocolor");
+        type_keys(&mut page, &[KeyCode::Enter]);
+        assert_eq!(current_settings().get("foldcolor"), Some(&find_option("foldcolor").unwrap().1.default_value()));
+        assert!(page.message.as_ref().is_some_and(|message| message.starts_with("foldcolor: ")));
+        assert!(!page.input_bypass());
+    }
+
+    #[test]
+    fn saves_to_layer() {
+        let _shared = shared();
+        let dir = std::env::temp_dir().join(format!("demys-settings-page-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut page = page("relativenumber", dir.clone());
+        type_text(&mut page, "lp");
+        assert_eq!(page.message.as_deref(), Some("Saved relativenumber to project settings"));
+        let saved = std::fs::read_to_string(dir.join(PROJECT_FILE)).unwrap();
+        assert!(saved.contains("[editor]\nrelativenumber = true\n"));
+
+        // the saved value stays once the change for this session is reset
+        type_text(&mut page, "r");
+        assert!(current_settings().bool("relativenumber"));
+
+        let mut layers = current_layers();
+        layers.save(Layer::Project, &dir, page.info(), None).unwrap();
+        set_layers(layers);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -97,6 +97,11 @@ impl Canvas {
         self.dim.col - 1
     }
 
+    /// Text written in row, without styles or children
+    pub fn row_text(&self, row: usize) -> String {
+        self.text.chars().skip(row * self.dim.col).take(self.dim.col).collect()
+    }
+
     /// Turn on cursor display when canvas is written
     pub fn show_cursor(&mut self, show: bool) {
         self.show_cursor = show;
@@ -256,6 +261,10 @@ impl Canvas {
                 } else {
                     this.reset(stdout);
                 }
+                // restore what the reset also cleared
+                if let Some(other) = this.shares_reset().and_then(|shared| attribute_stack.get(&shared)).and_then(|stack| stack.last()) {
+                    other.apply(stdout);
+                }
             }
         }
     }
@@ -292,6 +301,11 @@ impl Canvas {
             return;
         }
 
+        // unstyled text in the theme's colors
+        if let (Some(fg), Some(bg)) = (ThemeColor::Primary.resolve(), ThemeColor::Background.resolve()) {
+            let _ = queue!(stdout, SetForegroundColor(fg), SetBackgroundColor(bg));
+        }
+
         // initialize attribute stack
         let mut attribute_stack: HashMap<usize, Vec<StyleAttribute>> = HashMap::new();
         for i in 0..StyleAttribute::COUNT { //
@@ -359,7 +373,7 @@ impl Canvas {
 
 #[cfg(test)]
 mod test {
-    use crate::style::ThemeColor;
+    use crate::style::{ThemeColor, UnderlineStyle};
     use super::*;
 
     #[test]
@@ -460,4 +474,22 @@ mod test {
 
         println!("{}", string);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn nested_attributes() {
+        let mut canvas = Canvas::new(Plot::new(1, 10));
+        canvas.write(&"0123456789".into());
+        canvas.set_attribute(StyleAttribute::Bold(true), Plot::new(0, 0), Plot::new(0, 6)).unwrap();
+        canvas.set_attribute(StyleAttribute::Dim(true), Plot::new(0, 2), Plot::new(0, 4)).unwrap();
+        canvas.set_attribute(StyleAttribute::Underline(UnderlineStyle::Single), Plot::new(0, 1), Plot::new(0, 8)).unwrap();
+        canvas.set_attribute(StyleAttribute::Strikethrough(true), Plot::new(0, 3), Plot::new(0, 5)).unwrap();
+
+        let mut buffer: Vec<u8> = Vec::new();
+        canvas.queue_write(&mut buffer, Plot::new(0, 0));
+        let output = String::from_utf8(buffer).unwrap();
+
+        // ending dim resets intensity, so bold is applied again
+        assert!(output.contains("\x1b[9m\x1b[1;4H3\x1b[22m\x1b[1m\x1b[1;5H4"), "{:?}", output);
+        assert!(output.contains("\x1b[29m\x1b[1;6H5\x1b[22m\x1b[1;7H67\x1b[24m"), "{:?}", output);
+    }
+}
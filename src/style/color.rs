@@ -0,0 +1,245 @@
+//! Terminal color support, and fitting colors to what the terminal can show
+use std::sync::atomic::{AtomicU8, Ordering};
+use crossterm::style::Color;
+
+/// Colors a terminal can show
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(u8)]
+pub enum ColorDepth {
+    /// 24 bit RGB
+    TrueColor,
+    /// 256 color palette
+    Ansi256,
+    /// 16 basic colors
+    Ansi16,
+    /// No colors, only attributes like bold and reverse
+    NoColor,
+}
+
+impl ColorDepth {
+    /// Depth from the environment: `NO_COLOR`, then `COLORTERM` and `TERM`
+    pub fn detect() -> Self {
+        let var = |name| std::env::var(name).ok();
+        Self::detect_from(var("NO_COLOR").as_deref(), var("COLORTERM").as_deref(), var("TERM").as_deref())
+    }
+
+    pub fn detect_from(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if no_color.is_some_and(|v| !v.is_empty()) {
+            return ColorDepth::NoColor;
+        }
+        if colorterm.is_some_and(|c| c == "truecolor" || c == "24bit") {
+            return ColorDepth::TrueColor;
+        }
+        match term {
+            Some("dumb") => ColorDepth::NoColor,
+            Some(term) if term.contains("truecolor") || term.contains("direct") => ColorDepth::TrueColor,
+            Some(term) if term.contains("256") => ColorDepth::Ansi256,
+            _ => ColorDepth::Ansi16,
+        }
+    }
+
+    /// Depth from a setting value: `truecolor`, `256`, `16` or `none`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" | "24bit" => Some(ColorDepth::TrueColor),
+            "256" => Some(ColorDepth::Ansi256),
+            "16" => Some(ColorDepth::Ansi16),
+            "none" | "0" => Some(ColorDepth::NoColor),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorDepth::TrueColor => "truecolor",
+            ColorDepth::Ansi256 => "256",
+            ColorDepth::Ansi16 => "16",
+            ColorDepth::NoColor => "none",
+        }
+    }
+}
+
+/// Unset until detected or set
+const UNSET: u8 = u8::MAX;
+static DEPTH: AtomicU8 = AtomicU8::new(UNSET);
+
+/// Depth colors are written with, detected on first use unless set
+pub fn color_depth() -> ColorDepth {
+    match DEPTH.load(Ordering::Relaxed) {
+        0 => ColorDepth::TrueColor,
+        1 => ColorDepth::Ansi256,
+        2 => ColorDepth::Ansi16,
+        3 => ColorDepth::NoColor,
+        _ => {
+            let depth = ColorDepth::detect();
+            set_color_depth(depth);
+            depth
+        }
+    }
+}
+
+pub fn set_color_depth(depth: ColorDepth) {
+    DEPTH.store(depth as u8, Ordering::Relaxed);
+}
+
+/// Whether the terminal draws curly, dotted and double underlines and underline colors.
+/// Others may show them as plain underlines or not at all
+pub fn detect_styled_underlines(term: Option<&str>, term_program: Option<&str>, vte_version: Option<&str>) -> bool {
+    const TERMS: [&str; 5] = ["kitty", "wezterm", "foot", "alacritty", "ghostty"];
+    if term.is_some_and(|term| TERMS.iter().any(|t| term.contains(t))) {
+        return true;
+    }
+    if term_program.is_some_and(|program| TERMS.iter().any(|t| program.to_lowercase().contains(t)) || program == "iTerm.app") {
+        return true;
+    }
+    // VTE based terminals support them since 0.51
+    vte_version.and_then(|v| v.parse::<u32>().ok()).is_some_and(|v| v >= 5100)
+}
+
+static STYLED_UNDERLINES: AtomicU8 = AtomicU8::new(UNSET);
+
+/// Styled underlines are written, detected on first use unless set
+pub fn styled_underlines() -> bool {
+    match STYLED_UNDERLINES.load(Ordering::Relaxed) {
+        UNSET => {
+            let var = |name| std::env::var(name).ok();
+            let styled = detect_styled_underlines(var("TERM").as_deref(), var("TERM_PROGRAM").as_deref(), var("VTE_VERSION").as_deref());
+            set_styled_underlines(styled);
+            styled
+        }
+        styled => styled != 0,
+    }
+}
+
+pub fn set_styled_underlines(styled: bool) {
+    STYLED_UNDERLINES.store(styled as u8, Ordering::Relaxed);
+}
+
+/// RGB of the 16 basic colors, as xterm shows them
+const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+/// Channel values of the 6x6x6 color cube
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Closest color depth can show, None if it shows no colors
+pub fn downsample(color: Color, depth: ColorDepth) -> Option<Color> {
+    match (depth, color) {
+        (ColorDepth::NoColor, _) => None,
+        (ColorDepth::TrueColor, _) => Some(color),
+        (_, Color::Rgb { r, g, b }) if depth == ColorDepth::Ansi256 => Some(Color::AnsiValue(nearest_256(r, g, b))),
+        (_, Color::Rgb { r, g, b }) => Some(ansi16(nearest_16(r, g, b))),
+        (ColorDepth::Ansi16, Color::AnsiValue(i)) if i >= 16 => {
+            let (r, g, b) = palette_rgb(i);
+            Some(ansi16(nearest_16(r, g, b)))
+        }
+        (ColorDepth::Ansi16, Color::AnsiValue(i)) => Some(ansi16(i)),
+        _ => Some(color),
+    }
+}
+
+/// RGB of a 256 color palette index
+pub fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI16[index as usize],
+        16..=231 => {
+            let i = index - 16;
+            (CUBE[(i / 36) as usize], CUBE[(i / 6 % 6) as usize], CUBE[(i % 6) as usize])
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Closest palette index in the color cube or the gray ramp
+pub fn nearest_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| CUBE.iter().enumerate().min_by_key(|(_, c)| (**c as i32 - v as i32).abs()).map_or(0, |(i, _)| i as u8);
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance(palette_rgb(gray), (r, g, b)) < distance(palette_rgb(cube), (r, g, b)) { gray } else { cube }
+}
+
+/// Closest of the 16 basic colors
+pub fn nearest_16(r: u8, g: u8, b: u8) -> u8 {
+    (0..16u8).min_by_key(|i| distance(ANSI16[*i as usize], (r, g, b))).unwrap_or(0)
+}
+
+/// Named color of one of the 16 basic ones
+fn ansi16(index: u8) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::DarkRed,
+        2 => Color::DarkGreen,
+        3 => Color::DarkYellow,
+        4 => Color::DarkBlue,
+        5 => Color::DarkMagenta,
+        6 => Color::DarkCyan,
+        7 => Color::Grey,
+        8 => Color::DarkGrey,
+        9 => Color::Red,
+        10 => Color::Green,
+        11 => Color::Yellow,
+        12 => Color::Blue,
+        13 => Color::Magenta,
+        14 => Color::Cyan,
+        _ => Color::White,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detects_depth() {
+        assert_eq!(ColorDepth::detect_from(Some("1"), Some("truecolor"), None), ColorDepth::NoColor);
+        assert_eq!(ColorDepth::detect_from(Some(""), Some("truecolor"), None), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::detect_from(None, None, Some("xterm-256color")), ColorDepth::Ansi256);
+        assert_eq!(ColorDepth::detect_from(None, None, Some("xterm")), ColorDepth::Ansi16);
+        assert_eq!(ColorDepth::detect_from(None, None, Some("dumb")), ColorDepth::NoColor);
+    }
+
+    #[test]
+    fn detects_styled_underlines() {
+        assert!(detect_styled_underlines(Some("xterm-kitty"), None, None));
+        assert!(detect_styled_underlines(Some("xterm-256color"), Some("WezTerm"), None));
+        assert!(detect_styled_underlines(Some("xterm-256color"), None, Some("7600")));
+        assert!(!detect_styled_underlines(Some("xterm-256color"), None, Some("4200")));
+        assert!(!detect_styled_underlines(Some("screen"), None, None));
+    }
+
+    #[test]
+    fn nearest_palette_colors() {
+        assert_eq!(nearest_256(255, 0, 0), 196);
+        assert_eq!(nearest_256(0, 0, 0), 16);
+        assert_eq!(nearest_256(128, 128, 128), 244);
+        assert_eq!(nearest_16(250, 10, 10), 9);
+        assert_eq!(nearest_16(0, 0, 200), 4);
+    }
+
+    #[test]
+    fn downsamples_to_depth() {
+        let orange = Color::Rgb { r: 255, g: 135, b: 0 };
+        assert_eq!(downsample(orange, ColorDepth::TrueColor), Some(orange));
+        assert_eq!(downsample(orange, ColorDepth::Ansi256), Some(Color::AnsiValue(208)));
+        assert_eq!(downsample(orange, ColorDepth::Ansi16), Some(Color::DarkYellow));
+        assert_eq!(downsample(Color::AnsiValue(196), ColorDepth::Ansi16), Some(Color::Red));
+        assert_eq!(downsample(Color::Blue, ColorDepth::Ansi16), Some(Color::Blue));
+        assert_eq!(downsample(Color::Blue, ColorDepth::NoColor), None);
+    }
+}
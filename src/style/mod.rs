@@ -1,6 +1,10 @@
 //! Styling and writing into specific regions of terminal.
 mod canvas;
+mod color;
 mod style;
+mod theme;
 
 pub use canvas::*;
-pub use style::*;
\ No newline at end of file
+pub use color::*;
+pub use style::*;
+pub use theme::*;
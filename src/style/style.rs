@@ -1,10 +1,12 @@
+use std::fmt;
 use std::io::{Stdout, Write};
 use crossterm::QueueableCommand;
-use crossterm::style::{Attribute, Color, SetAttribute, SetBackgroundColor, SetForegroundColor};
+use crossterm::style::{Attribute, Color, SetAttribute, SetBackgroundColor, SetForegroundColor, SetUnderlineColor};
+use crate::style::{color_depth, current_theme, downsample, parse_color, styled_underlines, ColorDepth};
 
 /// Basically a map to native terminal colors
 /// Exists to be dynamically change color themes
-#[derive(Copy,Clone)]
+#[derive(Copy,Clone,PartialEq,Debug)]
 pub enum ThemeColor {
     Primary,
     Blue,
@@ -16,32 +18,127 @@ pub enum ThemeColor {
     Black,
     White,
     Background,
+    Comment,
+    Keyword,
+    Selection,
+    StatusBar,
+    Border,
+    /// Fixed 24 bit color, outside the theme
+    Rgb(u8, u8, u8),
+    /// Fixed 256 color palette index, outside the theme
+    Indexed(u8),
 }
-impl From<ThemeColor> for crossterm::style::Color {
+impl ThemeColor {
+    /// Number of colors themes set
+    pub const COUNT: usize = 15;
+    pub const ALL: [ThemeColor; Self::COUNT] = [
+        ThemeColor::Primary, ThemeColor::Blue, ThemeColor::DarkBlue, ThemeColor::Magenta,
+        ThemeColor::Yellow, ThemeColor::Green, ThemeColor::Gray, ThemeColor::Black, ThemeColor::White,
+        ThemeColor::Background, ThemeColor::Comment, ThemeColor::Keyword, ThemeColor::Selection,
+        ThemeColor::StatusBar, ThemeColor::Border,
+    ];
+
+    /// Name used in theme files
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThemeColor::Primary => "primary",
+            ThemeColor::Blue => "blue",
+            ThemeColor::DarkBlue => "dark_blue",
+            ThemeColor::Magenta => "magenta",
+            ThemeColor::Yellow => "yellow",
+            ThemeColor::Green => "green",
+            ThemeColor::Gray => "gray",
+            ThemeColor::Black => "black",
+            ThemeColor::White => "white",
+            ThemeColor::Background => "background",
+            ThemeColor::Comment => "comment",
+            ThemeColor::Keyword => "keyword",
+            ThemeColor::Selection => "selection",
+            ThemeColor::StatusBar => "status_bar",
+            ThemeColor::Border => "border",
+            ThemeColor::Rgb(..) => "rgb",
+            ThemeColor::Indexed(_) => "indexed",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|color| color.name() == name)
+    }
+    /// Position in ALL, None for fixed colors
+    pub fn index(&self) -> Option<usize> {
+        Self::ALL.iter().position(|color| color == self)
+    }
+    /// Color from a role name, `#rrggbb` or a 256 color palette index
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        // roles go by name, fixed colors by hex or palette index
+        Self::from_name(text).or_else(|| match parse_color(text)? {
+            Color::Rgb { r, g, b } => Some(ThemeColor::Rgb(r, g, b)),
+            Color::AnsiValue(index) => Some(ThemeColor::Indexed(index)),
+            _ => None,
+        })
+    }
+    /// Color written to the terminal, fit to its color depth. None when it shows no colors
+    pub fn resolve(&self) -> Option<Color> {
+        downsample((*self).into(), color_depth())
+    }
+}
+impl fmt::Display for ThemeColor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThemeColor::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            ThemeColor::Indexed(i) => write!(f, "{}", i),
+            role => f.write_str(role.name()),
+        }
+    }
+}
+impl From<ThemeColor> for Color {
+    /// Color in the current theme
     fn from(color: ThemeColor) -> Self {
-        match color {
-            ThemeColor::Primary => crossterm::style::Color::Black,
-            ThemeColor::Blue => crossterm::style::Color::Blue,
-            ThemeColor::DarkBlue => crossterm::style::Color::DarkBlue,
-            ThemeColor::Magenta => crossterm::style::Color::Magenta,
-            ThemeColor::Yellow => crossterm::style::Color::Yellow,
-            ThemeColor::Green => crossterm::style::Color::Green,
-            ThemeColor::Gray => crossterm::style::Color::Grey,
-            ThemeColor::Black => crossterm::style::Color::Black,
-            ThemeColor::White => crossterm::style::Color::AnsiValue(7),
-            ThemeColor::Background => crossterm::style::Color::Reset
+        current_theme().color(color)
+    }
+}
+
+/// Shape of an underline. Terminals without styled underlines draw a single one
+#[derive(Copy,Clone,PartialEq,Debug)]
+pub enum UnderlineStyle {
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+impl UnderlineStyle {
+    fn attribute(&self) -> Attribute {
+        if *self != UnderlineStyle::None && !styled_underlines() {
+            return Attribute::Underlined;
+        }
+        match self {
+            UnderlineStyle::None => Attribute::NoUnderline,
+            UnderlineStyle::Single => Attribute::Underlined,
+            UnderlineStyle::Double => Attribute::DoubleUnderlined,
+            UnderlineStyle::Curly => Attribute::Undercurled,
+            UnderlineStyle::Dotted => Attribute::Underdotted,
+            UnderlineStyle::Dashed => Attribute::Underdashed,
         }
     }
 }
 
 /// Wrapper for style options
 #[repr(usize)]
-#[derive(Copy,Clone)]
+#[derive(Copy,Clone,PartialEq,Debug)]
 pub enum StyleAttribute {
     Color(ThemeColor),
     Bold(bool),
     Italic(bool),
     BgColor(ThemeColor),
+    Underline(UnderlineStyle),
+    /// Color of underlines, where the terminal supports it
+    UnderlineColor(ThemeColor),
+    Reverse(bool),
+    Dim(bool),
+    Strikethrough(bool),
+    Blink(bool),
 }
 impl From<StyleAttribute> for usize {
     fn from(attr: StyleAttribute) -> Self {
@@ -50,65 +147,100 @@ impl From<StyleAttribute> for usize {
             StyleAttribute::Bold(_) => 1,
             StyleAttribute::Italic(_) => 2,
             StyleAttribute::BgColor(_) => 3,
+            StyleAttribute::Underline(_) => 4,
+            StyleAttribute::UnderlineColor(_) => 5,
+            StyleAttribute::Reverse(_) => 6,
+            StyleAttribute::Dim(_) => 7,
+            StyleAttribute::Strikethrough(_) => 8,
+            StyleAttribute::Blink(_) => 9,
         }
     }
 }
 
 impl StyleAttribute {
-    pub const COUNT: usize = 4;
+    pub const COUNT: usize = 10;
+
+    /// Variant whose reset also clears this one, and needs to be applied again after it.
+    /// Bold and dim share normal intensity, and backgrounds are reversed without colors
+    pub fn shares_reset(&self) -> Option<usize> {
+        let shared = match self {
+            StyleAttribute::Bold(_) => StyleAttribute::Dim(false),
+            StyleAttribute::Dim(_) => StyleAttribute::Bold(false),
+            StyleAttribute::BgColor(_) => StyleAttribute::Reverse(false),
+            StyleAttribute::Reverse(_) => StyleAttribute::BgColor(ThemeColor::Background),
+            _ => return None,
+        };
+        Some(usize::from(shared))
+    }
+
     /// Apply attribute to stdout
     pub fn apply<W: QueueableCommand + Write>(&self, stdout: &mut W) {
-        match self {
-            StyleAttribute::Color(color) => {
-                let _ = stdout.queue(
-                    SetForegroundColor((*color).into())
-                );
-            }
-            StyleAttribute::Bold(bold) => {
-                let _ = stdout.queue(
-                    if *bold {
-                        SetAttribute(Attribute::Bold)
-                    } else {
-                        SetAttribute(Attribute::NormalIntensity)
-                    }
-                );
-            }
-            StyleAttribute::Italic(italic) => {
-                let _ = stdout.queue(
-                    if *italic {
-                        SetAttribute(Attribute::Italic)
-                    } else {
-                        SetAttribute(Attribute::NoItalic)
-                    }
-                );
-            }
-            StyleAttribute::BgColor(color) => {
-                let _ = stdout.queue(
-                    SetBackgroundColor((*color).into())
-                );
-            }
-        }
+        let toggle = |on: bool, set: Attribute, unset: Attribute| SetAttribute(if on { set } else { unset });
+        let _ = match self {
+            StyleAttribute::Color(color) => match color.resolve() {
+                Some(color) => stdout.queue(SetForegroundColor(color)),
+                None => return,
+            },
+            StyleAttribute::Bold(bold) => stdout.queue(toggle(*bold, Attribute::Bold, Attribute::NormalIntensity)),
+            StyleAttribute::Italic(italic) => stdout.queue(toggle(*italic, Attribute::Italic, Attribute::NoItalic)),
+            StyleAttribute::BgColor(color) => match color.resolve() {
+                Some(color) => stdout.queue(SetBackgroundColor(color)),
+                // without colors, highlighted backgrounds are shown reversed
+                None if *color == ThemeColor::Background => stdout.queue(SetAttribute(Attribute::NoReverse)),
+                None => stdout.queue(SetAttribute(Attribute::Reverse)),
+            },
+            StyleAttribute::Underline(style) => stdout.queue(SetAttribute(style.attribute())),
+            StyleAttribute::UnderlineColor(color) => match color.resolve() {
+                Some(color) if styled_underlines() => stdout.queue(SetUnderlineColor(color)),
+                _ => return,
+            },
+            StyleAttribute::Reverse(reverse) => stdout.queue(toggle(*reverse, Attribute::Reverse, Attribute::NoReverse)),
+            StyleAttribute::Dim(dim) => stdout.queue(toggle(*dim, Attribute::Dim, Attribute::NormalIntensity)),
+            StyleAttribute::Strikethrough(strike) => stdout.queue(toggle(*strike, Attribute::CrossedOut, Attribute::NotCrossedOut)),
+            StyleAttribute::Blink(blink) => stdout.queue(toggle(*blink, Attribute::SlowBlink, Attribute::NoBlink)),
+        };
     }
-    /// Appy default version of variant to stdout
+    /// Appy default version of variant to stdout, colors being the theme's text and background
     pub fn reset<W: QueueableCommand + Write>(&self, stdout: &mut W) {
         let _ = match self {
             StyleAttribute::Color(_) => {
-                stdout.queue(SetForegroundColor(Color::Reset))
+                match ThemeColor::Primary.resolve() {
+                    Some(color) => stdout.queue(SetForegroundColor(color)),
+                    None => return,
+                }
             }
-            StyleAttribute::Bold(_) => {
+            StyleAttribute::Bold(_) | StyleAttribute::Dim(_) => {
                 stdout.queue(SetAttribute(Attribute::NormalIntensity))
             }
             StyleAttribute::Italic(_) => {
                 stdout.queue(SetAttribute(Attribute::NoItalic))
             }
             StyleAttribute::BgColor(_) => {
-                stdout.queue(SetBackgroundColor(Color::Reset))
+                match ThemeColor::Background.resolve() {
+                    Some(color) => stdout.queue(SetBackgroundColor(color)),
+                    None => stdout.queue(SetAttribute(Attribute::NoReverse)),
+                }
+            }
+            StyleAttribute::Underline(_) => {
+                stdout.queue(SetAttribute(Attribute::NoUnderline))
+            }
+            StyleAttribute::UnderlineColor(_) => {
+                if color_depth() == ColorDepth::NoColor || !styled_underlines() { return; }
+                stdout.queue(SetUnderlineColor(Color::Reset))
+            }
+            StyleAttribute::Reverse(_) => {
+                stdout.queue(SetAttribute(Attribute::NoReverse))
+            }
+            StyleAttribute::Strikethrough(_) => {
+                stdout.queue(SetAttribute(Attribute::NotCrossedOut))
+            }
+            StyleAttribute::Blink(_) => {
+                stdout.queue(SetAttribute(Attribute::NoBlink))
             }
         };
     }
 }
 
-
 /// Text paired with styling and writing options
 #[derive (Clone)]
 pub struct StyledText {
@@ -0,0 +1,225 @@
+//! Color themes, mapping each ThemeColor to a terminal color
+use std::error::Error;
+use std::sync::{Arc, OnceLock, RwLock};
+use crossterm::style::Color;
+use crate::config::{parse, read_definitions, Value};
+use crate::style::ThemeColor;
+
+/// Colors of the default theme, in ThemeColor order. Other themes fall back to these
+const DEFAULT_COLORS: [Color; ThemeColor::COUNT] = [
+    Color::Reset,           // primary
+    Color::Blue,
+    Color::DarkBlue,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Grey,
+    Color::Black,
+    Color::AnsiValue(7),    // white
+    Color::Reset,           // background
+    Color::DarkGrey,        // comment
+    Color::Magenta,         // keyword
+    Color::DarkMagenta,     // selection
+    Color::Grey,            // status bar
+    Color::Black,           // border
+];
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("light.toml", include_str!("../../themes/light.toml")),
+    ("gruvbox.toml", include_str!("../../themes/gruvbox.toml")),
+    ("nord.toml", include_str!("../../themes/nord.toml")),
+    ("solarized-light.toml", include_str!("../../themes/solarized-light.toml")),
+];
+
+pub struct Theme {
+    pub name: String,
+    /// Made for a dark terminal background
+    pub dark: bool,
+    colors: [Color; ThemeColor::COUNT],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme { name: "default".into(), dark: true, colors: DEFAULT_COLORS }
+    }
+}
+
+impl Theme {
+    /// Read theme from the contents of a theme file
+    pub fn from_source(source: &str) -> Result<Self, Box<dyn Error>> {
+        let table = parse(source)?;
+        let mut theme = Theme {
+            name: table.get("name").and_then(Value::as_str).ok_or("missing name")?.to_string(),
+            dark: table.get("dark").and_then(Value::as_bool).unwrap_or(true),
+            ..Default::default()
+        };
+
+        if let Some(colors) = table.get("colors").and_then(Value::as_table) {
+            for (name, value) in colors {
+                let index = ThemeColor::from_name(name).and_then(|role| role.index())
+                    .ok_or_else(|| format!("unknown color role '{}'", name))?;
+                let color = match value {
+                    Value::String(s) => parse_color(s),
+                    Value::Integer(i) => u8::try_from(*i).ok().map(Color::AnsiValue),
+                    _ => None,
+                };
+                theme.colors[index] = color.ok_or_else(|| format!("invalid color for '{}'", name))?;
+            }
+        }
+        Ok(theme)
+    }
+
+    pub fn color(&self, color: ThemeColor) -> Color {
+        match color {
+            ThemeColor::Rgb(r, g, b) => Color::Rgb { r, g, b },
+            ThemeColor::Indexed(i) => Color::AnsiValue(i),
+            role => role.index().map_or(Color::Reset, |i| self.colors[i]),
+        }
+    }
+}
+
+/// Color from a name like `dark_blue`, `#rrggbb` or a 256 color palette index
+pub fn parse_color(text: &str) -> Option<Color> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() != 6 { return None; }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb { r: channel(0)?, g: channel(2)?, b: channel(4)? });
+    }
+    if let Ok(index) = text.parse::<u8>() {
+        return Some(Color::AnsiValue(index));
+    }
+
+    let color = match text.to_lowercase().replace(['-', ' '], "_").as_str() {
+        "reset" | "default" | "none" => Color::Reset,
+        "black" => Color::Black,
+        "dark_grey" | "dark_gray" => Color::DarkGrey,
+        "red" => Color::Red,
+        "dark_red" => Color::DarkRed,
+        "green" => Color::Green,
+        "dark_green" => Color::DarkGreen,
+        "yellow" => Color::Yellow,
+        "dark_yellow" => Color::DarkYellow,
+        "blue" => Color::Blue,
+        "dark_blue" => Color::DarkBlue,
+        "magenta" => Color::Magenta,
+        "dark_magenta" => Color::DarkMagenta,
+        "cyan" => Color::Cyan,
+        "dark_cyan" => Color::DarkCyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        _ => return None,
+    };
+    Some(color)
+}
+
+/// Every loaded theme
+pub struct Themes {
+    themes: Vec<Arc<Theme>>,
+    /// Files that failed to load, with the reason
+    pub errors: Vec<String>,
+}
+
+impl Themes {
+    /// Load the default and built-in themes, then the user's
+    fn load() -> Self {
+        let mut themes = Themes { themes: vec![Arc::new(Theme::default())], errors: Vec::new() };
+        for (file, source) in BUILTIN {
+            themes.add(file, source);
+        }
+        for (path, source) in read_definitions("themes") {
+            let file = path.display().to_string();
+            match source {
+                Ok(source) => themes.add(&file, &source),
+                Err(e) => themes.errors.push(format!("{}: {}", file, e)),
+            }
+        }
+        themes
+    }
+
+    /// Add theme defined in source, replacing one with the same name
+    fn add(&mut self, file: &str, source: &str) {
+        match Theme::from_source(source) {
+            Ok(theme) => {
+                self.themes.retain(|t| t.name != theme.name);
+                self.themes.push(Arc::new(theme));
+            }
+            Err(e) => self.errors.push(format!("{}: {}", file, e)),
+        }
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<Arc<Theme>> {
+        self.themes.iter().find(|t| t.name == name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.themes.iter().map(|t| t.name.as_str()).collect()
+    }
+}
+
+/// Themes, loaded on first use
+pub fn themes() -> &'static Themes {
+    static THEMES: OnceLock<Themes> = OnceLock::new();
+    THEMES.get_or_init(Themes::load)
+}
+
+static CURRENT: RwLock<Option<Arc<Theme>>> = RwLock::new(None);
+
+/// Theme colors are currently drawn with
+pub fn current_theme() -> Arc<Theme> {
+    static DEFAULT: OnceLock<Arc<Theme>> = OnceLock::new();
+    let current = CURRENT.read().ok().and_then(|current| current.clone());
+    current.unwrap_or_else(|| DEFAULT.get_or_init(|| Arc::new(Theme::default())).clone())
+}
+
+pub fn set_theme(theme: Arc<Theme>) {
+    if let Ok(mut current) = CURRENT.write() {
+        *current = Some(theme);
+    }
+}
+
+/// Switch to the theme called name
+pub fn set_colorscheme(name: &str) -> Result<(), String> {
+    let theme = themes().by_name(name).ok_or_else(|| format!("Unknown color scheme '{}'", name))?;
+    set_theme(theme);
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(parse_color("#ff8000"), Some(Color::Rgb { r: 255, g: 128, b: 0 }));
+        assert_eq!(parse_color("dark-blue"), Some(Color::DarkBlue));
+        assert_eq!(parse_color("Gray"), Some(Color::Grey));
+        assert_eq!(parse_color("208"), Some(Color::AnsiValue(208)));
+        assert_eq!(parse_color("#ff80"), None);
+        assert_eq!(parse_color("purple"), None);
+    }
+
+    #[test]
+    fn missing_colors_fall_back_to_default() {
+        let theme = Theme::from_source("name = \"t\"\ndark = false\n[colors]\nkeyword = \"#010203\"\nborder = 17\n").unwrap();
+        assert!(!theme.dark);
+        assert_eq!(theme.color(ThemeColor::Keyword), Color::Rgb { r: 1, g: 2, b: 3 });
+        assert_eq!(theme.color(ThemeColor::Border), Color::AnsiValue(17));
+        assert_eq!(theme.color(ThemeColor::Blue), Color::Blue);
+
+        assert!(Theme::from_source("name = \"t\"\n[colors]\nkeyword = \"nope\"\n").is_err());
+        assert!(Theme::from_source("name = \"t\"\n[colors]\nnope = \"red\"\n").is_err());
+    }
+
+    #[test]
+    fn builtin_themes_load() {
+        let mut themes = Themes { themes: Vec::new(), errors: Vec::new() };
+        for (file, source) in BUILTIN {
+            themes.add(file, source);
+        }
+        assert!(themes.errors.is_empty(), "{:?}", themes.errors);
+        assert!(themes.themes.iter().any(|t| t.dark));
+        assert!(themes.themes.iter().any(|t| !t.dark));
+    }
+}
@@ -0,0 +1,250 @@
+//! Highlighting results kept per line, updated incrementally.
+//! Lines near the last valid one are highlighted on the spot, longer stretches in a worker thread
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use crate::event::{waker, Wake};
+use crate::syntax::{highlight_line, Grammar, LineState, Span};
+use crate::textedit::buffer::Changes;
+
+/// Most lines highlighted without the worker
+pub const SYNC_LINES: usize = 500;
+
+#[derive(Clone, Default)]
+struct CachedLine {
+    /// State the line before ended in
+    state: LineState,
+    spans: Vec<Span>,
+}
+
+/// Lines start.. to highlight, tagged with the cache's generation when sent
+struct Job {
+    generation: usize,
+    start: usize,
+    state: LineState,
+    lines: Vec<String>,
+}
+
+struct JobResult {
+    generation: usize,
+    start: usize,
+    /// Spans of each line, and the state it ended in
+    lines: Vec<(Vec<Span>, LineState)>,
+}
+
+struct Worker {
+    jobs: Sender<Job>,
+    results: Receiver<JobResult>,
+}
+
+impl Worker {
+    fn spawn(grammar: Arc<Grammar>) -> Self {
+        let (jobs, job_receiver) = channel::<Job>();
+        let (result_sender, results) = channel();
+        let wake = waker();
+
+        // stops once the cache, and with it the job sender, is dropped
+        thread::spawn(move || {
+            while let Ok(mut job) = job_receiver.recv() {
+                // only the newest job is worth doing
+                while let Ok(newer) = job_receiver.try_recv() {
+                    job = newer;
+                }
+
+                let mut state = job.state;
+                let mut lines = Vec::with_capacity(job.lines.len());
+                for line in job.lines.iter() {
+                    let (spans, next) = highlight_line(&grammar, line, state);
+                    lines.push((spans, next));
+                    state = next;
+                }
+
+                let result = JobResult { generation: job.generation, start: job.start, lines };
+                if result_sender.send(result).is_err() { break; }
+                // the window takes the results in when the main loop collects its requests
+                if let Some(wake) = &wake {
+                    let _ = wake.send(Wake::Collect);
+                }
+            }
+        });
+
+        Worker { jobs, results }
+    }
+}
+
+pub struct HighlightCache {
+    grammar: Arc<Grammar>,
+    lines: Vec<CachedLine>,
+    /// Lines before this are up to date, ones after may be stale
+    valid: usize,
+    /// Bumped on every edit, so results of jobs sent before it are dropped
+    generation: usize,
+    /// Generation and last line of the job being worked on
+    pending: Option<(usize, usize)>,
+    worker: Option<Worker>,
+}
+
+impl HighlightCache {
+    pub fn new(grammar: Arc<Grammar>) -> Self {
+        HighlightCache { grammar, lines: Vec::new(), valid: 0, generation: 0, pending: None, worker: None }
+    }
+
+    pub fn grammar(&self) -> &Arc<Grammar> {
+        &self.grammar
+    }
+
+    /// Move cached lines along with lines added or removed, and invalidate from the first edited line.
+    /// Stale spans are kept to be shown until they are highlighted again
+    pub fn apply_changes(&mut self, changes: &Changes) {
+        let Some(first_line) = changes.first_line else { return };
+        for (at, delta) in changes.shifts.iter() {
+            let at = std::cmp::min(*at, self.lines.len());
+            if *delta >= 0 {
+                let added = std::iter::repeat_n(CachedLine::default(), delta.unsigned_abs());
+                self.lines.splice(at..at, added);
+            } else {
+                let end = std::cmp::min(at + delta.unsigned_abs(), self.lines.len());
+                self.lines.drain(at..end);
+            }
+        }
+        self.valid = std::cmp::min(self.valid, first_line);
+        self.generation += 1;
+    }
+
+    /// Make lines up to last valid for a buffer of line_count lines, text(line) giving their content.
+    /// Short stretches are highlighted right away, longer ones are sent to the worker
+    pub fn prepare(&mut self, last: usize, line_count: usize, text: &dyn Fn(usize) -> String) {
+        self.lines.resize(line_count, CachedLine::default());
+        self.valid = std::cmp::min(self.valid, line_count);
+        let last = std::cmp::min(last, line_count.saturating_sub(1));
+        if self.valid > last || line_count == 0 { return; }
+
+        if last - self.valid < SYNC_LINES {
+            for line in self.valid..=last {
+                let (spans, next) = highlight_line(&self.grammar, &text(line), self.lines[line].state);
+                self.store(line, spans, next);
+            }
+            self.valid = last + 1;
+            return;
+        }
+
+        if self.pending.is_some_and(|(generation, end)| generation == self.generation && end >= last) { return; }
+        let job = Job {
+            generation: self.generation,
+            start: self.valid,
+            state: self.lines[self.valid].state,
+            lines: (self.valid..=last).map(text).collect(),
+        };
+        let grammar = self.grammar.clone();
+        let worker = self.worker.get_or_insert_with(|| Worker::spawn(grammar));
+        if worker.jobs.send(job).is_ok() {
+            self.pending = Some((self.generation, last));
+        }
+    }
+
+    /// Take in results from the worker. True if any made the cache change.
+    /// The window calls it when collecting requests, which the worker wakes the main loop for
+    pub fn receive(&mut self) -> bool {
+        let Some(worker) = &self.worker else { return false };
+        let results: Vec<JobResult> = worker.results.try_iter().collect();
+
+        let mut changed = false;
+        for result in results {
+            // edited since, or already caught up
+            if result.generation != self.generation || result.start > self.valid { continue; }
+
+            let end = std::cmp::min(result.start + result.lines.len(), self.lines.len());
+            for (line, (spans, next)) in (result.start..end).zip(result.lines) {
+                self.store(line, spans, next);
+            }
+            if end > self.valid {
+                self.valid = end;
+                changed = true;
+            }
+            self.pending = None;
+        }
+        changed
+    }
+
+    /// Spans of line, possibly stale, empty if never highlighted
+    pub fn spans(&self, line: usize) -> &[Span] {
+        self.lines.get(line).map(|l| l.spans.as_slice()).unwrap_or_default()
+    }
+
+    /// Spans of line holding text, highlighted now from the state the line before was cached to end in
+    pub fn highlight(&self, line: usize, text: &str) -> Vec<Span> {
+        let state = self.lines.get(line).map(|l| l.state).unwrap_or_default();
+        highlight_line(&self.grammar, text, state).0
+    }
+
+    /// Whether line is up to date
+    pub fn is_valid(&self, line: usize) -> bool {
+        line < self.valid
+    }
+
+    fn store(&mut self, line: usize, spans: Vec<Span>, next: LineState) {
+        self.lines[line].spans = spans;
+        if let Some(following) = self.lines.get_mut(line + 1) {
+            following.state = next;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use crate::event::wake_channel;
+    use crate::syntax::{registry, Scope};
+
+    fn cache() -> HighlightCache {
+        HighlightCache::new(registry().by_name("rust").unwrap())
+    }
+
+    #[test]
+    fn invalidates_from_edited_line() {
+        let mut lines = ["let a = 1;", "/* open", "still */ let"];
+        let mut cache = cache();
+
+        cache.prepare(2, 3, &|l| lines[l].to_string());
+        assert!(cache.is_valid(2));
+        assert_eq!(cache.spans(2).last().map(|s| s.scope), Some(Scope::Keyword));
+
+        // closing the comment on line 1 changes line 2
+        lines[1] = "/* open */";
+        cache.apply_changes(&Changes { first_line: Some(1), last_line: 1, shifts: Vec::new() });
+        assert!(cache.is_valid(0));
+        assert!(!cache.is_valid(1));
+
+        cache.prepare(2, 3, &|l| lines[l].to_string());
+        assert_eq!(cache.spans(2).first().map(|s| s.scope), Some(Scope::Keyword));
+    }
+
+    #[test]
+    fn shifts_with_added_lines() {
+        let mut cache = cache();
+        cache.prepare(1, 2, &|l| if l == 0 { "fn".into() } else { "let".into() });
+
+        // two lines added after line 0
+        cache.apply_changes(&Changes { first_line: Some(0), last_line: 2, shifts: vec![(1, 2)] });
+        assert_eq!(cache.spans(3).len(), 1);
+        assert!(cache.spans(1).is_empty());
+    }
+
+    #[test]
+    fn long_stretches_in_background() {
+        let wakes = wake_channel();
+        let mut cache = cache();
+        let count = SYNC_LINES * 3;
+        cache.prepare(count - 1, count, &|_| "let x = \"s\";".into());
+        assert!(!cache.is_valid(0));
+
+        // the worker wakes the main loop, instead of it polling
+        while !cache.receive() {
+            wakes.recv_timeout(Duration::from_secs(5)).expect("worker didn't wake the main loop");
+        }
+        assert!(cache.is_valid(count - 1));
+        assert_eq!(cache.spans(count - 1).len(), 2);
+    }
+}
@@ -0,0 +1,147 @@
+//! Language definition read from a grammar file
+use std::collections::HashMap;
+use std::error::Error;
+use crate::config::{parse, Table, Value};
+use crate::syntax::Scope;
+
+/// Text between a start and an end delimiter, like a string or comment
+#[derive(Debug)]
+pub struct Region {
+    pub start: Vec<char>,
+    /// None runs to the end of the line
+    pub end: Option<Vec<char>>,
+    pub scope: Scope,
+    /// Char making the next one part of the region, so it can't end it
+    pub escape: Option<char>,
+    /// Start delimiters inside open another level that needs its own end
+    pub nested: bool,
+    /// May continue on following lines. Otherwise it's only matched when ending on the same line
+    pub multiline: bool,
+    /// Only match as the first non-blank text of a line
+    pub at_line_start: bool,
+    /// Longest content between the delimiters that matches
+    pub max_length: Option<usize>,
+    /// Only match when the end is followed by this, after optional spaces
+    pub followed_by: Option<Vec<char>>,
+}
+
+/// Words picked out by their surroundings rather than by name
+#[derive(Debug)]
+pub struct WordRule {
+    pub scope: Scope,
+    /// Text right before the word, included in the token
+    pub prefix: Option<Vec<char>>,
+    /// Text right after the word, not included
+    pub followed_by: Option<Vec<char>>,
+    /// Allow spaces between the word and followed_by
+    pub skip_space: bool,
+    /// Word starts with an uppercase letter
+    pub capitalized: bool,
+    pub at_line_start: bool,
+}
+
+#[derive(Debug)]
+pub struct Grammar {
+    pub name: String,
+    pub extensions: Vec<String>,
+    /// Highlight numbers
+    pub numbers: bool,
+    /// Chars besides alphanumerics and `_` that are part of words
+    pub word_chars: Vec<char>,
+    pub keywords: HashMap<String, Scope>,
+    /// Sorted by start length, longest first
+    pub regions: Vec<Region>,
+    pub words: Vec<WordRule>,
+}
+
+impl Grammar {
+    /// Read grammar from the contents of a definition file
+    pub fn from_source(source: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_table(&parse(source)?)
+    }
+
+    pub fn from_table(table: &Table) -> Result<Self, Box<dyn Error>> {
+        let name = table.get("name").and_then(Value::as_str).ok_or("missing name")?.to_string();
+
+        let mut keywords = HashMap::new();
+        if let Some(groups) = table.get("keywords").and_then(Value::as_table) {
+            for (scope, words) in groups {
+                let scope = scope_named(scope)?;
+                for word in words.as_strings() {
+                    keywords.insert(word, scope);
+                }
+            }
+        }
+
+        let mut regions = Vec::new();
+        for rule in tables(table, "region")? {
+            let start = chars(rule, "start").ok_or("region without start")?;
+            if start.is_empty() { return Err("region with empty start".into()); }
+            regions.push(Region {
+                start,
+                end: chars(rule, "end").filter(|end| !end.is_empty()),
+                scope: scope_of(rule)?,
+                escape: rule.get("escape").and_then(Value::as_str).and_then(|e| e.chars().next()),
+                nested: flag(rule, "nested"),
+                multiline: flag(rule, "multiline"),
+                at_line_start: flag(rule, "at_line_start"),
+                max_length: rule.get("max_length").and_then(Value::as_integer).map(|n| n.max(0) as usize),
+                followed_by: chars(rule, "followed_by"),
+            });
+        }
+        regions.sort_by_key(|r| std::cmp::Reverse(r.start.len()));
+
+        let mut words = Vec::new();
+        for rule in tables(table, "word")? {
+            words.push(WordRule {
+                scope: scope_of(rule)?,
+                prefix: chars(rule, "prefix").filter(|p| !p.is_empty()),
+                followed_by: chars(rule, "followed_by").filter(|f| !f.is_empty()),
+                skip_space: flag(rule, "skip_space"),
+                capitalized: flag(rule, "capitalized"),
+                at_line_start: flag(rule, "at_line_start"),
+            });
+        }
+
+        Ok(Grammar {
+            name,
+            extensions: table.get("extensions").map(Value::as_strings).unwrap_or_default(),
+            numbers: table.get("numbers").and_then(Value::as_bool).unwrap_or(true),
+            word_chars: table.get("word_chars").and_then(Value::as_str).unwrap_or_default().chars().collect(),
+            keywords,
+            regions,
+            words,
+        })
+    }
+
+    pub fn is_word_char(&self, ch: char) -> bool {
+        ch.is_alphanumeric() || ch == '_' || self.word_chars.contains(&ch)
+    }
+}
+
+fn scope_named(name: &str) -> Result<Scope, Box<dyn Error>> {
+    Scope::from_name(name).ok_or_else(|| format!("unknown scope '{}'", name).into())
+}
+
+fn scope_of(rule: &Table) -> Result<Scope, Box<dyn Error>> {
+    scope_named(rule.get("scope").and_then(Value::as_str).ok_or("rule without scope")?)
+}
+
+fn chars(rule: &Table, key: &str) -> Option<Vec<char>> {
+    rule.get(key).and_then(Value::as_str).map(|s| s.chars().collect())
+}
+
+fn flag(rule: &Table, key: &str) -> bool {
+    rule.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// Tables of the `[[key]]` array
+fn tables<'a>(table: &'a Table, key: &str) -> Result<Vec<&'a Table>, Box<dyn Error>> {
+    match table.get(key) {
+        None => Ok(Vec::new()),
+        Some(Value::Array(items)) => items.iter()
+            .map(|item| item.as_table().ok_or_else(|| format!("'{}' must be a list of tables", key).into()))
+            .collect(),
+        Some(_) => Err(format!("'{}' must be a list of tables", key).into()),
+    }
+}
@@ -0,0 +1,242 @@
+//! Splitting lines into highlighted tokens
+use crate::syntax::{Grammar, Region, Scope, WordRule};
+
+/// What the previous line left open
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LineState {
+    #[default]
+    Normal,
+    /// Inside the region at index of the grammar's regions, depth levels deep if nested
+    Region { index: usize, depth: usize },
+}
+
+/// Highlighted chars start..end of a line, in char indices
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub scope: Scope,
+}
+
+/// Tokens of line, given the state the previous line ended in, and the state this one ends in
+pub fn highlight_line(grammar: &Grammar, line: &str, state: LineState) -> (Vec<Span>, LineState) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut spans = Vec::new();
+    let mut i = 0;
+
+    // finish the region left open
+    if let LineState::Region { index, depth } = state {
+        let Some(region) = grammar.regions.get(index) else { return (spans, LineState::Normal) };
+        match region_end(region, &chars, 0, depth) {
+            Ok(end) => {
+                push(&mut spans, 0, end, region.scope);
+                i = end;
+            }
+            Err(depth) => {
+                push(&mut spans, 0, chars.len(), region.scope);
+                return (spans, LineState::Region { index, depth });
+            }
+        }
+    }
+
+    let line_start = chars.iter().position(|c| !c.is_whitespace()).unwrap_or(chars.len());
+    'outer: while i < chars.len() {
+        for (index, region) in grammar.regions.iter().enumerate() {
+            if region.at_line_start && i != line_start { continue; }
+            if !starts_with(&chars, i, &region.start) { continue; }
+
+            let body = i + region.start.len();
+            match region_end(region, &chars, body, 1) {
+                Ok(end) => {
+                    let length = end - body - region.end.as_ref().map_or(0, |e| e.len());
+                    if region.max_length.is_some_and(|max| length > max) { continue; }
+                    if let Some(next) = &region.followed_by {
+                        if !starts_with(&chars, skip_spaces(&chars, end), next) { continue; }
+                    }
+                    push(&mut spans, i, end, region.scope);
+                    i = end;
+                    continue 'outer;
+                }
+                Err(depth) if region.multiline && region.followed_by.is_none() => {
+                    push(&mut spans, i, chars.len(), region.scope);
+                    return (spans, LineState::Region { index, depth });
+                }
+                Err(_) => (),
+            }
+        }
+
+        // words with a prefix, like `$var`
+        for rule in grammar.words.iter() {
+            let Some(prefix) = &rule.prefix else { continue };
+            if !starts_with(&chars, i, prefix) { continue; }
+            let start = i + prefix.len();
+            let end = word_end(grammar, &chars, start);
+            if end > start && rule_matches(rule, &chars, start, end, i == line_start) {
+                push(&mut spans, i, end, rule.scope);
+                i = end;
+                continue 'outer;
+            }
+        }
+
+        let ch = chars[i];
+        if !grammar.is_word_char(ch) || (i > 0 && grammar.is_word_char(chars[i - 1])) {
+            i += 1;
+            continue;
+        }
+
+        if ch.is_ascii_digit() {
+            let end = number_end(grammar, &chars, i);
+            if grammar.numbers {
+                push(&mut spans, i, end, Scope::Number);
+            }
+            i = end;
+            continue;
+        }
+
+        let end = word_end(grammar, &chars, i);
+        let word: String = chars[i..end].iter().collect();
+        let scope = grammar.keywords.get(&word).copied().or_else(|| {
+            grammar.words.iter()
+                .find(|rule| rule.prefix.is_none() && rule_matches(rule, &chars, i, end, i == line_start))
+                .map(|rule| rule.scope)
+        });
+        if let Some(scope) = scope {
+            push(&mut spans, i, end, scope);
+        }
+        i = end;
+    }
+
+    (spans, LineState::Normal)
+}
+
+fn push(spans: &mut Vec<Span>, start: usize, end: usize, scope: Scope) {
+    if start < end {
+        spans.push(Span { start, end, scope });
+    }
+}
+
+fn starts_with(chars: &[char], at: usize, pattern: &[char]) -> bool {
+    chars.get(at..at + pattern.len()).is_some_and(|s| s == pattern)
+}
+
+fn skip_spaces(chars: &[char], at: usize) -> usize {
+    at + chars[at..].iter().take_while(|c| **c == ' ' || **c == '\t').count()
+}
+
+/// Index after the region's end searching from `from`, depth levels deep,
+/// or the depth still open at the end of the line
+fn region_end(region: &Region, chars: &[char], from: usize, mut depth: usize) -> Result<usize, usize> {
+    let Some(end) = &region.end else { return Ok(chars.len()) };
+
+    let mut i = from;
+    while i < chars.len() {
+        if region.escape == Some(chars[i]) {
+            i += 2;
+        } else if starts_with(chars, i, end) {
+            i += end.len();
+            depth -= 1;
+            if depth == 0 { return Ok(i); }
+        } else if region.nested && starts_with(chars, i, &region.start) {
+            i += region.start.len();
+            depth += 1;
+        } else {
+            i += 1;
+        }
+    }
+    Err(depth)
+}
+
+fn word_end(grammar: &Grammar, chars: &[char], from: usize) -> usize {
+    from + chars[from..].iter().take_while(|c| grammar.is_word_char(**c)).count()
+}
+
+/// Digits and letters of a number like `0x1f`, `1_000u32` or `2.5e3`
+fn number_end(grammar: &Grammar, chars: &[char], from: usize) -> usize {
+    let mut i = word_end(grammar, chars, from);
+    while chars.get(i) == Some(&'.') && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) {
+        i = word_end(grammar, chars, i + 1);
+    }
+    i
+}
+
+fn rule_matches(rule: &WordRule, chars: &[char], start: usize, end: usize, line_start: bool) -> bool {
+    if rule.at_line_start && !line_start { return false; }
+    if rule.capitalized && !chars[start].is_uppercase() { return false; }
+    match &rule.followed_by {
+        Some(next) => {
+            let at = if rule.skip_space { skip_spaces(chars, end) } else { end };
+            starts_with(chars, at, next)
+        }
+        None => true,
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::syntax::registry;
+
+    fn tokenize(grammar: &str, line: &str, state: LineState) -> (Vec<(String, Scope)>, LineState) {
+        let grammar = registry().by_name(grammar).unwrap();
+        let chars: Vec<char> = line.chars().collect();
+        let (spans, state) = highlight_line(&grammar, line, state);
+        let tokens = spans.iter().map(|s| (chars[s.start..s.end].iter().collect(), s.scope)).collect();
+        (tokens, state)
+    }
+
+    fn scope_of(tokens: &[(String, Scope)], text: &str) -> Option<Scope> {
+        tokens.iter().find(|(t, _)| t == text).map(|(_, s)| *s)
+    }
+
+    #[test]
+    fn rust_tokens() {
+        let (tokens, state) = tokenize("rust", "let s: String = format!(\"a \\\" {}\", 1.5); // done", LineState::Normal);
+        assert_eq!(state, LineState::Normal);
+        assert_eq!(scope_of(&tokens, "let"), Some(Scope::Keyword));
+        assert_eq!(scope_of(&tokens, "String"), Some(Scope::Type));
+        assert_eq!(scope_of(&tokens, "format"), Some(Scope::Macro));
+        assert_eq!(scope_of(&tokens, "\"a \\\" {}\""), Some(Scope::String));
+        assert_eq!(scope_of(&tokens, "1.5"), Some(Scope::Number));
+        assert_eq!(scope_of(&tokens, "// done"), Some(Scope::Comment));
+    }
+
+    #[test]
+    fn rust_chars_and_lifetimes() {
+        let (tokens, _) = tokenize("rust", "fn f(c: &'a str) -> char { '\\n' }", LineState::Normal);
+        assert_eq!(scope_of(&tokens, "'a"), Some(Scope::Label));
+        assert_eq!(scope_of(&tokens, "'\\n'"), Some(Scope::String));
+        assert_eq!(scope_of(&tokens, "f"), Some(Scope::Function));
+    }
+
+    #[test]
+    fn multiline_block_comment() {
+        let (first, state) = tokenize("rust", "x /* a /* b */", LineState::Normal);
+        assert_eq!(scope_of(&first, "/* a /* b */"), Some(Scope::Comment));
+        assert!(matches!(state, LineState::Region { depth: 1, .. }));
+
+        let (second, state) = tokenize("rust", "c */ let", state);
+        assert_eq!(scope_of(&second, "c */"), Some(Scope::Comment));
+        assert_eq!(scope_of(&second, "let"), Some(Scope::Keyword));
+        assert_eq!(state, LineState::Normal);
+    }
+
+    #[test]
+    fn markdown_tokens() {
+        let (tokens, _) = tokenize("markdown", "## Title", LineState::Normal);
+        assert_eq!(scope_of(&tokens, "## Title"), Some(Scope::Heading));
+
+        let (tokens, _) = tokenize("markdown", "some **bold** and `code` but 2 * 3", LineState::Normal);
+        assert_eq!(scope_of(&tokens, "**bold**"), Some(Scope::Strong));
+        assert_eq!(scope_of(&tokens, "`code`"), Some(Scope::Code));
+        assert_eq!(tokens.len(), 2);
+    }
+
+    #[test]
+    fn json_keys() {
+        let (tokens, _) = tokenize("json", "{\"key\": \"value\", \"n\": null}", LineState::Normal);
+        assert_eq!(scope_of(&tokens, "\"key\""), Some(Scope::Key));
+        assert_eq!(scope_of(&tokens, "\"value\""), Some(Scope::String));
+        assert_eq!(scope_of(&tokens, "null"), Some(Scope::Constant));
+    }
+}
@@ -0,0 +1,191 @@
+//! Syntax highlighting from grammar definition files.
+//! Built-in grammars live in `grammars/`, files in the user's `grammars/` config directory
+//! are loaded on top of them and replace built-ins with the same name
+mod grammar;
+mod highlight;
+mod cache;
+
+pub use grammar::*;
+pub use highlight::*;
+pub use cache::*;
+
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use crate::config::read_definitions;
+use crate::style::{StyleAttribute, ThemeColor};
+use crate::textedit::filetype::FileType;
+
+/// Kind of token, mapped to a style
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Scope {
+    Keyword,
+    Type,
+    Constant,
+    String,
+    Comment,
+    Number,
+    Function,
+    Macro,
+    Variable,
+    Attribute,
+    Label,
+    Heading,
+    Emphasis,
+    Strong,
+    Code,
+    Link,
+    Key,
+    Tag,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 18] = [
+        Scope::Keyword, Scope::Type, Scope::Constant, Scope::String, Scope::Comment, Scope::Number,
+        Scope::Function, Scope::Macro, Scope::Variable, Scope::Attribute, Scope::Label, Scope::Heading,
+        Scope::Emphasis, Scope::Strong, Scope::Code, Scope::Link, Scope::Key, Scope::Tag,
+    ];
+
+    /// Text in strings and comments, where brackets and quotes aren't code
+    pub fn is_text(&self) -> bool {
+        matches!(self, Scope::String | Scope::Comment)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scope::Keyword => "keyword",
+            Scope::Type => "type",
+            Scope::Constant => "constant",
+            Scope::String => "string",
+            Scope::Comment => "comment",
+            Scope::Number => "number",
+            Scope::Function => "function",
+            Scope::Macro => "macro",
+            Scope::Variable => "variable",
+            Scope::Attribute => "attribute",
+            Scope::Label => "label",
+            Scope::Heading => "heading",
+            Scope::Emphasis => "emphasis",
+            Scope::Strong => "strong",
+            Scope::Code => "code",
+            Scope::Link => "link",
+            Scope::Key => "key",
+            Scope::Tag => "tag",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.name() == name)
+    }
+
+    /// Default style of tokens in scope
+    pub fn style(&self) -> Vec<StyleAttribute> {
+        match self {
+            Scope::Keyword => vec![StyleAttribute::Color(ThemeColor::Keyword)],
+            Scope::Type => vec![StyleAttribute::Color(ThemeColor::Yellow)],
+            Scope::Constant => vec![StyleAttribute::Color(ThemeColor::Yellow)],
+            Scope::String => vec![StyleAttribute::Color(ThemeColor::Green)],
+            Scope::Comment => vec![StyleAttribute::Color(ThemeColor::Comment), StyleAttribute::Italic(true)],
+            Scope::Number => vec![StyleAttribute::Color(ThemeColor::Yellow)],
+            Scope::Function => vec![StyleAttribute::Color(ThemeColor::Blue)],
+            Scope::Macro => vec![StyleAttribute::Color(ThemeColor::Magenta)],
+            Scope::Variable => vec![StyleAttribute::Color(ThemeColor::Blue)],
+            Scope::Attribute => vec![StyleAttribute::Color(ThemeColor::Gray)],
+            Scope::Label => vec![StyleAttribute::Color(ThemeColor::Yellow)],
+            Scope::Heading => vec![StyleAttribute::Color(ThemeColor::Blue), StyleAttribute::Bold(true)],
+            Scope::Emphasis => vec![StyleAttribute::Italic(true)],
+            Scope::Strong => vec![StyleAttribute::Bold(true)],
+            Scope::Code => vec![StyleAttribute::Color(ThemeColor::Green)],
+            Scope::Link => vec![StyleAttribute::Color(ThemeColor::Blue)],
+            Scope::Key => vec![StyleAttribute::Color(ThemeColor::Blue)],
+            Scope::Tag => vec![StyleAttribute::Color(ThemeColor::Magenta)],
+        }
+    }
+}
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("rust.toml", include_str!("../../grammars/rust.toml")),
+    ("markdown.toml", include_str!("../../grammars/markdown.toml")),
+    ("toml.toml", include_str!("../../grammars/toml.toml")),
+    ("json.toml", include_str!("../../grammars/json.toml")),
+    ("python.toml", include_str!("../../grammars/python.toml")),
+    ("shell.toml", include_str!("../../grammars/shell.toml")),
+];
+
+/// Every loaded grammar
+pub struct Registry {
+    grammars: Vec<Arc<Grammar>>,
+    /// Files that failed to load, with the reason
+    pub errors: Vec<String>,
+}
+
+impl Registry {
+    /// Load built-in grammars, then the user's
+    fn load() -> Self {
+        let mut registry = Registry { grammars: Vec::new(), errors: Vec::new() };
+        for (file, source) in BUILTIN {
+            registry.add(file, source);
+        }
+
+        for (path, source) in read_definitions("grammars") {
+            let file = path.display().to_string();
+            match source {
+                Ok(source) => registry.add(&file, &source),
+                Err(e) => registry.errors.push(format!("{}: {}", file, e)),
+            }
+        }
+        registry
+    }
+
+    /// Add grammar defined in source, replacing one with the same name
+    fn add(&mut self, file: &str, source: &str) {
+        match Grammar::from_source(source) {
+            Ok(grammar) => {
+                self.grammars.retain(|g| g.name != grammar.name);
+                self.grammars.push(Arc::new(grammar));
+            }
+            Err(e) => self.errors.push(format!("{}: {}", file, e)),
+        }
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<Arc<Grammar>> {
+        self.grammars.iter().find(|g| g.name == name).cloned()
+    }
+
+    /// Grammar of the file's filetype, or else one listing its extension
+    pub fn for_path(&self, path: &Path) -> Option<Arc<Grammar>> {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+        self.by_name(FileType::from_path(path).name)
+            .or_else(|| self.grammars.iter().find(|g| g.extensions.contains(&extension)).cloned())
+    }
+}
+
+/// Grammars, loaded on first use
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::load)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builtin_grammars_load() {
+        let mut registry = Registry { grammars: Vec::new(), errors: Vec::new() };
+        for (file, source) in BUILTIN {
+            registry.add(file, source);
+        }
+        assert!(registry.errors.is_empty(), "{:?}", registry.errors);
+        for name in ["rust", "markdown", "toml", "json", "python", "shell"] {
+            assert!(registry.by_name(name).is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn scope_names() {
+        for scope in Scope::ALL {
+            assert_eq!(Scope::from_name(scope.name()), Some(scope));
+        }
+    }
+}
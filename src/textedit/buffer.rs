@@ -20,8 +20,8 @@ impl Metrics {
             lb > &gap_index
         });
         if let Some(i) = i {
-            self.new_lines_raw.insert(gap_index, i);
             self.new_lines_order.insert(i, gap_index);
+            self.reindex(i);
         } else {
             self.new_lines_raw.insert(gap_index, self.new_lines_order.len());
             self.new_lines_order.push(gap_index);
@@ -31,11 +31,20 @@ impl Metrics {
         gap_index += 1;
         if let Some(order) = self.new_lines_raw.remove(&gap_index) {
             self.new_lines_order.remove(order);
+            self.reindex(order);
         }
     }
     pub fn remove_linebreak_order(&mut self, order: usize) {
         let gap_index = self.new_lines_order.remove(order);
         self.new_lines_raw.remove(&gap_index);
+        self.reindex(order);
+    }
+
+    /// Keep order lookups valid after an insert/remove shifted everything from `from` onward
+    fn reindex(&mut self, from: usize) {
+        for (i, lb) in self.new_lines_order.iter().enumerate().skip(from) {
+            self.new_lines_raw.insert(*lb, i);
+        }
     }
 
     pub fn get_new_line_order(&self) -> &Vec<usize> {
@@ -43,12 +52,43 @@ impl Metrics {
     }
 }
 
+/// Edits since changes were last taken, for keeping line based state outside the buffer in sync
+#[derive(Default, Debug, PartialEq)]
+pub struct Changes {
+    /// Lowest line with modified content
+    pub first_line: Option<usize>,
+    /// Highest line with modified content, counted after the changes
+    pub last_line: usize,
+    /// Linebreaks added or removed, in order. (at, delta) moves lines from at onward by delta,
+    /// removed lines merge into the line before at
+    pub shifts: Vec<(usize, isize)>,
+}
+
+impl Changes {
+    /// Where a line from before the changes is now
+    pub fn map_line(&self, mut line: usize) -> usize {
+        for (at, delta) in self.shifts.iter() {
+            if line < *at { continue; }
+            line = if *delta >= 0 {
+                line + delta.unsigned_abs()
+            } else {
+                cmp::max(line.saturating_sub(delta.unsigned_abs()), at - 1)
+            };
+        }
+        line
+    }
+    pub fn is_empty(&self) -> bool {
+        self.first_line.is_none()
+    }
+}
+
 pub struct TextBuffer {
     content: Vec<fixed_char>,
     cursor: usize,
     gap_end: usize,
     operations: Vec<Box<dyn TextBufferOperation>>,
     metrics: Metrics,
+    changes: Changes,
 }
 
 
@@ -73,7 +113,8 @@ impl TextBuffer {
                 length: 0,
                 new_lines_order: vec![0],
                 new_lines_raw: HashMap::from([(0, 0)]),
-            }
+            },
+            changes: Changes::default(),
         }
     }
     pub fn apply(&mut self, mut operation: Box<dyn TextBufferOperation>) {
@@ -155,6 +196,9 @@ impl TextBuffer {
     pub fn get_new_lines(&self) -> &Vec<usize> {
         &self.metrics.get_new_line_order()
     }
+    pub fn line_count(&self) -> usize {
+        self.metrics.get_new_line_order().len()
+    }
     pub fn set_linebreak_at(&mut self, gap_index: usize) {
         self.metrics.set_linebreak_raw(gap_index);
     }
@@ -162,7 +206,92 @@ impl TextBuffer {
         self.metrics.remove_linebreak_raw(gap_index);
     }
 
+    /// Record an edit at logical index which added (or removed, if negative) linebreaks.
+    /// Insertions are recorded before they happen, deletions after
+    pub fn mark_edit(&mut self, index: usize, linebreaks: isize) {
+        // linebreaks left inside the gap mid operation are stale, skip them
+        let gap = self.gap_end - self.cursor;
+        let line = self.metrics.get_new_line_order().iter()
+            .filter(|lb| **lb <= index || (**lb >= self.gap_end && **lb - gap <= index))
+            .count() - 1;
+        // lines edited before move along with the linebreaks
+        let mut last = if self.changes.is_empty() { line } else { self.changes.last_line };
+        if last > line {
+            last = if linebreaks >= 0 { last + linebreaks.unsigned_abs() } else { cmp::max(last.saturating_sub(linebreaks.unsigned_abs()), line) };
+        }
+        self.changes.last_line = cmp::max(last, line + linebreaks.max(0).unsigned_abs());
+        self.changes.first_line = Some(self.changes.first_line.map_or(line, |first| cmp::min(first, line)));
+        if linebreaks != 0 {
+            self.changes.shifts.push((line + 1, linebreaks));
+        }
+    }
+    /// Edits since last call
+    pub fn take_changes(&mut self) -> Changes {
+        std::mem::take(&mut self.changes)
+    }
+
+
+
+
+    // LINE ACCESS
+    // all indices here are logical, meaning the gap is skipped over
+
+    /// Map a raw index (into content) to a logical one
+    fn to_logical(&self, raw: usize) -> usize {
+        if raw <= self.cursor {
+            raw
+        } else {
+            raw - (self.gap_end - self.cursor)
+        }
+    }
+    /// Character at logical index
+    pub fn char_at(&self, index: usize) -> Option<char> {
+        if index >= self.metrics.length { return None; }
 
+        let raw = if index < self.cursor {
+            index
+        } else {
+            index + (self.gap_end - self.cursor)
+        };
+        self.content.get(raw).map(|c| *c as char)
+    }
+    /// Line the cursor is currently on
+    pub fn cursor_line(&self) -> usize {
+        self.line_of(self.cursor)
+    }
+    /// Line containing logical index
+    pub fn line_of(&self, index: usize) -> usize {
+        let ordered = self.metrics.get_new_line_order();
+        let next_line = ordered.iter().position(|lb| self.to_logical(*lb) > index);
+        if let Some(next_line) = next_line {
+            next_line-1
+        } else {
+            ordered.len()-1
+        }
+    }
+    /// Logical index of first char in line
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.metrics.get_new_line_order().get(line).map(|lb| self.to_logical(*lb))
+    }
+    /// Logical index of the linebreak ending the line (or length if last line)
+    pub fn line_end(&self, line: usize) -> Option<usize> {
+        self.line_start(line)?;
+        if let Some(next) = self.line_start(line+1) {
+            Some(next-1)
+        } else {
+            Some(self.metrics.length)
+        }
+    }
+    /// Content of line, without its linebreak
+    pub fn line_string(&self, line: usize) -> Option<String> {
+        let start = self.line_start(line)?;
+        let end = self.line_end(line)?;
+        Some((start..end).filter_map(|i| self.char_at(i)).collect())
+    }
+    /// Column of the cursor inside its line (in chars)
+    pub fn cursor_col(&self) -> usize {
+        self.cursor - self.line_start(self.cursor_line()).unwrap_or(0)
+    }
 
 
     pub fn string_raw(&self) -> String {
@@ -190,7 +319,7 @@ impl TextBuffer {
 
 #[cfg(test)]
 mod test {
-    use crate::textedit::operation::{CursorLeft, CursorRight, DeleteBack, InsertChar, InsertLinebreak, InsertString};
+    use crate::textedit::operation::{CursorLeft, CursorRight, DeleteBack, InsertChar, InsertLinebreak, InsertString, OperationGroup};
     // use crate::textedit::traverse_ops::current_line;
     use super::*;
 
@@ -394,6 +523,89 @@ mod test {
         assert_eq!(buf.metrics.new_lines_raw[&buf.metrics.new_lines_order[0]], 0);
     }
 
+    #[test]
+    fn operation_group_undo() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new("ab".to_string())));
+
+        buf.apply(Box::new(OperationGroup(vec![
+            Box::new(InsertLinebreak),
+            Box::new(InsertString::new("    ".to_string())),
+            Box::new(CursorLeft(2)),
+        ])));
+        assert_eq!(buf.string(), "ab\n    ");
+        assert_eq!(buf.cursor, 5);
+        assert_eq!(buf.line_count(), 2);
+
+        buf.undo();
+        assert_eq!(buf.string(), "ab");
+        assert_eq!(buf.line_count(), 1);
+    }
+
+    #[test]
+    fn linebreak_order_survives_delete() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new("a\nb\nc\nd".to_string())));
+        buf.apply(Box::new(CursorLeft(3)));
+        buf.apply(Box::new(DeleteBack::new(2)));
+        assert_eq!(buf.string(), "a\nc\nd");
+
+        // remaining linebreaks can still be removed by position
+        buf.apply(Box::new(CursorRight(2)));
+        buf.apply(Box::new(DeleteBack::new(1)));
+        assert_eq!(buf.string(), "a\ncd");
+        assert_eq!(buf.line_count(), 2);
+    }
+
+    #[test]
+    fn changes_track_lines() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new("a\nb\nc\nd".to_string())));
+        buf.take_changes();
+
+        // join b and c
+        buf.apply(Box::new(CursorLeft(3)));
+        buf.apply(Box::new(DeleteBack::new(1)));
+        let changes = buf.take_changes();
+        assert_eq!(changes.first_line, Some(1));
+        assert_eq!(changes.last_line, 1);
+        assert_eq!(changes.map_line(0), 0);
+        assert_eq!(changes.map_line(2), 1);
+        assert_eq!(changes.map_line(3), 2);
+
+        // moving doesn't count as an edit
+        buf.apply(Box::new(CursorLeft(2)));
+        assert!(buf.take_changes().is_empty());
+
+        buf.apply(Box::new(InsertString::new("x\n\n".to_string())));
+        let changes = buf.take_changes();
+        assert_eq!(changes.first_line, Some(0));
+        assert_eq!(changes.last_line, 2);
+        assert_eq!(changes.map_line(0), 0);
+        assert_eq!(changes.map_line(1), 3);
+
+        buf.undo();
+        assert_eq!(buf.take_changes().map_line(3), 1);
+    }
+
+    #[test]
+    fn line_access_across_gap() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new("foo\n\tbar\nbaz".to_string())));
+        buf.apply(Box::new(CursorLeft(6)));
+
+        assert_eq!(buf.line_count(), 3);
+        assert_eq!(buf.cursor_line(), 1);
+        assert_eq!(buf.cursor_col(), 2);
+        assert_eq!(buf.line_start(1), Some(4));
+        assert_eq!(buf.line_end(1), Some(8));
+        assert_eq!(buf.line_string(0), Some("foo".to_string()));
+        assert_eq!(buf.line_string(1), Some("\tbar".to_string()));
+        assert_eq!(buf.line_string(2), Some("baz".to_string()));
+        assert_eq!(buf.char_at(6), Some('a'));
+        assert_eq!(buf.char_at(12), None);
+    }
+
     #[test]
     fn get_line() {
         let mut buf = TextBuffer::new();
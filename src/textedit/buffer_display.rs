@@ -1,107 +1,273 @@
-use crate::plot::Plot;
-
-pub fn wrap_content(content: String, dim: Plot, mut cursor: usize) -> (Vec<Vec<String>>, Plot) {
-    let mut out = Vec::new();
-
-    let mut n = 0;
-    let mut cursor_plot = None;
-    for line in content.split("\n") {
-        let mut subout = Vec::new();
-
-        let mut line = line.to_string();
-        loop {
-            // take dim.col width of chars into vec
-            let partial: String = line
-                .drain(..std::cmp::min(line.len(), dim.col))
-                .collect();
-
-            // set cursor if on right line
-            if cursor_plot.is_none() {
-                if cursor <= partial.len() {
-                    cursor_plot = Some(Plot::new(n, cursor));
-                } else if partial.len() <= cursor {
-                    cursor -= partial.len();
-                }
-            }
+/// Options affecting how buffer content is laid out on screen
+#[derive(Clone)]
+pub struct DisplayOptions {
+    pub tabstop: usize,
+    /// Soft wrap long lines, otherwise each line is one segment
+    pub wrap: bool,
+    /// Shown at the edge when a line continues to the right
+    pub extends: char,
+    /// Shown at the edge when a line continues to the left
+    pub precedes: char,
+    /// Wrap at the last blank that fits instead of the last column
+    pub linebreak: bool,
+    /// Continuation rows start at the line's indent
+    pub breakindent: bool,
+    /// Shown at the start of continuation rows
+    pub showbreak: String,
+}
 
-            subout.push(partial);
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            tabstop: 4,
+            wrap: true,
+            extends: '>',
+            precedes: '<',
+            linebreak: false,
+            breakindent: false,
+            showbreak: String::new(),
+        }
+    }
+}
 
-            n += 1;
-            // break if hiting end
-            if line.len() == 0 || n >= dim.row {
-                break;
-            }
+/// Width of a tab starting at display column col
+fn tab_width(col: usize, tabstop: usize) -> usize {
+    if tabstop == 0 { return 0; }
+    tabstop - (col % tabstop)
+}
+
+/// Display width of text, with tabs expanded to the next tabstop
+pub fn display_width(text: &str, tabstop: usize) -> usize {
+    text.chars().fold(0, |col, ch| {
+        if ch == '\t' { col + tab_width(col, tabstop) } else { col + 1 }
+    })
+}
+
+/// Display column of each char of text, followed by the column after the last one
+pub fn char_columns(text: &str, tabstop: usize) -> Vec<usize> {
+    let mut cols = vec![0];
+    for ch in text.chars() {
+        let col = *cols.last().unwrap_or(&0);
+        cols.push(if ch == '\t' { col + tab_width(col, tabstop) } else { col + 1 });
+    }
+    cols
+}
+
+/// Replace tabs with spaces up to the next tabstop
+pub fn expand_tabs(text: &str, tabstop: usize) -> String {
+    let mut out = String::new();
+    for ch in text.chars() {
+        if ch == '\t' {
+            out += &" ".repeat(tab_width(out.chars().count(), tabstop));
+        } else {
+            out.push(ch);
         }
+    }
+    out
+}
+
+/// Part of a line shown on one screen row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// Display column in the line where the segment starts
+    pub col: usize,
+    /// Text shown, with tabs expanded
+    pub text: String,
+    /// Indent and marker shown before the text on continuation rows
+    pub prefix: String,
+}
+
+/// Split a line into the segments shown on each screen row
+pub fn layout_line(line: &str, width: usize, options: &DisplayOptions) -> Vec<Segment> {
+    let expanded: Vec<char> = expand_tabs(line, options.tabstop).chars().collect();
+    if !options.wrap {
+        return vec![Segment { col: 0, text: expanded.into_iter().collect(), prefix: String::new() }];
+    }
+    if width == 0 || expanded.is_empty() {
+        return vec![Segment { col: 0, text: String::new(), prefix: String::new() }];
+    }
 
-        if cursor > 0 {
-            cursor -= 1;
+    let continuation = continuation_prefix(&expanded, width, options);
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut start = 0;
+    while start < expanded.len() {
+        let prefix = if segments.is_empty() { String::new() } else { continuation.clone() };
+        let room = width - prefix.chars().count();
+        let mut end = std::cmp::min(start + room, expanded.len());
+
+        // break after the last blank, unless the row ends right before one
+        if options.linebreak && end < expanded.len() && !expanded[end].is_whitespace() {
+            if let Some(blank) = expanded[start..end].iter().rposition(|c| c.is_whitespace()) {
+                end = start + blank + 1;
+            }
         }
 
-        out.push(subout);
+        segments.push(Segment { col: start, text: expanded[start..end].iter().collect(), prefix });
+        start = end;
     }
+    segments
+}
 
-    if cursor_plot.is_none() {
-        cursor_plot = Some(Plot::new(0,0));
+/// Prefix of continuation rows, leaving at least half the width for text
+fn continuation_prefix(expanded: &[char], width: usize, options: &DisplayOptions) -> String {
+    let mut prefix = String::new();
+    if options.breakindent {
+        prefix.extend(expanded.iter().take_while(|c| c.is_whitespace()));
     }
+    prefix += &options.showbreak;
+    prefix.chars().take(width / 2).collect()
+}
 
-    (out, cursor_plot.unwrap())
+/// Segment index and screen column within its row of a display column.
+/// The end of a line filling the full width stays on its last segment
+pub fn locate_in_line(segments: &[Segment], col: usize) -> (usize, usize) {
+    let i = segments.iter().rposition(|s| s.col <= col).unwrap_or(0);
+    match segments.get(i) {
+        Some(segment) => (i, segment.prefix.chars().count() + col - segment.col),
+        None => (0, col),
+    }
 }
 
+/// Columns left..left+width of a segment's text. The edge columns are
+/// replaced with markers when text continues past them
+pub fn clip(text: &str, left: usize, width: usize, options: &DisplayOptions) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut shown: Vec<char> = chars.iter().skip(left).take(width).copied().collect();
 
+    if width > 0 && chars.len() > left + width {
+        shown[width-1] = options.extends;
+    }
+    if left > 0 && !shown.is_empty() {
+        shown[0] = options.precedes;
+    }
+    shown.into_iter().collect()
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::plot::Plot;
+
+    /// Screen row and column of col in line, lines laid out one after another
+    fn position(lines: &[&str], line: usize, col: usize, width: usize, options: &DisplayOptions) -> Plot {
+        let above: usize = lines[..line].iter().map(|l| layout_line(l, width, options).len()).sum();
+        let display_col = char_columns(lines[line], options.tabstop)[col];
+        let (segment, col) = locate_in_line(&layout_line(lines[line], width, options), display_col);
+        Plot::new(above + segment, col)
+    }
 
     #[test]
     fn test_wraps_content() {
-        let content = "012345\n678910X\nthis line is extraaa long".to_string();
+        let options = DisplayOptions::default();
+        let texts = |line: &str| -> Vec<String> {
+            layout_line(line, 6, &options).into_iter().map(|s| s.text).collect()
+        };
 
-        let dim = Plot::new(10,6);
-        let (wrapped, _) = wrap_content(content.clone(), dim, 0);
-
-        assert_eq!(wrapped[0], vec!["012345".to_string()]);
-        assert_eq!(wrapped[1], vec!["678910".to_string(), "X".to_string()]);
-        assert_eq!(wrapped[2].len(), 5);
+        assert_eq!(texts("012345"), vec!["012345".to_string()]);
+        assert_eq!(texts("678910X"), vec!["678910".to_string(), "X".to_string()]);
+        assert_eq!(texts("this line is extraaa long").len(), 5);
     }
 
     #[test]
     fn test_cursor() {
-        let content = "012345\n678910X\nthis line is extraaa long".to_string();
+        let lines = ["012345", "678910X", "this line is extraaa long"];
+        let options = DisplayOptions::default();
 
-        let dim = Plot::new(10,6);
+        assert_eq!(position(&lines, 0, 0, 6, &options), Plot::new(0,0));
+        assert_eq!(position(&lines, 0, 5, 6, &options), Plot::new(0,5));
+        // end of a line filling the full width stays on its row
+        assert_eq!(position(&lines, 0, 6, 6, &options), Plot::new(0,6));
+        assert_eq!(position(&lines, 1, 6, 6, &options), Plot::new(2,0));
+        assert_eq!(position(&lines, 1, 7, 6, &options), Plot::new(2,1));
+    }
 
-        let (_, cursor) = wrap_content(content.clone(), dim, 0);
-        assert_eq!(cursor, Plot::new(0,0));
+    #[test]
+    fn test_expands_tabs() {
+        assert_eq!(expand_tabs("\tx", 4), "    x");
+        assert_eq!(expand_tabs("ab\tx", 4), "ab  x");
+        assert_eq!(expand_tabs("abcd\tx", 4), "abcd    x");
+        assert_eq!(display_width("a\t\t", 8), 16);
+    }
+
+    #[test]
+    fn test_cursor_with_tabs() {
+        let lines = ["a", "\tbc", ""];
+        let options = DisplayOptions { tabstop: 8, ..Default::default() };
 
-        let (_, cursor) = wrap_content(content.clone(), dim, 5);
-        assert_eq!(cursor, Plot::new(0,5));
+        assert_eq!(layout_line(lines[1], 20, &options)[0].text, "        bc");
+        assert_eq!(position(&lines, 1, 1, 20, &options), Plot::new(1,8));
+        assert_eq!(position(&lines, 1, 2, 20, &options), Plot::new(1,9));
+    }
+
+    #[test]
+    fn test_layout_line() {
+        let options = DisplayOptions::default();
+        let segments = layout_line("abcdefgh", 3, &options);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[2], Segment { col: 6, text: "gh".to_string(), prefix: String::new() });
 
-        let (_, cursor) = wrap_content(content.clone(), dim, 6);
-        assert_eq!(cursor, Plot::new(0,6));
+        assert_eq!(locate_in_line(&segments, 4), (1, 1));
+        assert_eq!(locate_in_line(&segments, 8), (2, 2));
 
-        let (_, cursor) = wrap_content(content.clone(), dim, 13);
-        assert_eq!(cursor, Plot::new(2,0));
+        let segments = layout_line("abcdef", 3, &options);
+        assert_eq!(locate_in_line(&segments, 6), (1, 3));
 
-        let (_, cursor) = wrap_content(content.clone(), dim, 14);
-        assert_eq!(cursor, Plot::new(3,0));
+        assert_eq!(layout_line("", 3, &options).len(), 1);
     }
 
     #[test]
-    fn test_cursor_empty_lines() {
-        let content = "012345\n\n\nthis line is extraa long".to_string();
-        let dim = Plot::new(10,6);
+    fn test_linebreak() {
+        let options = DisplayOptions { linebreak: true, ..Default::default() };
+        let texts = |line: &str, width| -> Vec<String> {
+            layout_line(line, width, &options).into_iter().map(|s| s.text).collect()
+        };
+
+        assert_eq!(texts("one two three", 9), vec!["one two ", "three"]);
+        assert_eq!(texts("one two three", 7), vec!["one two", " three"]);
+        // no blank to break at
+        assert_eq!(texts("abcdefgh ij", 4), vec!["abcd", "efgh", " ij"]);
+        // three or more rows
+        assert_eq!(texts("aa bb cc dd", 3), vec!["aa ", "bb ", "cc ", "dd"]);
+    }
+
+    #[test]
+    fn test_breakindent_showbreak() {
+        let options = DisplayOptions { breakindent: true, showbreak: "+".to_string(), ..Default::default() };
+        let segments = layout_line("  abcdefgh", 6, &options);
+
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0].prefix, "");
+        assert_eq!(segments[1], Segment { col: 6, text: "efg".to_string(), prefix: "  +".to_string() });
+        assert_eq!(segments[2].text, "h");
 
-        let (_, cursor) = wrap_content(content.clone(), dim, 0);
-        assert_eq!(cursor, Plot::new(0,0));
+        assert_eq!(locate_in_line(&segments, 7), (1, 4));
+        assert_eq!(locate_in_line(&segments, 10), (2, 4));
+    }
+
+    #[test]
+    fn test_nowrap_clip() {
+        let options = DisplayOptions { wrap: false, ..Default::default() };
+        let segments = layout_line("abcdefgh", 3, &options);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(locate_in_line(&segments, 7), (0, 7));
 
-        let (_, cursor) = wrap_content(content.clone(), dim, 7);
-        assert_eq!(cursor, Plot::new(0,6));
+        assert_eq!(clip("abcdefgh", 0, 3, &options), "ab>");
+        assert_eq!(clip("abcdefgh", 2, 3, &options), "<d>");
+        assert_eq!(clip("abcdefgh", 5, 3, &options), "<gh");
+        assert_eq!(clip("abcdefgh", 0, 8, &options), "abcdefgh");
+        assert_eq!(clip("ab", 4, 3, &options), "");
+    }
 
-        let (_, cursor) = wrap_content(content.clone(), dim, 7);
-        assert_eq!(cursor, Plot::new(1,0));
+    #[test]
+    fn test_cursor_empty_lines() {
+        let lines = ["012345", "", "", "this line is extraa long"];
+        let options = DisplayOptions::default();
 
-        let (_, cursor) = wrap_content(content.clone(), dim, 8);
-        assert_eq!(cursor, Plot::new(2,0));
+        assert_eq!(position(&lines, 0, 0, 6, &options), Plot::new(0,0));
+        assert_eq!(position(&lines, 0, 6, 6, &options), Plot::new(0,6));
+        // empty lines still take a row
+        assert_eq!(position(&lines, 1, 0, 6, &options), Plot::new(1,0));
+        assert_eq!(position(&lines, 2, 0, 6, &options), Plot::new(2,0));
+        assert_eq!(position(&lines, 3, 0, 6, &options), Plot::new(3,0));
     }
 }
\ No newline at end of file
@@ -0,0 +1,127 @@
+//! Commenting and uncommenting blocks of lines
+use crate::textedit::buffer_display::{char_columns, display_width};
+use crate::textedit::filetype::FileType;
+use crate::textedit::indent::{is_blank, leading_whitespace};
+
+/// New content for lines after toggling comments.
+/// Lines are uncommented if every non-blank line is already commented, otherwise all are commented,
+/// with the comment token at the column of the smallest indent, tabs taking tabstop columns.
+/// Returns None if the filetype has no comments
+pub fn toggle_comments(lines: &[String], filetype: &FileType, tabstop: usize) -> Option<Vec<String>> {
+    let content: Vec<&String> = lines.iter().filter(|l| !is_blank(l)).collect();
+    if content.is_empty() { return Some(lines.to_vec()); }
+
+    if let Some(token) = filetype.line_comment {
+        let commented = content.iter().all(|l| l.trim_start().starts_with(token));
+        let indent = content.iter().map(|l| display_width(&leading_whitespace(l), tabstop)).min().unwrap_or(0);
+
+        return Some(lines.iter().map(|line| {
+            if is_blank(line) {
+                line.clone()
+            } else if commented {
+                uncomment_line(line, token)
+            } else {
+                let (at, pad) = split_at_column(line, indent, tabstop);
+                format!("{}{}{} {}", &line[..at], " ".repeat(pad), token, &line[at..])
+            }
+        }).collect());
+    }
+
+    if let Some((open, close)) = filetype.block_comment {
+        let commented = content.iter().all(|l| {
+            let trimmed = l.trim();
+            trimmed.starts_with(open) && trimmed.ends_with(close)
+        });
+
+        return Some(lines.iter().map(|line| {
+            if is_blank(line) {
+                line.clone()
+            } else if commented {
+                unwrap_block(line, open, close)
+            } else {
+                let indent = leading_whitespace(line);
+                format!("{}{} {} {}", indent, open, line[indent.len()..].trim_end(), close)
+            }
+        }).collect());
+    }
+
+    None
+}
+
+/// Index in the indent of line where display column col starts. When a tab spans col,
+/// the index of the tab and the spaces that reach col before it
+fn split_at_column(line: &str, col: usize, tabstop: usize) -> (usize, usize) {
+    let cols = char_columns(line, tabstop);
+    match cols.iter().position(|c| *c >= col) {
+        Some(i) if cols[i] > col && i > 0 => (i - 1, col - cols[i - 1]),
+        Some(i) => (i, 0),
+        None => (line.len(), 0),
+    }
+}
+
+/// Remove the token and a single space after it
+fn uncomment_line(line: &str, token: &str) -> String {
+    let indent = leading_whitespace(line);
+    let rest = &line[indent.len() + token.len()..];
+    format!("{}{}", indent, rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+fn unwrap_block(line: &str, open: &str, close: &str) -> String {
+    let indent = leading_whitespace(line);
+    let inner = line.trim()
+        .strip_prefix(open).unwrap_or("")
+        .strip_suffix(close).unwrap_or("");
+    let inner = inner.strip_prefix(' ').unwrap_or(inner);
+    let inner = inner.strip_suffix(' ').unwrap_or(inner);
+    format!("{}{}", indent, inner)
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn comments_aligned_to_smallest_indent() {
+        let rust = FileType::by_name("rust").unwrap();
+        let out = toggle_comments(&lines(&["    if x {", "        y();", "", "    }"]), rust, 4).unwrap();
+        assert_eq!(out, lines(&["    // if x {", "    //     y();", "", "    // }"]));
+
+        // toggling again restores
+        assert_eq!(toggle_comments(&out, rust, 4).unwrap(), lines(&["    if x {", "        y();", "", "    }"]));
+    }
+
+    #[test]
+    fn comments_aligned_across_tabs() {
+        let rust = FileType::by_name("rust").unwrap();
+        let out = toggle_comments(&lines(&["\tif x {", "        y();"]), rust, 4).unwrap();
+        assert_eq!(out, lines(&["\t// if x {", "    //     y();"]));
+
+        // a tab spanning the column is moved after the token
+        let out = toggle_comments(&lines(&["  a", "\tb"]), rust, 4).unwrap();
+        assert_eq!(out, lines(&["  // a", "  // \tb"]));
+    }
+
+    #[test]
+    fn mixed_block_gets_commented() {
+        let python = FileType::by_name("python").unwrap();
+        let out = toggle_comments(&lines(&["# a", "b"]), python, 4).unwrap();
+        assert_eq!(out, lines(&["# # a", "# b"]));
+    }
+
+    #[test]
+    fn block_comment_fallback() {
+        let html = FileType::by_name("html").unwrap();
+        let out = toggle_comments(&lines(&["  <p>hi</p>"]), html, 4).unwrap();
+        assert_eq!(out, lines(&["  <!-- <p>hi</p> -->"]));
+        assert_eq!(toggle_comments(&out, html, 4).unwrap(), lines(&["  <p>hi</p>"]));
+
+        let json = FileType::by_name("json").unwrap();
+        assert!(toggle_comments(&lines(&["{}"]), json, 4).is_none());
+    }
+}
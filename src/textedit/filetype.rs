@@ -0,0 +1,282 @@
+//! Per-language editing rules, picked from a file's name
+use std::path::Path;
+
+pub struct FileType {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub file_names: &'static [&'static str],
+    /// Chars that open a new indent level when ending a line
+    pub indent_open: &'static [char],
+    /// Chars that close an indent level when starting a line
+    pub indent_close: &'static [char],
+    /// Lines ending in a tag that opens an element, not closed on the line, open an indent level
+    pub indent_tags: bool,
+    /// Opening and closing chars inserted together
+    pub pairs: &'static [(char, char)],
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// Options set for files of this type, over the current settings, as arguments of `:set`
+    pub options: &'static [&'static str],
+}
+
+const BRACES_OPEN: &[char] = &['{', '(', '['];
+const BRACES_CLOSE: &[char] = &['}', ')', ']'];
+
+/// Html elements that never have content or a closing tag
+const VOID_ELEMENTS: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"];
+
+const BRACE_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+const ALL_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+const DOUBLE_QUOTE_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+
+pub const PLAIN: FileType = FileType {
+    name: "text",
+    extensions: &["txt"],
+    file_names: &[],
+    indent_open: BRACES_OPEN,
+    indent_close: BRACES_CLOSE,
+    indent_tags: false,
+    pairs: BRACE_PAIRS,
+    line_comment: None,
+    block_comment: None,
+    options: &[],
+};
+
+const FILETYPES: &[FileType] = &[
+    FileType {
+        name: "rust",
+        extensions: &["rs"],
+        file_names: &[],
+        indent_open: BRACES_OPEN,
+        indent_close: BRACES_CLOSE,
+        indent_tags: false,
+        pairs: DOUBLE_QUOTE_PAIRS,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        options: &[],
+    },
+    FileType {
+        name: "c",
+        extensions: &["c", "h", "cpp", "hpp", "cc"],
+        file_names: &[],
+        indent_open: BRACES_OPEN,
+        indent_close: BRACES_CLOSE,
+        indent_tags: false,
+        pairs: DOUBLE_QUOTE_PAIRS,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        options: &[],
+    },
+    FileType {
+        name: "go",
+        extensions: &["go"],
+        file_names: &[],
+        indent_open: BRACES_OPEN,
+        indent_close: BRACES_CLOSE,
+        indent_tags: false,
+        pairs: DOUBLE_QUOTE_PAIRS,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        options: &[],
+    },
+    FileType {
+        name: "javascript",
+        extensions: &["js", "ts", "jsx", "tsx"],
+        file_names: &[],
+        indent_open: BRACES_OPEN,
+        indent_close: BRACES_CLOSE,
+        indent_tags: false,
+        pairs: ALL_PAIRS,
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        options: &[],
+    },
+    FileType {
+        name: "json",
+        extensions: &["json"],
+        file_names: &[],
+        indent_open: BRACES_OPEN,
+        indent_close: BRACES_CLOSE,
+        indent_tags: false,
+        pairs: DOUBLE_QUOTE_PAIRS,
+        line_comment: None,
+        block_comment: None,
+        options: &[],
+    },
+    FileType {
+        name: "python",
+        extensions: &["py"],
+        file_names: &[],
+        indent_open: &[':', '{', '(', '['],
+        indent_close: BRACES_CLOSE,
+        indent_tags: false,
+        pairs: ALL_PAIRS,
+        line_comment: Some("#"),
+        block_comment: None,
+        options: &[],
+    },
+    FileType {
+        name: "toml",
+        extensions: &["toml"],
+        file_names: &["Cargo.lock"],
+        indent_open: &['[', '{'],
+        indent_close: &[']', '}'],
+        indent_tags: false,
+        pairs: ALL_PAIRS,
+        line_comment: Some("#"),
+        block_comment: None,
+        options: &[],
+    },
+    FileType {
+        name: "markdown",
+        extensions: &["md", "markdown"],
+        file_names: &[],
+        indent_open: &[],
+        indent_close: &[],
+        indent_tags: false,
+        pairs: &[('(', ')'), ('[', ']'), ('`', '`')],
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+        options: &[],
+    },
+    FileType {
+        name: "html",
+        extensions: &["html", "htm", "xml", "svg"],
+        file_names: &[],
+        indent_open: &[],
+        indent_close: &[],
+        indent_tags: true,
+        pairs: &[('<', '>'), ('"', '"')],
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+        options: &[],
+    },
+    FileType {
+        name: "css",
+        extensions: &["css", "scss"],
+        file_names: &[],
+        indent_open: BRACES_OPEN,
+        indent_close: BRACES_CLOSE,
+        indent_tags: false,
+        pairs: DOUBLE_QUOTE_PAIRS,
+        line_comment: None,
+        block_comment: Some(("/*", "*/")),
+        options: &[],
+    },
+    FileType {
+        name: "shell",
+        extensions: &["sh", "bash", "zsh"],
+        file_names: &[".bashrc", ".zshrc", ".profile"],
+        indent_open: BRACES_OPEN,
+        indent_close: BRACES_CLOSE,
+        indent_tags: false,
+        pairs: ALL_PAIRS,
+        line_comment: Some("#"),
+        block_comment: None,
+        options: &[],
+    },
+    FileType {
+        name: "make",
+        extensions: &["mk"],
+        file_names: &["Makefile", "makefile", "GNUmakefile"],
+        indent_open: &[':'],
+        indent_close: &[],
+        indent_tags: false,
+        pairs: &[('(', ')'), ('{', '}')],
+        line_comment: Some("#"),
+        block_comment: None,
+        // recipes must start with a tab
+        options: &["noexpandtab"],
+    },
+];
+
+impl FileType {
+    /// Find filetype by file name, then extension. Falls back to plain text
+    pub fn from_path(path: &Path) -> &'static FileType {
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let extension = path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+
+        FILETYPES.iter()
+            .find(|ft| ft.file_names.contains(&file_name.as_str()))
+            .or_else(|| FILETYPES.iter().find(|ft| ft.extensions.contains(&extension.as_str())))
+            .unwrap_or(&PLAIN)
+    }
+
+    /// Find filetype by name
+    pub fn by_name(name: &str) -> Option<&'static FileType> {
+        if name == PLAIN.name { return Some(&PLAIN); }
+        FILETYPES.iter().find(|ft| ft.name == name)
+    }
+
+    /// Whether line (up to the cursor) should open a new indent level
+    pub fn opens_indent(&self, before: &str) -> bool {
+        let before = before.trim_end();
+        before.chars().last().is_some_and(|ch| self.indent_open.contains(&ch))
+            || (self.indent_tags && ends_in_open_tag(before))
+    }
+
+    /// Closing char paired with open
+    pub fn pair_close(&self, open: char) -> Option<char> {
+        self.pairs.iter().find(|(o, _)| *o == open).map(|(_, c)| *c)
+    }
+
+    /// Whether ch is the closing half of a pair
+    pub fn is_pair_close(&self, ch: char) -> bool {
+        self.pairs.iter().any(|(_, c)| *c == ch)
+    }
+
+    /// Whether ch closes an indent level
+    pub fn closes_indent(&self, ch: char) -> bool {
+        self.indent_close.contains(&ch)
+    }
+}
+
+
+/// Whether the last tag of line ends it and opens an element, skipping closing, self-closing and void tags
+fn ends_in_open_tag(line: &str) -> bool {
+    let Some(tag) = line.strip_suffix('>').and_then(|l| l.rfind('<').map(|lt| &l[lt + 1..])) else { return false };
+    if tag.starts_with(['/', '!', '?']) || tag.ends_with('/') { return false; }
+    let name: String = tag.chars().take_while(|c| c.is_alphanumeric() || *c == '-').collect();
+    !name.is_empty() && !VOID_ELEMENTS.contains(&name.to_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use super::*;
+
+    #[test]
+    fn detects_filetype() {
+        assert_eq!(FileType::from_path(&PathBuf::from("src/main.rs")).name, "rust");
+        assert_eq!(FileType::from_path(&PathBuf::from("Makefile")).name, "make");
+        assert_eq!(FileType::from_path(&PathBuf::from("notes")).name, "text");
+        assert_eq!(FileType::by_name("python").unwrap().name, "python");
+        assert_eq!(FileType::by_name("make").unwrap().options, ["noexpandtab"]);
+    }
+
+    #[test]
+    fn openers() {
+        let python = FileType::by_name("python").unwrap();
+        assert!(python.opens_indent("def foo():  "));
+        assert!(!python.opens_indent("x = 1"));
+        assert!(PLAIN.closes_indent('}'));
+
+        let html = FileType::by_name("html").unwrap();
+        assert!(html.opens_indent("<div class=\"a\">"));
+        assert!(html.opens_indent("<ul><li>"));
+        assert!(!html.opens_indent("</div>"));
+        assert!(!html.opens_indent("<p>text</p>"));
+        assert!(!html.opens_indent("<br>"));
+        assert!(!html.opens_indent("<img src=\"a.png\"/>"));
+        assert!(!html.opens_indent("<!-- note -->"));
+    }
+
+    #[test]
+    fn pairs() {
+        let rust = FileType::by_name("rust").unwrap();
+        assert_eq!(rust.pair_close('('), Some(')'));
+        assert_eq!(rust.pair_close('"'), Some('"'));
+        assert_eq!(rust.pair_close('\''), None);
+        assert!(rust.is_pair_close(']'));
+    }
+}
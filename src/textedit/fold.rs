@@ -0,0 +1,321 @@
+//! Folded regions of lines
+use crate::textedit::buffer::Changes;
+use crate::textedit::buffer_display::display_width;
+use crate::textedit::indent::{is_blank, leading_whitespace};
+
+/// How folds are created
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FoldMethod {
+    /// Only with `zf`
+    Manual,
+    /// From lines followed by more indented ones
+    Indent,
+    /// Between `{{{` and `}}}`
+    Marker,
+}
+
+impl FoldMethod {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "manual" => Some(FoldMethod::Manual),
+            "indent" => Some(FoldMethod::Indent),
+            "marker" => Some(FoldMethod::Marker),
+            _ => None
+        }
+    }
+}
+
+/// Lines start..=end, shown as one line when closed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+    pub closed: bool,
+}
+
+/// What fold methods read of a line, its indent width (None if blank) and the markers it holds
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct LineKey {
+    pub indent: Option<usize>,
+    pub opens: bool,
+    pub closes: bool,
+}
+
+impl LineKey {
+    pub fn new(line: &str, tabstop: usize) -> Self {
+        LineKey {
+            indent: if is_blank(line) { None } else { Some(display_width(&leading_whitespace(line), tabstop)) },
+            opens: line.contains("{{{"),
+            closes: line.contains("}}}"),
+        }
+    }
+}
+
+/// Folds of a buffer, possibly nested
+#[derive(Default)]
+pub struct Folds {
+    /// Sorted by start, outer folds first
+    folds: Vec<Fold>,
+    /// Key of each line, folds are recomputed from only when one changes
+    keys: Vec<LineKey>,
+}
+
+impl Folds {
+    /// Add a closed fold
+    pub fn add(&mut self, start: usize, end: usize) {
+        if start >= end { return; }
+        self.folds.retain(|f| !(f.start == start && f.end == end));
+        self.folds.push(Fold { start, end, closed: true });
+        self.sort();
+    }
+
+    /// Remove the innermost fold containing line
+    pub fn remove(&mut self, line: usize) {
+        if let Some(i) = self.innermost(line, |_| true) {
+            self.folds.remove(i);
+        }
+    }
+
+    /// Replace all folds with ranges, keeping closed folds that start on the same line closed
+    pub fn replace(&mut self, ranges: Vec<(usize, usize)>) {
+        let closed: Vec<usize> = self.folds.iter().filter(|f| f.closed).map(|f| f.start).collect();
+        self.folds = ranges.into_iter()
+            .map(|(start, end)| Fold { start, end, closed: closed.contains(&start) })
+            .collect();
+        self.sort();
+    }
+
+    /// Recreate folds of method from the key of every line. Manual folds are kept as is
+    pub fn recompute(&mut self, method: FoldMethod, line_count: usize, key: &dyn Fn(usize) -> LineKey) {
+        self.keys = (0..line_count).map(key).collect();
+        self.refold(method);
+    }
+
+    /// Move folds along with changes, recreating them only if an edited line's key changed
+    pub fn update(&mut self, method: FoldMethod, changes: &Changes, line_count: usize, key: &dyn Fn(usize) -> LineKey) {
+        self.apply_changes(changes);
+        if method == FoldMethod::Manual { return; }
+
+        for (at, delta) in changes.shifts.iter() {
+            let at = std::cmp::min(*at, self.keys.len());
+            if *delta >= 0 {
+                self.keys.splice(at..at, std::iter::repeat_n(LineKey::default(), delta.unsigned_abs()));
+            } else {
+                let end = std::cmp::min(at + delta.unsigned_abs(), self.keys.len());
+                self.keys.drain(at..end);
+            }
+        }
+        self.keys.resize(line_count, LineKey::default());
+
+        let Some(first) = changes.first_line else { return };
+        let mut changed = false;
+        for line in first..=std::cmp::min(changes.last_line, line_count.saturating_sub(1)) {
+            let new = key(line);
+            if self.keys[line] != new {
+                self.keys[line] = new;
+                changed = true;
+            }
+        }
+        if changed {
+            self.refold(method);
+        }
+    }
+
+    fn refold(&mut self, method: FoldMethod) {
+        match method {
+            FoldMethod::Manual => (),
+            FoldMethod::Indent => self.replace(indent_folds(&self.keys)),
+            FoldMethod::Marker => self.replace(marker_folds(&self.keys)),
+        }
+    }
+
+    /// Move folds along with lines added or removed above or inside them
+    pub fn apply_changes(&mut self, changes: &Changes) {
+        for fold in self.folds.iter_mut() {
+            fold.start = changes.map_line(fold.start);
+            fold.end = changes.map_line(fold.end);
+        }
+        self.folds.retain(|f| f.start < f.end);
+        self.sort();
+    }
+
+    /// Open the outermost closed fold containing line, or close the innermost open one
+    pub fn toggle(&mut self, line: usize) {
+        if self.closed_at(line).is_some() {
+            self.open(line);
+        } else {
+            self.close(line);
+        }
+    }
+    /// Open the outermost closed fold containing line
+    pub fn open(&mut self, line: usize) {
+        if let Some(fold) = self.folds.iter_mut().find(|f| f.closed && f.start <= line && line <= f.end) {
+            fold.closed = false;
+        }
+    }
+    /// Close the innermost open fold containing line
+    pub fn close(&mut self, line: usize) {
+        if let Some(i) = self.innermost(line, |f| !f.closed) {
+            self.folds[i].closed = true;
+        }
+    }
+    pub fn open_all(&mut self) {
+        self.folds.iter_mut().for_each(|f| f.closed = false);
+    }
+    pub fn close_all(&mut self) {
+        self.folds.iter_mut().for_each(|f| f.closed = true);
+    }
+
+    /// Outermost closed fold containing line, which is what's shown in its place
+    pub fn closed_at(&self, line: usize) -> Option<Fold> {
+        self.folds.iter().find(|f| f.closed && f.start <= line && line <= f.end).copied()
+    }
+
+    /// Ranges of lines hidden behind closed folds, outermost only
+    pub fn closed_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for fold in self.folds.iter().filter(|f| f.closed) {
+            if ranges.last().is_some_and(|(_, end)| fold.start <= *end) { continue; }
+            ranges.push((fold.start, fold.end));
+        }
+        ranges
+    }
+
+    fn innermost(&self, line: usize, filter: impl Fn(&Fold) -> bool) -> Option<usize> {
+        self.folds.iter()
+            .enumerate()
+            .filter(|(_, f)| f.start <= line && line <= f.end && filter(f))
+            .min_by_key(|(_, f)| f.end - f.start)
+            .map(|(i, _)| i)
+    }
+
+    fn sort(&mut self) {
+        self.folds.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+    }
+}
+
+/// Folds starting at each line followed by more indented lines, up to the last of them.
+/// Blank lines don't end a fold but aren't included at its end
+pub fn indent_folds(keys: &[LineKey]) -> Vec<(usize, usize)> {
+    let indents: Vec<Option<usize>> = keys.iter().map(|k| k.indent).collect();
+
+    let mut folds = Vec::new();
+    for (start, indent) in indents.iter().enumerate() {
+        let Some(indent) = indent else { continue };
+
+        let mut end = start;
+        for (i, other) in indents.iter().enumerate().skip(start + 1) {
+            match other {
+                Some(other) if other > indent => end = i,
+                Some(_) => break,
+                None => ()
+            }
+        }
+        if end > start {
+            folds.push((start, end));
+        }
+    }
+    folds
+}
+
+/// Folds between lines containing `{{{` and `}}}`, which may nest. Unmatched markers are ignored
+pub fn marker_folds(keys: &[LineKey]) -> Vec<(usize, usize)> {
+    let mut open = Vec::new();
+    let mut folds = Vec::new();
+    for (i, key) in keys.iter().enumerate() {
+        if key.opens {
+            open.push(i);
+        }
+        if key.closes {
+            if let Some(start) = open.pop() {
+                if start < i { folds.push((start, i)); }
+            }
+        }
+    }
+    folds
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keys(text: &str) -> Vec<LineKey> {
+        text.split('\n').map(|l| LineKey::new(l, 4)).collect()
+    }
+
+    #[test]
+    fn indent_based() {
+        let text = keys("fn main() {\n    if x {\n        y();\n\n    }\n    z();\n}\nfoo");
+        assert_eq!(indent_folds(&text), vec![(0, 5), (1, 2)]);
+    }
+
+    #[test]
+    fn marker_based() {
+        let text = keys("a {{{\nb {{{\nc\n}}}\n}}}\n}}}\nd {{{");
+        assert_eq!(marker_folds(&text), vec![(1, 3), (0, 4)]);
+    }
+
+    #[test]
+    fn open_and_close_nested() {
+        let mut folds = Folds::default();
+        folds.add(0, 10);
+        folds.add(2, 4);
+        assert_eq!(folds.closed_ranges(), vec![(0, 10)]);
+
+        folds.open(3);
+        assert_eq!(folds.closed_at(3), Some(Fold { start: 2, end: 4, closed: true }));
+        folds.toggle(3);
+        assert_eq!(folds.closed_at(3), None);
+        folds.toggle(3);
+        assert_eq!(folds.closed_ranges(), vec![(2, 4)]);
+
+        folds.close_all();
+        assert_eq!(folds.closed_ranges(), vec![(0, 10)]);
+        folds.open_all();
+        assert!(folds.closed_ranges().is_empty());
+    }
+
+    #[test]
+    fn refolds_only_when_keys_change() {
+        let mut text: Vec<String> = "a\n    b\n    c\nd".split('\n').map(String::from).collect();
+        let mut folds = Folds::default();
+        folds.recompute(FoldMethod::Indent, text.len(), &|l| LineKey::new(&text[l], 4));
+        assert_eq!(folds.closed_ranges(), Vec::new());
+        // refolding would drop it
+        folds.add(2, 3);
+
+        // only the edited line is read, and its indent stays
+        text[2] = "    cc".into();
+        let read = std::cell::Cell::new(0);
+        let changes = Changes { first_line: Some(2), last_line: 2, shifts: Vec::new() };
+        folds.update(FoldMethod::Indent, &changes, text.len(), &|l| { read.set(read.get() + 1); LineKey::new(&text[l], 4) });
+        assert_eq!(read.get(), 1);
+        assert_eq!(folds.closed_ranges(), vec![(2, 3)]);
+
+        text[3] = "    d".into();
+        let changes = Changes { first_line: Some(3), last_line: 3, shifts: Vec::new() };
+        folds.update(FoldMethod::Indent, &changes, text.len(), &|l| LineKey::new(&text[l], 4));
+        folds.close_all();
+        assert_eq!(folds.closed_ranges(), vec![(0, 3)]);
+    }
+
+    #[test]
+    fn follow_edits_above() {
+        let mut folds = Folds::default();
+        folds.add(5, 8);
+
+        let changes = Changes { first_line: Some(1), last_line: 3, shifts: vec![(2, 2)] };
+        folds.apply_changes(&changes);
+        assert_eq!(folds.closed_ranges(), vec![(7, 10)]);
+
+        // lines 8 and 9 joined into 7
+        let changes = Changes { first_line: Some(7), last_line: 7, shifts: vec![(8, -2)] };
+        folds.apply_changes(&changes);
+        assert_eq!(folds.closed_ranges(), vec![(7, 8)]);
+
+        let changes = Changes { first_line: Some(7), last_line: 7, shifts: vec![(8, -1)] };
+        folds.apply_changes(&changes);
+        assert!(folds.closed_ranges().is_empty());
+    }
+}
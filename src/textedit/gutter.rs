@@ -0,0 +1,174 @@
+//! Column left of the text with signs and line numbers
+use std::collections::HashMap;
+use crate::style::{StyleAttribute, StyledText, ThemeColor};
+
+/// How line numbers are shown
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    /// Distance from the cursor's line
+    Relative,
+    /// Relative, with the absolute number on the cursor's line
+    Hybrid,
+}
+
+impl LineNumbers {
+    /// Mode from vim style `number` and `relativenumber` flags
+    pub fn from_flags(number: bool, relative: bool) -> Self {
+        match (number, relative) {
+            (false, false) => LineNumbers::Off,
+            (true, false) => LineNumbers::Absolute,
+            (false, true) => LineNumbers::Relative,
+            (true, true) => LineNumbers::Hybrid,
+        }
+    }
+}
+
+/// Options affecting how the gutter is drawn
+#[derive(Clone)]
+pub struct GutterOptions {
+    pub numbers: LineNumbers,
+    /// Show a column for signs
+    pub signcolumn: bool,
+    pub number_color: ThemeColor,
+}
+
+impl Default for GutterOptions {
+    fn default() -> Self {
+        Self {
+            numbers: LineNumbers::Absolute,
+            signcolumn: false,
+            number_color: ThemeColor::Green,
+        }
+    }
+}
+
+/// Mark placed next to a line, like a diagnostic, change or breakpoint
+#[derive(Clone)]
+pub struct Sign {
+    /// Up to SIGN_WIDTH chars
+    pub text: String,
+    pub color: ThemeColor,
+}
+
+pub const SIGN_WIDTH: usize = 2;
+
+#[derive(Default)]
+pub struct Gutter {
+    signs: HashMap<usize, Sign>,
+}
+
+impl Gutter {
+    pub fn set_sign(&mut self, line: usize, sign: Sign) {
+        self.signs.insert(line, sign);
+    }
+    pub fn remove_sign(&mut self, line: usize) -> Option<Sign> {
+        self.signs.remove(&line)
+    }
+    pub fn clear_signs(&mut self) {
+        self.signs.clear();
+    }
+
+    /// Columns taken up, numbers are wide enough for the last line plus a space
+    pub fn width(&self, line_count: usize, options: &GutterOptions) -> usize {
+        let signs = if options.signcolumn { SIGN_WIDTH } else { 0 };
+        let numbers = match options.numbers {
+            LineNumbers::Off => 0,
+            _ => digits(line_count) + 1,
+        };
+        signs + numbers
+    }
+
+    /// Gutter of one screen row. line is None on continuation rows of a wrapped line
+    pub fn render(&self, line: Option<usize>, cursor_line: usize, line_count: usize, options: &GutterOptions) -> Vec<StyledText> {
+        let mut out = Vec::new();
+
+        if options.signcolumn {
+            match line.and_then(|l| self.signs.get(&l)) {
+                Some(sign) => {
+                    let text: String = sign.text.chars().take(SIGN_WIDTH).collect();
+                    out.push(StyledText::new(format!("{:<w$}", text, w = SIGN_WIDTH))
+                        .with(StyleAttribute::Color(sign.color)));
+                }
+                None => out.push(StyledText::new(" ".repeat(SIGN_WIDTH))),
+            }
+        }
+
+        let width = digits(line_count);
+        let number = match (options.numbers, line) {
+            (LineNumbers::Off, _) => return out,
+            (_, None) => String::new(),
+            (LineNumbers::Absolute, Some(line)) => format!("{:>w$}", line + 1, w = width),
+            (LineNumbers::Hybrid, Some(line)) if line == cursor_line => format!("{:<w$}", line + 1, w = width),
+            (_, Some(line)) => format!("{:>w$}", line.abs_diff(cursor_line), w = width),
+        };
+
+        let mut number = StyledText::new(format!("{:<w$}", number, w = width + 1))
+            .with(StyleAttribute::Color(options.number_color));
+        if line == Some(cursor_line) && options.numbers != LineNumbers::Absolute {
+            number = number.with(StyleAttribute::Bold(true));
+        }
+        out.push(number);
+        out
+    }
+}
+
+fn digits(n: usize) -> usize {
+    n.max(1).ilog10() as usize + 1
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(gutter: &Gutter, line: Option<usize>, cursor_line: usize, options: &GutterOptions) -> String {
+        gutter.render(line, cursor_line, 120, options).iter().map(|t| t.get_text().to_string()).collect()
+    }
+
+    #[test]
+    fn width_follows_line_count() {
+        let gutter = Gutter::default();
+        let mut options = GutterOptions::default();
+        assert_eq!(gutter.width(9, &options), 2);
+        assert_eq!(gutter.width(1000, &options), 5);
+
+        options.signcolumn = true;
+        assert_eq!(gutter.width(9, &options), 4);
+
+        options.numbers = LineNumbers::Off;
+        assert_eq!(gutter.width(1000, &options), 2);
+    }
+
+    #[test]
+    fn number_modes() {
+        let gutter = Gutter::default();
+        let mut options = GutterOptions::default();
+        assert_eq!(text(&gutter, Some(4), 9, &options), "  5 ");
+        assert_eq!(text(&gutter, None, 9, &options), "    ");
+
+        options.numbers = LineNumbers::Relative;
+        assert_eq!(text(&gutter, Some(4), 9, &options), "  5 ");
+        assert_eq!(text(&gutter, Some(9), 9, &options), "  0 ");
+
+        options.numbers = LineNumbers::Hybrid;
+        assert_eq!(text(&gutter, Some(12), 9, &options), "  3 ");
+        assert_eq!(text(&gutter, Some(9), 9, &options), "10  ");
+    }
+
+    #[test]
+    fn signs() {
+        let mut gutter = Gutter::default();
+        let options = GutterOptions { signcolumn: true, ..Default::default() };
+        gutter.set_sign(2, Sign { text: "E".to_string(), color: ThemeColor::Yellow });
+
+        assert_eq!(text(&gutter, Some(2), 0, &options), "E   3 ");
+        assert_eq!(text(&gutter, Some(3), 0, &options), "    4 ");
+        // only on the first row of a line
+        assert_eq!(text(&gutter, None, 0, &options), "      ");
+
+        gutter.remove_sign(2);
+        assert_eq!(text(&gutter, Some(2), 0, &options), "    3 ");
+    }
+}
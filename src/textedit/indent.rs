@@ -0,0 +1,156 @@
+//! Helpers for measuring and building indentation
+use crate::textedit::buffer_display::display_width;
+use crate::textedit::filetype::FileType;
+
+/// How indentation is written into the buffer
+#[derive(Clone, Copy)]
+pub struct IndentStyle {
+    pub expandtab: bool,
+    pub shiftwidth: usize,
+    pub tabstop: usize,
+}
+
+impl IndentStyle {
+    /// Whitespace filling width columns. Uses tabs where possible unless expandtab is set
+    pub fn fill(&self, width: usize) -> String {
+        if self.expandtab || self.tabstop == 0 {
+            " ".repeat(width)
+        } else {
+            "\t".repeat(width / self.tabstop) + &" ".repeat(width % self.tabstop)
+        }
+    }
+
+    /// Width of one indent level
+    pub fn level(&self) -> usize {
+        self.shiftwidth.max(1)
+    }
+
+    /// What the Tab key inserts when the cursor is at display column col
+    pub fn tab_string(&self, col: usize) -> String {
+        if self.expandtab {
+            let sw = self.level();
+            " ".repeat(sw - col % sw)
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    /// Number of chars to remove from the end of a whitespace prefix to reach the previous indent level
+    pub fn dedent_count(&self, prefix: &str) -> usize {
+        let width = display_width(prefix, self.tabstop);
+        if width == 0 { return 0; }
+
+        let sw = self.level();
+        let target = (width-1) / sw * sw;
+
+        let mut chars: Vec<char> = prefix.chars().collect();
+        let mut count = 0;
+        while display_width(&chars.iter().collect::<String>(), self.tabstop) > target {
+            chars.pop();
+            count += 1;
+        }
+        count
+    }
+}
+
+/// Whitespace at the start of line
+pub fn leading_whitespace(line: &str) -> String {
+    line.chars().take_while(|c| *c == ' ' || *c == '\t').collect()
+}
+
+/// Indentation of line moved by levels. None for blank lines, which are left alone
+pub fn shift(line: &str, levels: isize, style: &IndentStyle) -> Option<String> {
+    if is_blank(line) { return None; }
+
+    let width = display_width(&leading_whitespace(line), style.tabstop) as isize;
+    let target = width + levels * style.level() as isize;
+    Some(style.fill(std::cmp::max(0, target) as usize))
+}
+
+/// New indentation for each line, following the filetype's openers and closers.
+/// prev is the closest non-blank line above the first one
+pub fn reindent(prev: Option<&str>, lines: &[String], filetype: &FileType, style: &IndentStyle) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut prev = prev.map(|p| p.to_string());
+
+    for line in lines {
+        let content = line.trim_start_matches([' ', '\t']);
+        if content.is_empty() {
+            out.push(String::new());
+            continue;
+        }
+
+        let mut width = 0;
+        if let Some(prev) = prev.as_ref() {
+            width = display_width(&leading_whitespace(prev), style.tabstop);
+            if filetype.opens_indent(prev) {
+                width += style.level();
+            }
+        }
+        if content.chars().next().is_some_and(|ch| filetype.closes_indent(ch)) {
+            width = width.saturating_sub(style.level());
+        }
+
+        let indent = style.fill(width);
+        prev = Some(format!("{}{}", indent, content));
+        out.push(indent);
+    }
+
+    out
+}
+
+/// True if text is made up of only spaces and tabs
+pub fn is_blank(text: &str) -> bool {
+    text.chars().all(|c| c == ' ' || c == '\t')
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SPACES: IndentStyle = IndentStyle { expandtab: true, shiftwidth: 4, tabstop: 8 };
+    const TABS: IndentStyle = IndentStyle { expandtab: false, shiftwidth: 8, tabstop: 8 };
+
+    #[test]
+    fn fill() {
+        assert_eq!(SPACES.fill(6), "      ");
+        assert_eq!(TABS.fill(10), "\t  ");
+    }
+
+    #[test]
+    fn tab_string() {
+        assert_eq!(SPACES.tab_string(0), "    ");
+        assert_eq!(SPACES.tab_string(5), "   ");
+        assert_eq!(TABS.tab_string(3), "\t");
+    }
+
+    #[test]
+    fn shift_lines() {
+        assert_eq!(shift("  foo", 1, &SPACES), Some("      ".to_string()));
+        assert_eq!(shift("  foo", -1, &SPACES), Some("".to_string()));
+        assert_eq!(shift("\tfoo", 1, &TABS), Some("\t\t".to_string()));
+        assert_eq!(shift("   ", 1, &SPACES), None);
+    }
+
+    #[test]
+    fn reindent_lines() {
+        let lines: Vec<String> = vec!["fn main() {", "let x = (", "1", ")", "", "}"]
+            .into_iter().map(String::from).collect();
+        let indents = reindent(None, &lines, &crate::textedit::filetype::PLAIN, &SPACES);
+        assert_eq!(indents, vec!["", "    ", "        ", "    ", "", ""]);
+
+        let lines = vec!["bar".to_string()];
+        let indents = reindent(Some("  foo {"), &lines, &crate::textedit::filetype::PLAIN, &SPACES);
+        assert_eq!(indents, vec!["      "]);
+    }
+
+    #[test]
+    fn dedent_count() {
+        assert_eq!(SPACES.dedent_count("        "), 4);
+        assert_eq!(SPACES.dedent_count("      "), 2);
+        assert_eq!(SPACES.dedent_count(""), 0);
+        assert_eq!(TABS.dedent_count("\t\t"), 1);
+    }
+}
@@ -0,0 +1,105 @@
+//! Operations that edit whole lines
+use crate::textedit::buffer::TextBuffer;
+use crate::textedit::indent::leading_whitespace;
+use crate::textedit::operation::{DeleteBack, InsertString, OperationGroup, TBOperationError, TextBufferOperation};
+use crate::textedit::traverse_ops::CursorTo;
+
+/// Replace the leading whitespace of a line. Leaves cursor after the new indent
+pub struct SetIndent {
+    line: usize,
+    indent: String,
+    op: Option<OperationGroup>
+}
+impl SetIndent {
+    pub fn new(line: usize, indent: String) -> Self { Self { line, indent, op: None } }
+}
+
+impl TextBufferOperation for SetIndent {
+    fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        let start = buffer.line_start(self.line).ok_or(TBOperationError::MovesOutOfBounds)?;
+        let current = leading_whitespace(&buffer.line_string(self.line).unwrap_or_default());
+
+        let mut op = OperationGroup(vec![
+            Box::new(CursorTo::new(start + current.len())),
+            Box::new(DeleteBack::new(current.len())),
+            Box::new(InsertString::new(self.indent.clone())),
+        ]);
+        op.apply(buffer)?;
+        self.op = Some(op);
+        Ok(())
+    }
+    fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        if self.op.is_none() { return Err(TBOperationError::LogicError(None)); }
+        self.op.as_mut().unwrap().undo(buffer)
+    }
+}
+
+
+
+/// Replace the full content of a line. Leaves cursor at the end of the line
+pub struct SetLine {
+    line: usize,
+    text: String,
+    op: Option<OperationGroup>
+}
+impl SetLine {
+    pub fn new(line: usize, text: String) -> Self { Self { line, text, op: None } }
+}
+
+impl TextBufferOperation for SetLine {
+    fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        let start = buffer.line_start(self.line).ok_or(TBOperationError::MovesOutOfBounds)?;
+        let end = buffer.line_end(self.line).ok_or(TBOperationError::MovesOutOfBounds)?;
+
+        let mut op = OperationGroup(vec![
+            Box::new(CursorTo::new(end)),
+            Box::new(DeleteBack::new(end - start)),
+            Box::new(InsertString::new(self.text.clone())),
+        ]);
+        op.apply(buffer)?;
+        self.op = Some(op);
+        Ok(())
+    }
+    fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        if self.op.is_none() { return Err(TBOperationError::LogicError(None)); }
+        self.op.as_mut().unwrap().undo(buffer)
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use crate::textedit::operation::CursorLeft;
+    use super::*;
+
+    #[test]
+    fn set_indent_undoes() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new("a\n  b\nc".to_string())));
+        buf.apply(Box::new(CursorLeft(4)));
+
+        buf.apply(Box::new(OperationGroup(vec![
+            Box::new(SetIndent::new(1, "\t".to_string())),
+            Box::new(SetIndent::new(2, "    ".to_string())),
+        ])));
+        assert_eq!(buf.string(), "a\n\tb\n    c");
+
+        buf.undo();
+        assert_eq!(buf.string(), "a\n  b\nc");
+        assert_eq!(buf.get_cursor(), 3);
+    }
+
+    #[test]
+    fn set_line_undoes() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new("one\ntwo\nthree".to_string())));
+
+        buf.apply(Box::new(SetLine::new(1, "// two".to_string())));
+        assert_eq!(buf.string(), "one\n// two\nthree");
+        assert_eq!(buf.line_count(), 3);
+
+        buf.undo();
+        assert_eq!(buf.string(), "one\ntwo\nthree");
+    }
+}
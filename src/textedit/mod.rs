@@ -2,7 +2,15 @@
 pub mod buffer;
 pub mod textwindow;
 mod operation;
-mod buffer_display;
+pub mod buffer_display;
 mod traverse_ops;
+mod indent;
+pub mod filetype;
+mod motion;
+mod line_ops;
+mod comment;
+mod viewport;
+pub mod gutter;
+mod fold;
 
 pub type fixed_char = u8;
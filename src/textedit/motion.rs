@@ -0,0 +1,324 @@
+//! Cursor motions and the operators that act on them
+use crate::textedit::buffer::TextBuffer;
+use crate::textedit::traverse_ops::{matching_bracket, next_bracket_on_line};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    FileStart,
+    FileEnd,
+    /// Bracket matching the one under (or after) the cursor
+    MatchingBracket,
+    /// Whole lines, from doubling an operator (`>>`)
+    Line,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operator {
+    Indent,
+    Dedent,
+    Reindent,
+    Comment,
+    Lowercase,
+    Uppercase,
+    ToggleCase,
+    Fold,
+}
+
+/// Normal mode command typed so far, between the bindings it is made of
+#[derive(Default, Debug)]
+pub struct Pending {
+    /// Digits of the count typed first
+    count: String,
+    /// Operator waiting for its motion
+    pub operator: Option<Operator>,
+    /// Digits of the count typed after the operator, multiplying the first one
+    motion_count: String,
+    /// `r` waiting for the char to replace with
+    pub replace: bool,
+}
+
+impl Pending {
+    pub fn is_empty(&self) -> bool {
+        self.count.is_empty() && self.operator.is_none() && !self.replace
+    }
+    pub fn clear(&mut self) {
+        *self = Pending::default();
+    }
+
+    /// Add a typed digit to the count, or to the motion's after an operator.
+    /// False for other chars, and for a leading 0, which is a motion
+    pub fn push_digit(&mut self, ch: char) -> bool {
+        let count = if self.operator.is_some() { &mut self.motion_count } else { &mut self.count };
+        let counting = ch.is_ascii_digit() && !(ch == '0' && count.is_empty());
+        if !counting || self.replace { return false; }
+        count.push(ch);
+        true
+    }
+
+    /// Count typed, 1 if there is none. Counts before and after an operator multiply
+    pub fn count(&self) -> usize {
+        let count = |digits: &str| digits.parse::<usize>().unwrap_or(1);
+        count(&self.count).saturating_mul(count(&self.motion_count))
+    }
+    /// Digits of the count as typed before the command
+    pub fn typed_count(&self) -> &str {
+        &self.count
+    }
+    /// Digits of the count as typed after the operator
+    pub fn typed_motion_count(&self) -> &str {
+        &self.motion_count
+    }
+    /// Count typed, clearing the command
+    pub fn take_count(&mut self) -> usize {
+        let count = self.count();
+        self.clear();
+        count
+    }
+}
+
+/// Actions of the operators
+const OPERATORS: &[(&str, Operator)] = &[
+    ("operator.indent", Operator::Indent),
+    ("operator.dedent", Operator::Dedent),
+    ("operator.reindent", Operator::Reindent),
+    ("operator.comment", Operator::Comment),
+    ("operator.lowercase", Operator::Lowercase),
+    ("operator.uppercase", Operator::Uppercase),
+    ("operator.toggle_case", Operator::ToggleCase),
+    ("operator.fold", Operator::Fold),
+];
+
+impl Motion {
+    /// Motion of a keymap action
+    pub fn from_action(name: &str) -> Option<Motion> {
+        Some(match name {
+            "motion.left" => Motion::Left,
+            "motion.right" => Motion::Right,
+            "motion.up" => Motion::Up,
+            "motion.down" => Motion::Down,
+            "motion.line_start" => Motion::LineStart,
+            "motion.first_non_blank" => Motion::FirstNonBlank,
+            "motion.line_end" => Motion::LineEnd,
+            "motion.word_forward" => Motion::WordForward,
+            "motion.word_backward" => Motion::WordBackward,
+            "motion.word_end" => Motion::WordEnd,
+            "motion.file_start" => Motion::FileStart,
+            "motion.file_end" => Motion::FileEnd,
+            "motion.matching_bracket" => Motion::MatchingBracket,
+            _ => return None
+        })
+    }
+
+    /// Logical index the motion lands on, starting from the buffer's cursor.
+    /// in_text tells chars in strings and comments, which bracket matching passes over
+    pub fn target(&self, buffer: &TextBuffer, count: usize, in_text: &dyn Fn(usize) -> bool) -> usize {
+        let cursor = buffer.get_cursor();
+        let line = buffer.cursor_line();
+        let start = buffer.line_start(line).unwrap_or(0);
+        let end = buffer.line_end(line).unwrap_or(0);
+        let last_line = buffer.line_count()-1;
+
+        let to_line = |target: usize| {
+            let target_start = buffer.line_start(target).unwrap_or(0);
+            let target_end = buffer.line_end(target).unwrap_or(0);
+            target_start + std::cmp::min(cursor-start, target_end-target_start)
+        };
+
+        match self {
+            Motion::Left => std::cmp::max(start, cursor.saturating_sub(count)),
+            Motion::Right => std::cmp::min(end, cursor+count),
+            Motion::Up => to_line(line.saturating_sub(count)),
+            Motion::Down => to_line(std::cmp::min(last_line, line+count)),
+            Motion::LineStart => start,
+            Motion::FirstNonBlank => first_non_blank(buffer, line),
+            Motion::LineEnd => end,
+            Motion::WordForward => (0..count).fold(cursor, |i, _| next_word_start(buffer, i)),
+            Motion::WordBackward => (0..count).fold(cursor, |i, _| prev_word_start(buffer, i)),
+            Motion::WordEnd => (0..count).fold(cursor, |i, _| next_word_end(buffer, i)),
+            Motion::FileStart => 0,
+            Motion::FileEnd => buffer.line_start(last_line).unwrap_or(0),
+            Motion::MatchingBracket => {
+                next_bracket_on_line(buffer, cursor)
+                    .and_then(|i| matching_bracket(buffer, i, in_text, usize::MAX))
+                    .unwrap_or(cursor)
+            }
+            Motion::Line => buffer.line_start(std::cmp::min(last_line, line+count-1)).unwrap_or(0),
+        }
+    }
+
+    /// Motion selects whole lines when used with an operator
+    pub fn linewise(&self) -> bool {
+        matches!(self, Motion::Up | Motion::Down | Motion::FileStart | Motion::FileEnd | Motion::Line)
+    }
+
+    /// Motion includes the char it lands on when used with an operator
+    fn inclusive(&self) -> bool {
+        matches!(self, Motion::WordEnd | Motion::MatchingBracket)
+    }
+
+    /// Range of text (inclusive..exclusive) an operator acts on
+    pub fn span(&self, buffer: &TextBuffer, count: usize, in_text: &dyn Fn(usize) -> bool) -> (usize, usize) {
+        if self.linewise() {
+            let (first, last) = self.line_range(buffer, count, in_text);
+            (buffer.line_start(first).unwrap_or(0), buffer.line_end(last).unwrap_or(0))
+        } else {
+            let (start, end) = self.range(buffer, count, in_text);
+            let end = if self.inclusive() { end + 1 } else { end };
+            (start, std::cmp::min(end, buffer.get_length()))
+        }
+    }
+
+    /// Ordered range (inclusive) covered by moving from the cursor
+    pub fn range(&self, buffer: &TextBuffer, count: usize, in_text: &dyn Fn(usize) -> bool) -> (usize, usize) {
+        let cursor = buffer.get_cursor();
+        let target = self.target(buffer, count, in_text);
+        (std::cmp::min(cursor, target), std::cmp::max(cursor, target))
+    }
+
+    /// Ordered range of lines covered by moving from the cursor
+    pub fn line_range(&self, buffer: &TextBuffer, count: usize, in_text: &dyn Fn(usize) -> bool) -> (usize, usize) {
+        let (start, end) = self.range(buffer, count, in_text);
+        (buffer.line_of(start), buffer.line_of(end))
+    }
+}
+
+impl Operator {
+    /// Operator of a keymap action
+    pub fn from_action(name: &str) -> Option<Operator> {
+        OPERATORS.iter().find(|(action, _)| *action == name).map(|(_, operator)| *operator)
+    }
+    /// Keymap action running the operator
+    pub fn action(&self) -> &'static str {
+        OPERATORS.iter().find(|(_, operator)| operator == self).map_or("", |(action, _)| action)
+    }
+}
+
+
+
+// WORDS
+
+#[derive(PartialEq)]
+enum CharClass {
+    Space,
+    Word,
+    Punctuation,
+}
+fn class(ch: char) -> CharClass {
+    if ch.is_whitespace() {
+        CharClass::Space
+    } else if ch.is_alphanumeric() || ch == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+pub fn first_non_blank(buffer: &TextBuffer, line: usize) -> usize {
+    let start = buffer.line_start(line).unwrap_or(0);
+    let end = buffer.line_end(line).unwrap_or(0);
+    (start..end).find(|i| !buffer.char_at(*i).is_some_and(|c| c == ' ' || c == '\t')).unwrap_or(end)
+}
+
+fn next_word_start(buffer: &TextBuffer, mut i: usize) -> usize {
+    let len = buffer.get_length();
+    if let Some(ch) = buffer.char_at(i) {
+        let current = class(ch);
+        if current != CharClass::Space {
+            while buffer.char_at(i).is_some_and(|c| class(c) == current) { i += 1; }
+        }
+    }
+    while buffer.char_at(i).is_some_and(|c| class(c) == CharClass::Space) { i += 1; }
+    std::cmp::min(i, len)
+}
+
+fn next_word_end(buffer: &TextBuffer, mut i: usize) -> usize {
+    let len = buffer.get_length();
+    if len == 0 { return 0; }
+    i += 1;
+    while buffer.char_at(i).is_some_and(|c| class(c) == CharClass::Space) { i += 1; }
+    if let Some(ch) = buffer.char_at(i) {
+        let current = class(ch);
+        while buffer.char_at(i+1).is_some_and(|c| class(c) == current) { i += 1; }
+    }
+    std::cmp::min(i, len-1)
+}
+
+fn prev_word_start(buffer: &TextBuffer, mut i: usize) -> usize {
+    while i > 0 && buffer.char_at(i-1).is_some_and(|c| class(c) == CharClass::Space) { i -= 1; }
+    if i == 0 { return 0; }
+    let current = class(buffer.char_at(i-1).unwrap());
+    while i > 0 && buffer.char_at(i-1).is_some_and(|c| class(c) == current) { i -= 1; }
+    i
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use crate::textedit::operation::{CursorLeft, InsertString};
+    use super::*;
+
+    fn buffer(content: &str, cursor: usize) -> TextBuffer {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new(content.to_string())));
+        buf.apply(Box::new(CursorLeft(content.len()-cursor)));
+        buf
+    }
+
+    #[test]
+    fn counts_before_commands() {
+        let mut pending = Pending::default();
+        // a leading 0 is a motion
+        assert!(!pending.push_digit('0'));
+        assert!(pending.push_digit('1'));
+        assert!(pending.push_digit('0'));
+        assert!(!pending.push_digit('j'));
+        assert_eq!((pending.typed_count(), pending.count()), ("10", 10));
+
+        pending.operator = Some(Operator::Indent);
+        assert!(!pending.push_digit('0'));
+        assert!(pending.push_digit('3'));
+        assert_eq!(pending.typed_motion_count(), "3");
+        assert_eq!(pending.take_count(), 30);
+        assert!(pending.is_empty());
+        assert_eq!(pending.count(), 1);
+        assert_eq!(Operator::from_action(Operator::Comment.action()), Some(Operator::Comment));
+    }
+
+    #[test]
+    fn word_motions() {
+        let buf = buffer("foo.bar  baz\nqux", 0);
+        assert_eq!(Motion::WordForward.target(&buf, 1, &|_| false), 3);
+        assert_eq!(Motion::WordForward.target(&buf, 3, &|_| false), 9);
+        assert_eq!(Motion::WordForward.target(&buf, 4, &|_| false), 13);
+        assert_eq!(Motion::WordEnd.target(&buf, 1, &|_| false), 2);
+        assert_eq!(Motion::WordEnd.span(&buf, 1, &|_| false), (0, 3));
+        assert_eq!(Motion::WordForward.span(&buf, 1, &|_| false), (0, 3));
+
+        let buf = buffer("foo.bar  baz\nqux", 9);
+        assert_eq!(Motion::WordBackward.target(&buf, 1, &|_| false), 4);
+        assert_eq!(Motion::WordBackward.target(&buf, 2, &|_| false), 3);
+    }
+
+    #[test]
+    fn line_motions() {
+        let buf = buffer("  one\ntwo\n    three", 8);
+        assert_eq!(Motion::LineStart.target(&buf, 1, &|_| false), 6);
+        assert_eq!(Motion::LineEnd.target(&buf, 1, &|_| false), 9);
+        assert_eq!(Motion::Up.target(&buf, 1, &|_| false), 2);
+        assert_eq!(Motion::Down.target(&buf, 1, &|_| false), 12);
+        assert_eq!(Motion::FirstNonBlank.target(&buf, 1, &|_| false), 6);
+        assert_eq!(Motion::Line.line_range(&buf, 2, &|_| false), (1, 2));
+        assert_eq!(Motion::FileStart.line_range(&buf, 1, &|_| false), (0, 1));
+    }
+}
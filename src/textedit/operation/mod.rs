@@ -1,6 +1,11 @@
 use std::collections::BTreeSet;
 use crate::textedit::buffer::TextBuffer;
 use crate::textedit::fixed_char;
+use crate::textedit::traverse_ops::CursorTo;
+
+mod transform;
+
+pub use transform::*;
 
 pub enum TBOperationError {
     GapTooSmall {
@@ -15,6 +20,33 @@ pub trait TextBufferOperation {
     fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError>;
 }
 
+/// Several operations applied and undone as one step
+pub struct OperationGroup(pub Vec<Box<dyn TextBufferOperation>>);
+
+impl TextBufferOperation for OperationGroup {
+    fn modifies(&self) -> bool {
+        self.0.iter().any(|op| op.modifies())
+    }
+    fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        for i in 0..self.0.len() {
+            if let Err(error) = self.0[i].apply(buffer) {
+                // roll back so the whole group can be retried
+                for op in self.0[..i].iter_mut().rev() {
+                    let _ = op.undo(buffer);
+                }
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+    fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        for op in self.0.iter_mut().rev() {
+            op.undo(buffer)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct InsertChar(pub char);
 
 impl TextBufferOperation for InsertChar {
@@ -22,6 +54,7 @@ impl TextBufferOperation for InsertChar {
         let cursor = buffer.get_cursor();
         if buffer.get_gap_end()-cursor < 1 { return Err(TBOperationError::GapTooSmall { required: 1 }); }
 
+        buffer.mark_edit(cursor, 0);
         let slice = &vec![self.0 as fixed_char];
         buffer.get_content_mut()[cursor..(cursor+1)].copy_from_slice(slice);
         *buffer.get_length_mut() += 1;
@@ -32,6 +65,7 @@ impl TextBufferOperation for InsertChar {
         if buffer.get_cursor() == 0 { return Err(TBOperationError::MovesOutOfBounds); }
         *buffer.get_length_mut() -= 1;
         *buffer.get_cursor_mut() -= 1;
+        buffer.mark_edit(buffer.get_cursor(), 0);
         Ok(())
     }
 }
@@ -42,11 +76,13 @@ impl TextBufferOperation for InsertLinebreak {
     fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
         InsertChar('\n').apply(buffer)?;
         buffer.set_linebreak_at(buffer.get_cursor()-1);
+        buffer.mark_edit(buffer.get_cursor()-1, 1);
         Ok(())
     }
     fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
         InsertChar('\n').undo(buffer)?;
         buffer.remove_linebreak_at(buffer.get_cursor());
+        buffer.mark_edit(buffer.get_cursor(), -1);
         Ok(())
     }
 }
@@ -76,10 +112,12 @@ impl TextBufferOperation for DeleteBack {
             }
         }
 
+        let linebreaks = moved.iter().filter(|ch| **ch == '\n' as fixed_char).count();
         self.removed = Some(Vec::from(moved));
 
         *buffer.get_cursor_mut() -= n;
         *buffer.get_length_mut() -= n;
+        buffer.mark_edit(buffer.get_cursor(), -(linebreaks as isize));
 
         Ok(())
     }
@@ -90,6 +128,8 @@ impl TextBufferOperation for DeleteBack {
 
         if self.removed.is_none() { return Err(TBOperationError::LogicError(Some("no string found, operation hasn't been applied".to_string())))}
 
+        let linebreaks = self.removed.as_ref().unwrap().iter().filter(|ch| **ch == '\n' as fixed_char).count();
+        buffer.mark_edit(cursor, linebreaks as isize);
         for (i, ch) in self.removed.as_ref().unwrap().iter().enumerate() {
             if *ch == '\n' as fixed_char {
                 buffer.set_linebreak_at(cursor+i);
@@ -114,6 +154,8 @@ impl TextBufferOperation for InsertString {
         let cursor = buffer.get_cursor();
         if buffer.get_gap_end()-cursor < n { return Err(TBOperationError::GapTooSmall { required: n }); }
 
+        let linebreaks = self.0.iter().filter(|ch| **ch == '\n' as fixed_char).count();
+        buffer.mark_edit(cursor, linebreaks as isize);
         for (i, ch) in self.0.iter().enumerate() {
             if *ch == '\n' as fixed_char {
                 buffer.set_linebreak_at(cursor+i);
@@ -139,6 +181,8 @@ impl TextBufferOperation for InsertString {
 
         *buffer.get_length_mut() -= n;
         *buffer.get_cursor_mut() -= n;
+        let linebreaks = self.0.iter().filter(|ch| **ch == '\n' as fixed_char).count();
+        buffer.mark_edit(buffer.get_cursor(), -(linebreaks as isize));
         Ok(())
     }
 }
@@ -215,3 +259,35 @@ impl TextBufferOperation for CursorLeft {
         _cursor_right(self.0, buffer)
     }
 }
+
+
+
+/// Replace logical range start..end with text. Leaves cursor after the new text
+pub struct ReplaceRange {
+    start: usize,
+    end: usize,
+    text: String,
+    op: Option<OperationGroup>
+}
+impl ReplaceRange {
+    pub fn new(start: usize, end: usize, text: String) -> Self { Self { start, end, text, op: None } }
+}
+
+impl TextBufferOperation for ReplaceRange {
+    fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        if self.start > self.end || self.end > buffer.get_length() { return Err(TBOperationError::MovesOutOfBounds); }
+
+        let mut op = OperationGroup(vec![
+            Box::new(CursorTo::new(self.end)),
+            Box::new(DeleteBack::new(self.end - self.start)),
+            Box::new(InsertString::new(self.text.clone())),
+        ]);
+        op.apply(buffer)?;
+        self.op = Some(op);
+        Ok(())
+    }
+    fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        if self.op.is_none() { return Err(TBOperationError::LogicError(None)); }
+        self.op.as_mut().unwrap().undo(buffer)
+    }
+}
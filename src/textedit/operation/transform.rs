@@ -0,0 +1,331 @@
+//! Operations that rewrite existing text in place
+use crate::textedit::buffer::TextBuffer;
+use crate::textedit::operation::{OperationGroup, ReplaceRange, TBOperationError, TextBufferOperation};
+use crate::textedit::traverse_ops::CursorTo;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Case {
+    Upper,
+    Lower,
+    Toggle,
+}
+
+impl Case {
+    pub fn convert(&self, text: &str) -> String {
+        text.chars().map(|ch| match self {
+            Case::Upper => ch.to_ascii_uppercase(),
+            Case::Lower => ch.to_ascii_lowercase(),
+            Case::Toggle if ch.is_ascii_uppercase() => ch.to_ascii_lowercase(),
+            Case::Toggle => ch.to_ascii_uppercase(),
+        }).collect()
+    }
+}
+
+/// Text in logical range start..end
+fn text_in(buffer: &TextBuffer, start: usize, end: usize) -> String {
+    (start..end).filter_map(|i| buffer.char_at(i)).collect()
+}
+
+/// Replace then move cursor, all as one group that is kept for undo
+fn replace_then_move(buffer: &mut TextBuffer, start: usize, end: usize, text: String, cursor: usize) -> Result<OperationGroup, TBOperationError> {
+    let mut op = OperationGroup(vec![
+        Box::new(ReplaceRange::new(start, end, text)),
+        Box::new(CursorTo::new(cursor)),
+    ]);
+    op.apply(buffer)?;
+    Ok(op)
+}
+
+
+
+/// Change case of logical range start..end, leaving cursor at cursor
+pub struct ChangeCase {
+    start: usize,
+    end: usize,
+    case: Case,
+    cursor: usize,
+    op: Option<OperationGroup>
+}
+impl ChangeCase {
+    pub fn new(start: usize, end: usize, case: Case, cursor: usize) -> Self {
+        Self { start, end, case, cursor, op: None }
+    }
+}
+
+impl TextBufferOperation for ChangeCase {
+    fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        let text = self.case.convert(&text_in(buffer, self.start, self.end));
+        self.op = Some(replace_then_move(buffer, self.start, self.end, text, self.cursor)?);
+        Ok(())
+    }
+    fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        if self.op.is_none() { return Err(TBOperationError::LogicError(None)); }
+        self.op.as_mut().unwrap().undo(buffer)
+    }
+}
+
+
+
+/// Join the cursor's line with the lines below it.
+/// With spaces, the next line's indent is replaced by a single space (like `J`),
+/// otherwise lines are joined as they are (like `gJ`)
+pub struct JoinLines {
+    lines: usize,
+    spaces: bool,
+    op: Option<OperationGroup>
+}
+impl JoinLines {
+    /// Join lines total lines (at least two)
+    pub fn new(lines: usize, spaces: bool) -> Self { Self { lines: std::cmp::max(lines, 2), spaces, op: None } }
+}
+
+impl TextBufferOperation for JoinLines {
+    fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        let line = buffer.cursor_line();
+        if line+1 >= buffer.line_count() { return Err(TBOperationError::MovesOutOfBounds); }
+
+        let mut group = OperationGroup(Vec::new());
+        let mut join_point = buffer.get_cursor();
+
+        for _ in 1..self.lines {
+            if line+1 >= buffer.line_count() { break; }
+
+            let end = buffer.line_end(line).unwrap_or(0);
+            let mut op: Box<dyn TextBufferOperation> = if self.spaces {
+                let next = buffer.line_string(line+1).unwrap_or_default();
+                let content = next.trim_start_matches([' ', '\t']);
+                let indent = next.len() - content.len();
+
+                let ends_blank = end == buffer.line_start(line).unwrap_or(0)
+                    || buffer.char_at(end-1).is_some_and(|c| c == ' ' || c == '\t');
+                let separator = if ends_blank || content.is_empty() || content.starts_with(')') { "" } else { " " };
+
+                Box::new(ReplaceRange::new(end, end + 1 + indent, separator.to_string()))
+            } else {
+                Box::new(ReplaceRange::new(end, end + 1, String::new()))
+            };
+
+            if let Err(error) = op.apply(buffer) {
+                let _ = group.undo(buffer);
+                return Err(error);
+            }
+            group.0.push(op);
+            join_point = end;
+        }
+
+        let mut cursor = CursorTo::new(join_point);
+        if let Err(error) = cursor.apply(buffer) {
+            let _ = group.undo(buffer);
+            return Err(error);
+        }
+        group.0.push(Box::new(cursor));
+
+        self.op = Some(group);
+        Ok(())
+    }
+    fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        if self.op.is_none() { return Err(TBOperationError::LogicError(None)); }
+        self.op.as_mut().unwrap().undo(buffer)
+    }
+}
+
+
+
+/// Overwrite count chars from the cursor with ch. Cursor ends on the last replaced char
+pub struct ReplaceChars {
+    ch: char,
+    count: usize,
+    op: Option<OperationGroup>
+}
+impl ReplaceChars {
+    pub fn new(ch: char, count: usize) -> Self { Self { ch, count, op: None } }
+}
+
+impl TextBufferOperation for ReplaceChars {
+    fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        let start = buffer.get_cursor();
+        let end = start + self.count;
+        let line_end = buffer.line_end(buffer.cursor_line()).unwrap_or(0);
+        if self.count == 0 || end > line_end { return Err(TBOperationError::MovesOutOfBounds); }
+
+        let text = self.ch.to_string().repeat(self.count);
+        self.op = Some(replace_then_move(buffer, start, end, text, end-1)?);
+        Ok(())
+    }
+    fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        if self.op.is_none() { return Err(TBOperationError::LogicError(None)); }
+        self.op.as_mut().unwrap().undo(buffer)
+    }
+}
+
+
+
+/// Add delta to the number under or after the cursor on its line.
+/// Understands decimal, hex (0x) and binary (0b). Cursor ends on the number's last char
+pub struct IncrementNumber {
+    delta: i64,
+    op: Option<OperationGroup>
+}
+impl IncrementNumber {
+    pub fn new(delta: i64) -> Self { Self { delta, op: None } }
+}
+
+impl TextBufferOperation for IncrementNumber {
+    fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        let line = buffer.cursor_line();
+        let line_start = buffer.line_start(line).unwrap_or(0);
+        let text = buffer.line_string(line).unwrap_or_default();
+
+        let (start, end, number) = increment_in_line(&text, buffer.cursor_col(), self.delta)
+            .ok_or(TBOperationError::MovesOutOfBounds)?;
+
+        let cursor = line_start + start + number.len() - 1;
+        self.op = Some(replace_then_move(buffer, line_start + start, line_start + end, number, cursor)?);
+        Ok(())
+    }
+    fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        if self.op.is_none() { return Err(TBOperationError::LogicError(None)); }
+        self.op.as_mut().unwrap().undo(buffer)
+    }
+}
+
+/// Find the first number ending after col and add delta to it.
+/// Returns the number's range in line and its new text
+pub fn increment_in_line(line: &str, col: usize, delta: i64) -> Option<(usize, usize, String)> {
+    let chars: Vec<char> = line.chars().collect();
+
+    let mut i = 0;
+    while i < chars.len() {
+        let radix_prefix = chars[i] == '0'
+            && chars.get(i+1).is_some_and(|c| matches!(c, 'x' | 'X' | 'b' | 'B'));
+
+        if radix_prefix {
+            let radix = if matches!(chars[i+1], 'x' | 'X') { 16 } else { 2 };
+            let digits = chars[i+2..].iter().take_while(|c| c.is_digit(radix)).count();
+            let end = i + 2 + digits;
+            if digits > 0 && end > col {
+                let digit_text: String = chars[i+2..end].iter().collect();
+                let value = u64::from_str_radix(&digit_text, radix).ok()?;
+                let value = value.wrapping_add_signed(delta);
+
+                let upper = digit_text.chars().any(|c| c.is_ascii_uppercase());
+                let mut formatted = match (radix, upper) {
+                    (16, true) => format!("{:0width$X}", value, width = digits),
+                    (16, false) => format!("{:0width$x}", value, width = digits),
+                    _ => format!("{:0width$b}", value, width = digits),
+                };
+                formatted.insert_str(0, &chars[i..i+2].iter().collect::<String>());
+                return Some((i, end, formatted));
+            }
+            i = end.max(i+1);
+            continue;
+        }
+
+        if chars[i].is_ascii_digit() {
+            let digits = chars[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+            let end = i + digits;
+            if end > col {
+                let negative = i > 0 && chars[i-1] == '-';
+                let start = if negative { i-1 } else { i };
+                let text: String = chars[start..end].iter().collect();
+                let value = text.parse::<i64>().ok()?.wrapping_add(delta);
+
+                // keep zero padding
+                let width = if chars[i] == '0' && digits > 1 { digits } else { 0 };
+                let formatted = if value < 0 {
+                    format!("-{:0width$}", value.unsigned_abs(), width = width)
+                } else {
+                    format!("{:0width$}", value, width = width)
+                };
+                return Some((start, end, formatted));
+            }
+            i = end;
+            continue;
+        }
+
+        i += 1;
+    }
+
+    None
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use crate::textedit::operation::{CursorLeft, InsertString};
+    use super::*;
+
+    fn buffer(content: &str, cursor: usize) -> TextBuffer {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new(content.to_string())));
+        buf.apply(Box::new(CursorLeft(content.len()-cursor)));
+        buf
+    }
+
+    #[test]
+    fn change_case() {
+        let mut buf = buffer("hello World", 0);
+        buf.apply(Box::new(ChangeCase::new(0, 5, Case::Upper, 0)));
+        assert_eq!(buf.string(), "HELLO World");
+        buf.apply(Box::new(ChangeCase::new(0, 11, Case::Toggle, 0)));
+        assert_eq!(buf.string(), "hello wORLD");
+        assert_eq!(buf.get_cursor(), 0);
+
+        buf.undo();
+        assert_eq!(buf.string(), "HELLO World");
+    }
+
+    #[test]
+    fn join_lines() {
+        let mut buf = buffer("foo\n    bar\n\nbaz", 1);
+        buf.apply(Box::new(JoinLines::new(2, true)));
+        assert_eq!(buf.string(), "foo bar\n\nbaz");
+        assert_eq!(buf.get_cursor(), 3);
+
+        buf.apply(Box::new(JoinLines::new(3, true)));
+        assert_eq!(buf.string(), "foo bar baz");
+
+        buf.undo();
+        assert_eq!(buf.string(), "foo bar\n\nbaz");
+        buf.undo();
+        assert_eq!(buf.string(), "foo\n    bar\n\nbaz");
+
+        buf.apply(Box::new(JoinLines::new(2, false)));
+        assert_eq!(buf.string(), "foo    bar\n\nbaz");
+    }
+
+    #[test]
+    fn replace_chars() {
+        let mut buf = buffer("abcd\nef", 1);
+        buf.apply(Box::new(ReplaceChars::new('x', 2)));
+        assert_eq!(buf.string(), "axxd\nef");
+        assert_eq!(buf.get_cursor(), 2);
+
+        // can't run past the line
+        buf.apply(Box::new(ReplaceChars::new('x', 5)));
+        assert_eq!(buf.string(), "axxd\nef");
+    }
+
+    #[test]
+    fn increments_numbers() {
+        assert_eq!(increment_in_line("x = 9;", 0, 1), Some((4, 5, "10".to_string())));
+        assert_eq!(increment_in_line("x = 1, y = 2", 6, 1), Some((11, 12, "3".to_string())));
+        assert_eq!(increment_in_line("a-1", 0, 3), Some((1, 3, "2".to_string())));
+        assert_eq!(increment_in_line("007", 0, 1), Some((0, 3, "008".to_string())));
+        assert_eq!(increment_in_line("0xff", 0, 1), Some((0, 4, "0x100".to_string())));
+        assert_eq!(increment_in_line("0x0F", 2, -1), Some((0, 4, "0x0E".to_string())));
+        assert_eq!(increment_in_line("0b0111", 0, 1), Some((0, 6, "0b1000".to_string())));
+        assert_eq!(increment_in_line("none", 0, 1), None);
+    }
+
+    #[test]
+    fn increment_operation() {
+        let mut buf = buffer("a\nlet n = 41;", 3);
+        buf.apply(Box::new(IncrementNumber::new(1)));
+        assert_eq!(buf.string(), "a\nlet n = 42;");
+        assert_eq!(buf.get_cursor(), 11);
+        buf.undo();
+        assert_eq!(buf.string(), "a\nlet n = 41;");
+    }
+}
@@ -1,57 +1,192 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::default;
 use std::error::Error;
 use std::ops::AddAssign;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crossterm::event::{KeyCode, KeyModifiers};
+use crate::cmdline::{ArgKind, CommandSpec, ParsedCommand};
 use crate::event::{EventPoster, Uuid};
+use crate::keymap::{describe, keymaps, keys_to_string, Action, Feed, Key, KeyHints, KeyMatcher};
 use crate::plot::Plot;
 use crate::popup::PopUp;
 use crate::style::{Canvas, StyleAttribute, StyledText, ThemeColor};
+use crate::syntax::{self, HighlightCache, Span, SYNC_LINES};
 use crate::alert::Alert;
+use crate::config::{apply_saved_set, current_settings, file_overrides, save_file_option, Settings, Table};
 use crate::textedit::buffer::TextBuffer;
-use crate::textedit::buffer_display::wrap_content;
-use crate::textedit::operation::{CursorLeft, CursorRight, DeleteBack, InsertChar, InsertLinebreak, InsertString, TextBufferOperation};
-use crate::textedit::traverse_ops::{EndOfLine, LineMovement};
-// use crate::textedit::traverse_ops::{EndOfLine, LineMovement};
+use crate::textedit::buffer_display::{char_columns, clip, display_width, expand_tabs, layout_line, locate_in_line, DisplayOptions, Segment};
+use crate::textedit::filetype::{FileType, PLAIN};
+use crate::textedit::fold::{Fold, FoldMethod, Folds, LineKey};
+use crate::textedit::gutter::{Gutter, GutterOptions, LineNumbers};
+use crate::textedit::indent::{is_blank, leading_whitespace, reindent, shift, IndentStyle};
+use crate::textedit::line_ops::{SetIndent, SetLine};
+use crate::textedit::comment::toggle_comments;
+use crate::textedit::motion::{first_non_blank, Motion, Operator, Pending};
+use crate::textedit::operation::{Case, ChangeCase, CursorLeft, CursorRight, DeleteBack, IncrementNumber, InsertChar, InsertLinebreak, InsertString, JoinLines, OperationGroup, ReplaceChars, ReplaceRange, TextBufferOperation};
+use crate::textedit::traverse_ops::{matching_bracket, CursorTo, EndOfLine, LineMovement};
+use crate::textedit::viewport::Viewport;
+// use crate::textedit::traverse_ops::{matching_bracket, CursorTo, EndOfLine, LineMovement};
 use crate::window::{WindowRequest, Window, WindowEvent};
 
 enum Mode {
     Normal,
-    Insert
+    Insert,
+    /// Selection from anchor to cursor
+    Visual(usize),
+    /// Selection of whole lines from anchor's line to cursor's line
+    VisualLine(usize),
+    /// Typed chars overwrite existing ones
+    Replace,
 }
 
 struct TextWindowSettings {
     insert_color: ThemeColor,
     normal_color: ThemeColor,
+    visual_color: ThemeColor,
+    selection_color: ThemeColor,
+    replace_color: ThemeColor,
     line_number_color: ThemeColor,
+    match_color: ThemeColor,
+    fold_color: ThemeColor,
+    show_match: bool,
     dynamic_caret_color: bool,
     line_numbers: bool,
+    relative_numbers: bool,
+    signcolumn: bool,
+    tabstop: usize,
+    expandtab: bool,
+    shiftwidth: usize,
+    autoindent: bool,
+    smartindent: bool,
+    autopairs: bool,
+    /// Rows kept visible above and below the cursor
+    scrolloff: usize,
+    wrap: bool,
+    /// Columns kept visible left and right of the cursor when not wrapping
+    sidescrolloff: usize,
+    linebreak: bool,
+    breakindent: bool,
+    showbreak: String,
+    foldmethod: FoldMethod,
 }
 
-impl Default for TextWindowSettings {
-    fn default() -> Self {
+impl TextWindowSettings {
+    fn indent_style(&self) -> IndentStyle {
+        IndentStyle {
+            expandtab: self.expandtab,
+            shiftwidth: self.shiftwidth,
+            tabstop: self.tabstop,
+        }
+    }
+    fn display_options(&self) -> DisplayOptions {
+        DisplayOptions {
+            tabstop: self.tabstop,
+            wrap: self.wrap,
+            linebreak: self.linebreak,
+            breakindent: self.breakindent,
+            showbreak: self.showbreak.clone(),
+            ..Default::default()
+        }
+    }
+    fn gutter_options(&self) -> GutterOptions {
+        GutterOptions {
+            numbers: LineNumbers::from_flags(self.line_numbers, self.relative_numbers),
+            signcolumn: self.signcolumn,
+            number_color: self.line_number_color,
+        }
+    }
+}
+
+impl From<&Settings> for TextWindowSettings {
+    fn from(settings: &Settings) -> Self {
         Self {
-            insert_color: ThemeColor::Blue,
-            normal_color: ThemeColor::Gray,
-            line_number_color: ThemeColor::Green,
-            dynamic_caret_color: true,
-            line_numbers: true,
+            insert_color: settings.color("insertcolor"),
+            normal_color: settings.color("normalcolor"),
+            visual_color: settings.color("visualcolor"),
+            selection_color: settings.color("selectioncolor"),
+            replace_color: settings.color("replacecolor"),
+            line_number_color: settings.color("numbercolor"),
+            match_color: settings.color("matchcolor"),
+            fold_color: settings.color("foldcolor"),
+            show_match: settings.bool("showmatch"),
+            dynamic_caret_color: settings.bool("dynamiccaret"),
+            line_numbers: settings.bool("number"),
+            relative_numbers: settings.bool("relativenumber"),
+            signcolumn: settings.bool("signcolumn"),
+            tabstop: settings.number("tabstop").max(1),
+            expandtab: settings.bool("expandtab"),
+            shiftwidth: settings.number("shiftwidth").max(1),
+            autoindent: settings.bool("autoindent"),
+            smartindent: settings.bool("smartindent"),
+            autopairs: settings.bool("autopairs"),
+            scrolloff: settings.number("scrolloff"),
+            wrap: settings.bool("wrap"),
+            sidescrolloff: settings.number("sidescrolloff"),
+            linebreak: settings.bool("linebreak"),
+            breakindent: settings.bool("breakindent"),
+            showbreak: settings.text("showbreak").to_string(),
+            foldmethod: FoldMethod::from_name(settings.text("foldmethod")).unwrap_or(FoldMethod::Manual),
         }
     }
 }
 
+impl Default for TextWindowSettings {
+    /// Settings currently configured
+    fn default() -> Self {
+        Self::from(&*current_settings())
+    }
+}
+
 pub struct TextWindow {
     tb: TextBuffer,
     poster: Option<EventPoster<WindowRequest, Uuid>>,
     name: String,
+    /// File the buffer was read from
+    path: Option<PathBuf>,
+    /// Options set for this file only, over the current settings
+    overrides: Table,
+    filetype: &'static FileType,
+    /// Syntax highlighting, None for plain text
+    highlighter: Option<RefCell<HighlightCache>>,
     mode: Mode,
+    /// Count, operator or `r` of an unfinished normal mode command
+    pending: Pending,
+    /// Keys typed towards a binding of the mode's keymap
+    keys: KeyMatcher,
+    /// Chars overwritten in replace mode, None where text was added instead
+    replaced: Vec<Option<char>>,
     settings: TextWindowSettings,
+    gutter: Gutter,
+    folds: Folds,
     unsaved_popup: Box<dyn PopUp>,
+    /// Messages to show once the window has a poster
+    alerts: Vec<String>,
 
-    scroll: usize,
+    view: Viewport,
+    /// Canvas size of the last draw
+    dim: Cell<Plot>,
     focused: bool
 }
 
+/// Whether char col of a line is in one of its string or comment spans
+fn in_text(spans: &[Span], col: usize) -> bool {
+    spans.iter().any(|span| span.start <= col && col < span.end && span.scope.is_text())
+}
+
+/// Commands of text windows
+const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("write", "w", "Save the file, or write the text to another").arg(ArgKind::Path),
+    CommandSpec::new("wq", "wq", "Save the file and close it").arg(ArgKind::Path),
+    CommandSpec::new("quit", "q", "Close the file, or discard its changes with !").bang(),
+    CommandSpec::new("setfile", "setf", "Change options and save them for this file").required(ArgKind::Options),
+    CommandSpec::new("tl", "tl", "Toggle line numbers"),
+    CommandSpec::new("", "", "Go to the last line of the range").range(),
+    CommandSpec::new(">", ">", "Indent the lines of the range").range(),
+    CommandSpec::new("<", "<", "Dedent the lines of the range").range(),
+    CommandSpec::new("fold", "fo", "Fold the lines of the range").range(),
+];
+
 
 
 // TEXT TAB IMPL
@@ -59,15 +194,42 @@ pub struct TextWindow {
 impl TextWindow {
     pub fn new(tb: TextBuffer) -> TextWindow {
         let def_name = "[untitled]".to_string();
-        TextWindow { tb, focused: false, poster: None, mode: Mode::Normal, settings: TextWindowSettings::default(), scroll: 0, unsaved_popup: Self::unsaved_popup(&def_name), name: def_name}
+        TextWindow { tb, focused: false, poster: None, path: None, overrides: Table::new(), filetype: &PLAIN, highlighter: None, mode: Mode::Normal, pending: Pending::default(), keys: KeyMatcher::default(), replaced: Vec::new(), settings: TextWindowSettings::default(), gutter: Gutter::default(), folds: Folds::default(), view: Viewport::default(), dim: Cell::new(Plot::new(0,0)), unsaved_popup: Self::unsaved_popup(&def_name), alerts: Vec::new(), name: def_name}
     }
     pub fn from_file(path: PathBuf) -> TextWindow {
-        let name = path.file_name().unwrap().to_string_lossy().into();
-        let mut tb = TextBuffer::from(path);
-        let mut tw = Self::new(tb);
-        tw.name = name;
+        let mut tw = Self::new(TextBuffer::from(path.clone()));
+        tw.set_path(path);
         tw
     }
+    /// Window for the file at path, empty if there is no file there yet
+    pub fn open(path: PathBuf) -> Result<TextWindow, String> {
+        let tb = match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                let mut tb = TextBuffer::new();
+                tb.apply(Box::new(InsertString::new(content)));
+                tb
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => TextBuffer::new(),
+            Err(e) => return Err(format!("Can't open {}: {}", path.display(), e)),
+        };
+        let mut tw = Self::new(tb);
+        tw.set_path(path);
+        Ok(tw)
+    }
+    /// Take the file at path as this buffer's, with its file type and overrides
+    fn set_path(&mut self, path: PathBuf) {
+        self.name = path.file_name().map_or(path.display().to_string(), |name| name.to_string_lossy().into());
+        self.filetype = FileType::from_path(&path);
+        self.highlighter = syntax::registry().for_path(&path).map(|g| RefCell::new(HighlightCache::new(g)));
+        self.overrides = file_overrides(&path).unwrap_or_else(|e| {
+            self.alert(e);
+            Table::new()
+        });
+        self.unsaved_popup = Self::unsaved_popup(&self.name);
+        self.path = Some(path);
+        self.reconfigure();
+        self.compute_folds();
+    }
     fn unsaved_popup(name: &str) -> Box<dyn PopUp> {
         let save = StyledText::new("Save".into())
             .with(StyleAttribute::Bold(true));
@@ -90,80 +252,818 @@ impl TextWindow {
 
     fn insert_mode_input(&mut self, key: KeyCode, modifiers: KeyModifiers) {
         match (key, modifiers) {
-            (_, KeyModifiers::CONTROL) => match key {
-                KeyCode::Char('[') => self.mode = Mode::Normal,
-                KeyCode::Char('z') => self.tb.undo(),
-                _ => ()
-            },
-            (KeyCode::Char('['), _) => self.mode = Mode::Normal,
-
+            (_, KeyModifiers::CONTROL) => (),
+            (KeyCode::Backspace, _) => self.backspace(),
+            (KeyCode::Tab, _) => {
+                let before = self.text_before_cursor();
+                let col = display_width(&before, self.settings.tabstop);
+                let indent = self.settings.indent_style().tab_string(col);
+                self.tb.apply(Box::new(InsertString::new(indent)));
+            }
+            (KeyCode::Enter, _) => self.linebreak(),
+            (KeyCode::Char(ch), _) => self.insert_char(ch),
+            _ => ()
+        }
+    }
+    fn replace_mode_input(&mut self, key: KeyCode, modifiers: KeyModifiers) {
+        match (key, modifiers) {
             (KeyCode::Backspace, _) => {
-                self.tb.apply(Box::new(DeleteBack::new(1)));
+                match self.replaced.pop() {
+                    Some(Some(original)) => {
+                        let cursor = self.tb.get_cursor();
+                        self.tb.apply(Box::new(OperationGroup(vec![
+                            Box::new(ReplaceRange::new(cursor-1, cursor, original.to_string())),
+                            Box::new(CursorLeft(1)),
+                        ])));
+                    }
+                    Some(None) => self.tb.apply(Box::new(DeleteBack::new(1))),
+                    // nothing typed, just move back
+                    None => self.tb.apply(Box::new(CursorLeft(1))),
+                }
             }
             (KeyCode::Enter, _) => {
+                self.replaced.push(None);
                 self.tb.apply(Box::new(InsertLinebreak));
             }
-            (KeyCode::Char(ch), _) => {
-                self.tb.apply(Box::new(InsertChar(ch)));
+            (KeyCode::Char(ch), modifiers) if !modifiers.contains(KeyModifiers::CONTROL) => {
+                let cursor = self.tb.get_cursor();
+                let original = self.tb.char_at(cursor).filter(|c| *c != '\n');
+                self.replaced.push(original);
+
+                if original.is_some() {
+                    self.tb.apply(Box::new(ReplaceRange::new(cursor, cursor+1, ch.to_string())));
+                } else {
+                    self.tb.apply(Box::new(InsertChar(ch)));
+                }
             }
-            (KeyCode::Esc, _) => self.mode = Mode::Normal,
             _ => ()
         }
     }
-    fn normal_mode_input(&mut self, key: KeyCode, modifiers: KeyModifiers) {
-        match (key, modifiers) {
-            (key, KeyModifiers::CONTROL) => match key {
-                KeyCode::Char('s') => {
-                    // self.tb.save();
+
+    /// Whether the char at a logical index is in a string or comment, highlighting each line asked about once
+    fn text_at(&self) -> impl Fn(usize) -> bool + '_ {
+        let lines: RefCell<HashMap<usize, Vec<Span>>> = RefCell::default();
+        move |index| {
+            let Some(highlighter) = &self.highlighter else { return false };
+            let line = self.tb.line_of(index);
+            let col = index - self.tb.line_start(line).unwrap_or(0);
+            let mut lines = lines.borrow_mut();
+            let spans = lines.entry(line).or_insert_with(|| {
+                highlighter.borrow().highlight(line, &self.tb.line_string(line).unwrap_or_default())
+            });
+            in_text(spans, col)
+        }
+    }
+
+    /// Whether a char typed at col of the cursor's line goes in a string or comment
+    fn typing_in_text(&self, col: usize) -> bool {
+        let Some(highlighter) = &self.highlighter else { return false };
+        let line = self.tb.cursor_line();
+        // a space stands in for the char, as it can't end a string or comment
+        let mut text: Vec<char> = self.tb.line_string(line).unwrap_or_default().chars().collect();
+        text.insert(col.min(text.len()), ' ');
+        in_text(&highlighter.borrow().highlight(line, &text.iter().collect::<String>()), col)
+    }
+
+    /// Text on the cursor's line, up to the cursor
+    fn text_before_cursor(&self) -> String {
+        let line = self.tb.line_string(self.tb.cursor_line()).unwrap_or_default();
+        line.chars().take(self.tb.cursor_col()).collect()
+    }
+
+    /// Inserts a typed char, handling pairs and closers
+    fn insert_char(&mut self, ch: char) {
+        let cursor = self.tb.get_cursor();
+        let next = self.tb.char_at(cursor);
+        let prev = if cursor > 0 { self.tb.char_at(cursor-1) } else { None };
+
+        if self.settings.autopairs {
+            // in strings and comments only the quote ending them is stepped over, nothing is paired
+            let in_text = self.typing_in_text(self.tb.cursor_col());
+
+            // step over closer that was inserted with its opener
+            let own_close = self.filetype.pair_close(ch) == Some(ch);
+            if next == Some(ch) && self.filetype.is_pair_close(ch) && (!in_text || own_close) {
+                self.tb.apply(Box::new(CursorRight(1)));
+                return;
+            }
+
+            if let Some(close) = self.filetype.pair_close(ch).filter(|_| !in_text) {
+                let next_free = next.is_none_or(|c| c.is_whitespace() || self.filetype.is_pair_close(c));
+                // quotes right after a word are likely apostrophes
+                let is_quote = ch == close;
+                let after_word = prev.is_some_and(|c| c.is_alphanumeric() || c == '_');
+
+                if next_free && !(is_quote && after_word) {
+                    self.tb.apply(Box::new(OperationGroup(vec![
+                        Box::new(InsertChar(ch)),
+                        Box::new(InsertChar(close)),
+                        Box::new(CursorLeft(1)),
+                    ])));
+                    return;
+                }
+            }
+        }
+
+        if self.settings.smartindent && self.filetype.closes_indent(ch) {
+            self.insert_closer(ch);
+        } else {
+            self.tb.apply(Box::new(InsertChar(ch)));
+        }
+    }
+
+    /// Deletes one char, or one indent level when in leading whitespace.
+    /// Removes both halves of an empty pair
+    fn backspace(&mut self) {
+        let cursor = self.tb.get_cursor();
+        if self.settings.autopairs && cursor > 0 {
+            let prev = self.tb.char_at(cursor-1);
+            let next = self.tb.char_at(cursor);
+            let pair = prev.is_some_and(|p| self.filetype.pair_close(p).is_some() && self.filetype.pair_close(p) == next);
+            // probed before the opener, as an empty string's quotes would hold the probe
+            if pair && !self.typing_in_text(self.tb.cursor_col() - 1) {
+                self.tb.apply(Box::new(OperationGroup(vec![
+                    Box::new(CursorRight(1)),
+                    Box::new(DeleteBack::new(2)),
+                ])));
+                return;
+            }
+        }
+
+        let before = self.text_before_cursor();
+        let count = if !before.is_empty() && is_blank(&before) {
+            self.settings.indent_style().dedent_count(&before)
+        } else {
+            1
+        };
+        self.tb.apply(Box::new(DeleteBack::new(count)));
+    }
+
+    /// Inserts a linebreak, carrying over indentation when enabled.
+    /// Applied as one operation so it undoes together
+    fn linebreak(&mut self) {
+        let before = self.text_before_cursor();
+        let style = self.settings.indent_style();
+
+        let mut indent = String::new();
+        if self.settings.autoindent {
+            indent = leading_whitespace(&before);
+        }
+
+        let mut ops: Vec<Box<dyn TextBufferOperation>> = vec![Box::new(InsertLinebreak)];
+        if self.settings.smartindent && self.filetype.opens_indent(&before) {
+            let width = display_width(&indent, style.tabstop);
+            let inner = style.fill(width + style.level());
+            ops.push(Box::new(InsertString::new(inner.clone())));
+
+            // opener directly followed by its closer, move closer to its own line
+            let after = self.tb.char_at(self.tb.get_cursor());
+            if after.is_some_and(|ch| self.filetype.closes_indent(ch)) {
+                ops.push(Box::new(InsertLinebreak));
+                ops.push(Box::new(InsertString::new(indent.clone())));
+                ops.push(Box::new(CursorLeft(indent.len() + 1)));
+            }
+        } else {
+            ops.push(Box::new(InsertString::new(indent)));
+        }
+
+        self.tb.apply(Box::new(OperationGroup(ops)));
+    }
+
+    /// Inserts a closing char, removing one indent level if it starts the line
+    fn insert_closer(&mut self, ch: char) {
+        let before = self.text_before_cursor();
+        if before.is_empty() || !is_blank(&before) {
+            self.tb.apply(Box::new(InsertChar(ch)));
+            return;
+        }
+
+        let style = self.settings.indent_style();
+        let width = display_width(&before, style.tabstop);
+        let indent = style.fill(width.saturating_sub(style.level()));
+
+        self.tb.apply(Box::new(OperationGroup(vec![
+            Box::new(DeleteBack::new(before.chars().count())),
+            Box::new(InsertString::new(indent)),
+            Box::new(InsertChar(ch)),
+        ])));
+    }
+
+    /// Keymap of the mode
+    fn keymap_context(&self) -> &'static str {
+        match self.mode {
+            Mode::Normal => "normal",
+            Mode::Insert => "insert",
+            Mode::Visual(_) | Mode::VisualLine(_) => "visual",
+            Mode::Replace => "replace",
+        }
+    }
+
+    fn key_input(&mut self, key: Key) {
+        let commanding = matches!(self.mode, Mode::Normal | Mode::Visual(_) | Mode::VisualLine(_));
+        if commanding && !self.keys.is_pending() && self.command_key(key) {
+            return;
+        }
+        let fed = self.keys.feed(self.keymap_context(), key);
+        self.run_keys(fed);
+    }
+
+    /// Take a key that isn't looked up in the keymap: a digit of the count, the char `r` replaces with,
+    /// or the last key of the pending operator again, for whole lines
+    fn command_key(&mut self, key: Key) -> bool {
+        if self.pending.replace {
+            let count = self.pending.take_count();
+            if let Some(ch) = key.as_char() {
+                self.tb.apply(Box::new(ReplaceChars::new(ch, count)));
+            }
+            return true;
+        }
+        if let Some(operator) = self.pending.operator {
+            let keymaps = keymaps();
+            if keymaps.get(self.keymap_context()).keys_of(operator.action()).any(|keys| keys.last() == Some(&key)) {
+                self.run_operator(operator, Motion::Line);
+                return true;
+            }
+        }
+        key.as_char().is_some_and(|ch| self.pending.push_digit(ch))
+    }
+
+    /// Run the action of typed keys, or handle keys bound to nothing by the mode
+    fn run_keys(&mut self, fed: Vec<Feed>) {
+        for fed in fed {
+            match fed {
+                Feed::Action(Action::Named(action)) => self.run_action(action),
+                Feed::Action(Action::Command(command)) => {
+                    if let Some(poster) = self.poster.as_mut() {
+                        poster.post(WindowRequest::Command(command));
+                    }
+                }
+                Feed::Unbound(keys) => {
+                    for key in keys {
+                        self.mode_input(key);
+                    }
+                }
+            }
+        }
+    }
+
+    fn mode_input(&mut self, key: Key) {
+        match self.mode {
+            Mode::Insert => self.insert_mode_input(key.code, key.modifiers),
+            // keys bound to nothing cancel the command typed
+            Mode::Normal | Mode::Visual(_) | Mode::VisualLine(_) => self.pending.clear(),
+            Mode::Replace => self.replace_mode_input(key.code, key.modifiers),
+        }
+    }
+
+    fn run_action(&mut self, action: &str) {
+        if let Some(motion) = Motion::from_action(action) {
+            match self.pending.operator {
+                Some(operator) => self.run_operator(operator, motion),
+                None => {
+                    let count = self.pending.take_count();
+                    self.move_cursor(motion, count);
                 }
-                _ => ()
             }
+            return;
+        }
+        if let Some(operator) = Operator::from_action(action) {
+            self.operator_action(operator);
+            return;
+        }
+
+        let cursor = self.tb.get_cursor();
+        match action {
+            "editor.normal" => {
+                self.replaced.clear();
+                self.pending.clear();
+                self.mode = Mode::Normal;
+            }
+            "editor.insert" => self.mode = Mode::Insert,
+            "editor.replace" => self.mode = Mode::Replace,
+            "editor.visual" => self.mode = match self.mode {
+                Mode::Visual(_) => Mode::Normal,
+                Mode::VisualLine(anchor) => Mode::Visual(anchor),
+                _ => Mode::Visual(cursor),
+            },
+            "editor.visual_line" => self.mode = match self.mode {
+                Mode::VisualLine(_) => Mode::Normal,
+                Mode::Visual(anchor) => Mode::VisualLine(anchor),
+                _ => Mode::VisualLine(cursor),
+            },
+            "editor.increment" => {
+                let count = self.pending.take_count() as i64;
+                self.tb.apply(Box::new(IncrementNumber::new(count)));
+            }
+            "editor.decrement" => {
+                let count = self.pending.take_count() as i64;
+                self.tb.apply(Box::new(IncrementNumber::new(-count)));
+            }
+            "editor.scroll_down" => {
+                let count = self.pending.take_count() as isize;
+                self.scroll_view(count);
+            }
+            "editor.scroll_up" => {
+                let count = self.pending.take_count() as isize;
+                self.scroll_view(-count);
+            }
+            "editor.half_page_down" => self.scroll_with_cursor((self.text_area().row / 2) as isize),
+            "editor.half_page_up" => self.scroll_with_cursor(-((self.text_area().row / 2) as isize)),
+            "editor.page_down" => self.scroll_view(self.text_area().row.saturating_sub(2) as isize),
+            "editor.page_up" => self.scroll_view(-(self.text_area().row.saturating_sub(2) as isize)),
+            "editor.undo" => self.tb.undo(),
+            "editor.indent" => self.shift_current_line(1),
+            "editor.dedent" => self.shift_current_line(-1),
+            "editor.replace_char" => {
+                self.pending.operator = None;
+                self.pending.replace = true;
+                return;
+            }
+            "editor.cursor_center" | "editor.cursor_top" | "editor.cursor_bottom" => self.align_view(action),
+            "editor.toggle_fold" | "editor.open_fold" | "editor.close_fold"
+                | "editor.delete_fold" | "editor.open_folds" | "editor.close_folds" => self.fold_command(action),
+            "editor.toggle_case" | "editor.join" | "editor.join_raw" => {
+                let count = self.pending.count();
+                self.run_command(action, count);
+            }
+            _ => ()
+        }
+        // a command ends the one typed, a count before it was taken or ignored
+        self.pending.clear();
+    }
+
+    /// Operator typed: acts on the selection right away in visual modes,
+    /// otherwise waits for a motion, typing it twice acting on whole lines
+    fn operator_action(&mut self, operator: Operator) {
+        match self.mode {
+            Mode::Visual(anchor) | Mode::VisualLine(anchor) => {
+                let Some(span) = self.selection() else { return };
+                self.pending.clear();
+                let cursor = self.tb.get_cursor();
+                let first = self.tb.line_of(std::cmp::min(anchor, cursor));
+                let last = self.tb.line_of(std::cmp::max(anchor, cursor));
+                self.mode = Mode::Normal;
+                self.apply_operator(operator, (first, last), span);
+            }
+            Mode::Normal if self.pending.operator == Some(operator) => self.run_operator(operator, Motion::Line),
+            Mode::Normal => {
+                self.pending.replace = false;
+                self.pending.operator = Some(operator);
+            }
+            Mode::Insert | Mode::Replace => (),
+        }
+    }
+
+    /// Run the pending operator over the motion, repeated by the count
+    fn run_operator(&mut self, operator: Operator, motion: Motion) {
+        let count = self.pending.take_count();
+        let lines = motion.line_range(&self.tb, count, &self.text_at());
+        let span = motion.span(&self.tb, count, &self.text_at());
+        self.apply_operator(operator, lines, span);
+    }
+
+    fn run_command(&mut self, command: &str, count: usize) {
+        let op: Box<dyn TextBufferOperation> = match command {
+            "editor.toggle_case" => {
+                let cursor = self.tb.get_cursor();
+                let line_end = self.tb.line_end(self.tb.cursor_line()).unwrap_or(cursor);
+                let end = std::cmp::min(cursor + count, line_end);
+                Box::new(ChangeCase::new(cursor, end, Case::Toggle, end))
+            }
+            "editor.join" => Box::new(JoinLines::new(count, true)),
+            "editor.join_raw" => Box::new(JoinLines::new(count, false)),
+            _ => return
+        };
+        self.tb.apply(op);
+    }
+
+    fn move_cursor(&mut self, motion: Motion, count: usize) {
+        let op: Box<dyn TextBufferOperation> = match motion {
+            Motion::Left => Box::new(CursorLeft(count)),
+            Motion::Right => Box::new(CursorRight(count)),
+            Motion::Down => Box::new(LineMovement::down(count).skipping(self.folds.closed_ranges())),
+            Motion::Up => Box::new(LineMovement::up(count).skipping(self.folds.closed_ranges())),
+            Motion::LineEnd => Box::new(EndOfLine::new()),
+            motion => Box::new(CursorTo::new(motion.target(&self.tb, count, &self.text_at()))),
+        };
+        self.tb.apply(op);
+    }
 
-            (KeyCode::Char('J'), _) => {
-                self.scroll += 10;
-                // self.tb.cursor_move_by(Some(10), None);
+    /// Run an operator. Line operators act on lines first..=last, others on the span of text
+    fn apply_operator(&mut self, operator: Operator, (first, last): (usize, usize), (start, end): (usize, usize)) {
+        if operator == Operator::Fold {
+            // other methods create folds on their own
+            if self.settings.foldmethod == FoldMethod::Manual {
+                self.folds.add(first, last);
             }
+            return;
+        }
+
+        let case = match operator {
+            Operator::Lowercase => Some(Case::Lower),
+            Operator::Uppercase => Some(Case::Upper),
+            Operator::ToggleCase => Some(Case::Toggle),
+            _ => None
+        };
+        if let Some(case) = case {
+            self.tb.apply(Box::new(ChangeCase::new(start, end, case, start)));
+            return;
+        }
+
+        let style = self.settings.indent_style();
+        let lines: Vec<String> = (first..=last).filter_map(|l| self.tb.line_string(l)).collect();
 
-            (KeyCode::Char('K'), _) => {
-                if self.scroll >= 10 {
-                    self.scroll -= 10;
-                    // self.tb.cursor_move_by(Some(-10), None);
+        let indents: Vec<Option<String>> = match operator {
+            Operator::Indent => lines.iter().map(|l| shift(l, 1, &style)).collect(),
+            Operator::Dedent => lines.iter().map(|l| shift(l, -1, &style)).collect(),
+            Operator::Reindent => {
+                let prev = (0..first).rev()
+                    .filter_map(|l| self.tb.line_string(l))
+                    .find(|l| !is_blank(l));
+                reindent(prev.as_deref(), &lines, self.filetype, &style).into_iter().map(Some).collect()
+            }
+            Operator::Comment => {
+                if let Some(lines) = toggle_comments(&lines, self.filetype, self.settings.tabstop) {
+                    self.set_lines(first, lines);
                 }
+                return;
+            }
+            _ => return
+        };
+
+        self.set_indents(first, indents);
+    }
+
+    /// Replace content of consecutive lines as one undoable operation.
+    /// Cursor ends on the first non-blank of the first line
+    fn set_lines(&mut self, first: usize, lines: Vec<String>) {
+        let first_start = self.tb.line_start(first).unwrap_or(0);
+        let target = first_start + lines.first().map(|l| leading_whitespace(l).len()).unwrap_or(0);
+
+        let mut ops: Vec<Box<dyn TextBufferOperation>> = Vec::new();
+        for (i, line) in lines.into_iter().enumerate() {
+            if self.tb.line_string(first+i).as_ref() != Some(&line) {
+                ops.push(Box::new(SetLine::new(first+i, line)));
+            }
+        }
+        ops.push(Box::new(CursorTo::new(target)));
+
+        self.tb.apply(Box::new(OperationGroup(ops)));
+    }
+
+    /// Replace indentation of consecutive lines as one undoable operation.
+    /// Cursor ends on the first non-blank of the first line
+    fn set_indents(&mut self, first: usize, indents: Vec<Option<String>>) {
+        let first_start = self.tb.line_start(first).unwrap_or(0);
+        let target = match indents.first() {
+            Some(Some(indent)) => first_start + indent.len(),
+            _ => first_non_blank(&self.tb, first),
+        };
+
+        let mut ops: Vec<Box<dyn TextBufferOperation>> = Vec::new();
+        for (i, indent) in indents.into_iter().enumerate() {
+            if let Some(indent) = indent {
+                ops.push(Box::new(SetIndent::new(first+i, indent)));
             }
+        }
+        ops.push(Box::new(CursorTo::new(target)));
+
+        self.tb.apply(Box::new(OperationGroup(ops)));
+    }
+
+    /// Change indent of the cursor's line by levels, keeping the cursor on the same text
+    fn shift_current_line(&mut self, levels: isize) {
+        let line = self.tb.cursor_line();
+        let text = self.tb.line_string(line).unwrap_or_default();
+        let current = leading_whitespace(&text);
+        let style = self.settings.indent_style();
+
+        let width = display_width(&current, style.tabstop) as isize + levels * style.level() as isize;
+        let indent = style.fill(std::cmp::max(0, width) as usize);
+
+        let start = self.tb.line_start(line).unwrap_or(0);
+        let col = self.tb.cursor_col();
+        let target = if col >= current.len() {
+            start + indent.len() + (col - current.len())
+        } else {
+            start + indent.len()
+        };
+
+        self.tb.apply(Box::new(OperationGroup(vec![
+            Box::new(SetIndent::new(line, indent)),
+            Box::new(CursorTo::new(target)),
+        ])));
+    }
 
+    /// Selected range of logical indices (inclusive..exclusive), if in a visual mode
+    fn selection(&self) -> Option<(usize, usize)> {
+        let cursor = self.tb.get_cursor();
+        match self.mode {
+            Mode::Visual(anchor) => {
+                let end = std::cmp::min(std::cmp::max(anchor, cursor) + 1, self.tb.get_length());
+                Some((std::cmp::min(anchor, cursor), end))
+            }
+            Mode::VisualLine(anchor) => {
+                let first = self.tb.line_of(std::cmp::min(anchor, cursor));
+                let last = self.tb.line_of(std::cmp::max(anchor, cursor));
+                Some((self.tb.line_start(first)?, self.tb.line_end(last)?))
+            }
+            _ => None
+        }
+    }
 
-            (KeyCode::Char('h'), _) => { self.tb.apply(Box::new(CursorLeft(1))); }
-            (KeyCode::Char('j'), _) => { self.tb.apply(Box::new(LineMovement::down(1))); }
-            (KeyCode::Char('k'), _) => { self.tb.apply(Box::new(LineMovement::up(1))); }
-            (KeyCode::Char('l'), _) => { self.tb.apply(Box::new(CursorRight(1))); }
-            (KeyCode::Char('$'), _) => { self.tb.apply(Box::new(EndOfLine::new())); }
-            //
-            // (KeyCode::Char('s'), _) => { self.tb.seek_word(); }
-            // (KeyCode::Char('w'), _) => { self.tb.next_word_space(); }
-
-            // insert mode transitions
-            (KeyCode::Char('i'), _) => self.mode = Mode::Insert,
-            // (KeyCode::Char('I'), _) => {
-            //     self.tb.cursor_start_of_line();
-            //     self.mode = Mode::Insert;
-            // }
-            // (KeyCode::Char('a'), _) => {
-            //     self.tb.cursor_move_by(None, Some(1));
-            //     self.mode = Mode::Insert;
-            // }
-            // (KeyCode::Char('A'), _) => {
-            //     self.tb.cursor_end_of_line();
-            //     self.mode = Mode::Insert;
-            // }
-            // (KeyCode::Char('o'), _) => {
-            //     self.tb.cursor_end_of_line();
-            //     self.tb.insert("\n");
-            //     self.mode = Mode::Insert;
-            // }
+    fn fold_command(&mut self, command: &str) {
+        let line = self.tb.cursor_line();
+        match command {
+            "editor.toggle_fold" => self.folds.toggle(line),
+            "editor.open_fold" => self.folds.open(line),
+            "editor.close_fold" => self.folds.close(line),
+            "editor.delete_fold" => self.folds.remove(line),
+            "editor.open_folds" => self.folds.open_all(),
+            "editor.close_folds" => self.folds.close_all(),
             _ => ()
         }
     }
 
+    /// Recreate folds for the current fold method. Manual folds are kept as is
+    fn compute_folds(&mut self) {
+        let tabstop = self.settings.tabstop;
+        let tb = &self.tb;
+        self.folds.recompute(self.settings.foldmethod, tb.line_count(), &|line| LineKey::new(&tb.line_string(line).unwrap_or_default(), tabstop));
+    }
+
+    /// Style the parts of spans shown in a segment drawn on screen row.
+    /// cols holds the display column of each char of the line
+    fn draw_spans(&self, canvas: &mut Canvas, row: usize, spans: &[Span], cols: &[usize], segment: &Segment, view: &Viewport) {
+        let area = self.text_area();
+        let left = segment.col + view.col;
+        let right = segment.col + std::cmp::min(segment.text.chars().count(), view.col + area.col);
+        // screen column of the segment's first display column
+        let offset = self.gutter_width() + segment.prefix.chars().count();
+
+        for span in spans {
+            let start = std::cmp::max(cols[span.start], left);
+            let end = std::cmp::min(cols[span.end], right);
+            if start >= end { continue; }
+
+            for attribute in span.scope.style() {
+                let _ = canvas.set_attribute(
+                    attribute,
+                    Plot::new(row, start - left + offset),
+                    Plot::new(row, end - left + offset),
+                );
+            }
+        }
+    }
+
+    /// Text shown in place of a closed fold
+    fn fold_summary(&self, fold: Fold, width: usize) -> String {
+        let header = self.tb.line_string(fold.start).unwrap_or_default();
+        let header = expand_tabs(header.trim(), self.settings.tabstop);
+        let summary = format!("+--{:>3} lines: {} ", fold.end - fold.start + 1, header);
+        format!("{:-<w$}", summary, w = width)
+    }
+
+    /// Size of the text area in the last draw, without gutter and header
+    fn text_area(&self) -> Plot {
+        let dim = self.dim.get();
+        Plot::new(dim.row.saturating_sub(1), dim.col.saturating_sub(self.gutter_width()))
+    }
+
+    fn gutter_width(&self) -> usize {
+        self.gutter.width(self.tb.line_count(), &self.settings.gutter_options())
+    }
+
+    /// Screen rows taken up by a line
+    fn line_rows(&self, line: usize, width: usize) -> usize {
+        if let Some(fold) = self.folds.closed_at(line) {
+            return if fold.start == line { 1 } else { 0 };
+        }
+        let text = self.tb.line_string(line).unwrap_or_default();
+        layout_line(&text, width, &self.settings.display_options()).len()
+    }
+
+    /// Line and wrapped row of a logical index, and its column in that row
+    fn position_of(&self, index: usize, width: usize) -> ((usize, usize), usize) {
+        let options = self.settings.display_options();
+        let line = self.tb.line_of(index);
+        if let Some(fold) = self.folds.closed_at(line) {
+            return ((fold.start, 0), 0);
+        }
+        let text = self.tb.line_string(line).unwrap_or_default();
+        let start = self.tb.line_start(line).unwrap_or(0);
+
+        let before: String = text.chars().take(index.saturating_sub(start)).collect();
+        let segments = layout_line(&text, width, &options);
+        let (row, col) = locate_in_line(&segments, display_width(&before, options.tabstop));
+        ((line, row), col)
+    }
+
+    /// View scrolled so the cursor is visible
+    fn followed_view(&self) -> Viewport {
+        let area = self.text_area();
+        let rows = |line| self.line_rows(line, area.col);
+        let (cursor, col) = self.position_of(self.tb.get_cursor(), area.col);
+
+        let mut view = self.view;
+        // top may have been folded away
+        if let Some(fold) = self.folds.closed_at(view.line) {
+            view.line = fold.start;
+            view.row = 0;
+        }
+        view.follow(cursor, area.row, self.settings.scrolloff, &rows);
+        if self.settings.wrap {
+            view.col = 0;
+        } else {
+            view.follow_col(col, area.col, self.settings.sidescrolloff);
+        }
+        view
+    }
+
+    /// Scroll the view by n rows, moving the cursor if it would leave the view
+    fn scroll_view(&mut self, n: isize) {
+        let area = self.text_area();
+        let rows = |line| self.line_rows(line, area.col);
+
+        let mut view = self.view;
+        view.scroll(n, self.tb.line_count(), &rows);
+        self.view = view;
+        self.keep_cursor_in_view();
+    }
+
+    /// Move the cursor and the view by n lines
+    fn scroll_with_cursor(&mut self, n: isize) {
+        self.move_lines(n);
+        self.scroll_view(n);
+    }
+
+    /// Move the cursor to the closest line inside the scrolloff margins
+    fn keep_cursor_in_view(&mut self) {
+        let area = self.text_area();
+        if area.row == 0 { return; }
+
+        let rows = |line| self.line_rows(line, area.col);
+        let line_count = self.tb.line_count();
+        let margin = std::cmp::min(self.settings.scrolloff, (area.row - 1) / 2);
+        // no margin needed above the first line
+        let top_margin = if self.view.line == 0 && self.view.row == 0 { 0 } else { margin };
+
+        let (cursor, _) = self.position_of(self.tb.get_cursor(), area.col);
+        let target = match self.view.distance(cursor, area.row, &rows) {
+            Some(d) if d >= top_margin && d < area.row - margin => return,
+            Some(d) if d >= top_margin => self.view.position_at(area.row - 1 - margin, line_count, &rows).0,
+            _ => {
+                // first line starting at or below the margin
+                let (line, row) = self.view.position_at(top_margin, line_count, &rows);
+                if row > 0 { std::cmp::min(line + 1, line_count.saturating_sub(1)) } else { line }
+            }
+        };
+
+        self.move_lines(target as isize - cursor.0 as isize);
+    }
+
+    /// Move the cursor n lines down, or up if negative, stopping at the ends of the buffer
+    fn move_lines(&mut self, n: isize) {
+        let line = self.tb.cursor_line();
+        let last = self.tb.line_count().saturating_sub(1);
+        let count = std::cmp::min(n.unsigned_abs(), if n > 0 { last - line } else { line });
+        if count == 0 { return; }
+
+        if n > 0 {
+            self.tb.apply(Box::new(LineMovement::down(count)));
+        } else {
+            self.tb.apply(Box::new(LineMovement::up(count)));
+        }
+    }
+
+    /// Scroll so the cursor is at the top, middle or bottom of the view
+    fn align_view(&mut self, command: &str) {
+        let area = self.text_area();
+        if area.row == 0 { return; }
+
+        let margin = std::cmp::min(self.settings.scrolloff, (area.row - 1) / 2);
+        let n = match command {
+            "editor.cursor_top" => margin,
+            "editor.cursor_bottom" => area.row - 1 - margin,
+            _ => (area.row - 1) / 2,
+        };
+
+        let rows = |line| self.line_rows(line, area.col);
+        let (cursor, _) = self.position_of(self.tb.get_cursor(), area.col);
+        let mut view = self.view;
+        view.place(cursor, n, &rows);
+        self.view = view;
+    }
+
+    /// Current settings with the options of the file type, then this file's overrides
+    fn layered_settings(&self) -> Settings {
+        let mut settings = (*current_settings()).clone();
+        for option in self.filetype.options {
+            let _ = settings.apply_set(option);
+        }
+        settings.apply_table(&self.overrides);
+        settings
+    }
+
+    /// Take settings from the current ones and this file's overrides
+    fn reconfigure(&mut self) {
+        let (foldmethod, tabstop) = (self.settings.foldmethod, self.settings.tabstop);
+        self.settings = TextWindowSettings::from(&self.layered_settings());
+        if self.settings.foldmethod != foldmethod || self.settings.tabstop != tabstop {
+            self.compute_folds();
+        }
+    }
+
+    /// Apply the arguments of `:setfile`, and save the options named as overrides for this file
+    fn save_file_options(&mut self, args: &[String]) {
+        let Some(path) = self.path.clone() else {
+            self.alert("No file to save settings for".into());
+            return;
+        };
+        let mut settings = self.layered_settings();
+        let mut errors = Vec::new();
+
+        for arg in args {
+            let saved = apply_saved_set(&mut settings, arg)
+                .and_then(|(info, value)| save_file_option(&path, info, value.as_ref()).map(|_| (info, value)));
+            match saved {
+                Ok((info, value)) => {
+                    for alias in info.aliases {
+                        self.overrides.remove(*alias);
+                    }
+                    match value {
+                        Some(value) => { self.overrides.insert(info.name.to_string(), value.to_config()); }
+                        None => { self.overrides.remove(info.name); }
+                    }
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+
+        self.reconfigure();
+        if !errors.is_empty() {
+            self.alert(errors.join("  "));
+        }
+    }
+
+    /// Show message in a popup, once the window has a poster
+    fn alert(&mut self, message: String) {
+        match self.poster.as_mut() {
+            Some(poster) => poster.post(WindowRequest::AddPopup(Some(Box::new(Alert {
+                content: StyledText::new(message),
+                options: vec![(StyledText::new("Ok".into()), vec![])],
+                ..Default::default()
+            })))),
+            None => self.alerts.push(message),
+        }
+    }
+
+    /// Run a command of the prompt. Commands with a range act on the cursor's line without one
+    fn prompt_command(&mut self, command: ParsedCommand) {
+        let line = self.tb.cursor_line();
+        let (first, last) = command.range.map_or((line, line), |range| range.resolve(line, self.tb.line_count()));
+        match command.name() {
+            "write" => { self.write(command.path()); }
+            "wq" => {
+                if self.write(command.path()) {
+                    self.poster.as_mut().unwrap().post(WindowRequest::RemoveSelfWindow);
+                }
+            }
+            "quit" if command.bang => self.poster.as_mut().unwrap().post(WindowRequest::RemoveSelfWindow),
+            "quit" => self.try_quit(),
+            "setfile" => self.save_file_options(command.options()),
+            "tl" => self.settings.line_numbers = !self.settings.line_numbers,
+            "" => self.tb.apply(Box::new(CursorTo::new(first_non_blank(&self.tb, last)))),
+            name => {
+                let operator = match name {
+                    ">" => Operator::Indent,
+                    "<" => Operator::Dedent,
+                    "fold" => Operator::Fold,
+                    _ => return
+                };
+                let start = self.tb.line_start(first).unwrap_or(0);
+                let end = self.tb.line_end(last).unwrap_or(start);
+                self.apply_operator(operator, (first, last), (start, end));
+            }
+        }
+    }
+
+    /// Save the buffer to path, or to its own file. A buffer without a file takes path as its own
+    fn write(&mut self, path: Option<&Path>) -> bool {
+        let Some(path) = path.map(Path::to_path_buf).or_else(|| self.path.clone()) else {
+            self.alert("No file name, write with :w path".into());
+            return false;
+        };
+        if let Err(e) = std::fs::write(&path, self.tb.string()) {
+            self.alert(format!("Can't write {}: {}", path.display(), e));
+            return false;
+        }
+        if self.path.is_none() {
+            self.set_path(path);
+        }
+        true
+    }
+
     fn try_quit(&mut self) {
         // if self.tb.saved {
             self.poster.as_mut().unwrap().post(WindowRequest::RemoveSelfWindow);
@@ -182,96 +1082,144 @@ impl Window for TextWindow {
     }
     fn input_bypass(&self) -> bool {
         match self.mode {
-            Mode::Normal => false,
+            // finish typed sequence before super windows see keys
+            Mode::Normal => !self.pending.is_empty() || self.keys.is_pending(),
             Mode::Insert => true,
+            Mode::Visual(_) | Mode::VisualLine(_) => true,
+            Mode::Replace => true,
         }
     }
+    fn key_hints(&self) -> Option<KeyHints> {
+        let context = self.keymap_context();
+        let keymaps = keymaps();
+        let keymap = keymaps.get(context);
+        let action = match (self.pending.operator, self.pending.replace) {
+            (Some(operator), _) => operator.action(),
+            (None, true) => "editor.replace_char",
+            (None, false) => return self.keys.hints(context),
+        };
+        let command_keys = keymap.keys_of(action).next().unwrap_or_default();
+        let next = if self.pending.replace {
+            vec![("{char}".to_string(), "Replace with the char".to_string())]
+        } else {
+            // motions, or the operator's last key again
+            let mut next: Vec<(String, String)> = keymap.continuations(self.keys.pending()).into_iter()
+                .filter(|(_, action)| matches!(action, Action::Named(name) if Motion::from_action(name).is_some()))
+                .map(|(keys, action)| (keys_to_string(keys), describe(context, action)))
+                .collect();
+            let started = self.keys.is_pending() || !self.pending.typed_motion_count().is_empty();
+            if let (false, Some(last)) = (started, command_keys.last()) {
+                next.insert(0, (keys_to_string(&[*last]), "Whole lines".to_string()));
+            }
+            next
+        };
+        let typed = format!("{}{}{}{}", self.pending.typed_count(), keys_to_string(command_keys),
+            self.pending.typed_motion_count(), keys_to_string(self.keys.pending()));
+        Some(KeyHints { typed, next })
+    }
+    fn commands(&self) -> Vec<&'static CommandSpec> {
+        COMMANDS.iter().collect()
+    }
+    fn buffers(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
     fn event(&mut self, event: WindowEvent) {
         match event {
             WindowEvent::Focus => self.focused = true,
             WindowEvent::Unfocus => self.focused = false,
 
-            WindowEvent::Input {key, modifiers} => {
-                // global controls
-                match (key, modifiers) {
-                    _ => ()
-                }
-
-                // edit mode controls
-                match self.mode {
-                    Mode::Insert => self.insert_mode_input(key, modifiers),
-                    Mode::Normal => self.normal_mode_input(key, modifiers),
-                }
-            }
-            WindowEvent::Command(cmd) => {
-                if cmd == "w" {
-                    // self.tb.save();
-                }
-                if cmd == "wq" {
-                    // self.tb.save();
-                    self.poster.as_mut().unwrap().post(WindowRequest::RemoveSelfWindow);
-                }
-                if cmd == "q!" {
-                    self.poster.as_mut().unwrap().post(WindowRequest::RemoveSelfWindow);
-                }
-                if cmd == "q" {
-                    self.try_quit();
-                }
-                if cmd == "tl" {
-                    self.settings.line_numbers = !self.settings.line_numbers;
-                }
-            }
+            WindowEvent::Input {key, modifiers} => self.key_input(Key::new(key, modifiers)),
+            WindowEvent::Command(command) => self.prompt_command(command),
             WindowEvent::TryQuit => {
                 self.try_quit();
             }
+            WindowEvent::SettingsChanged => self.reconfigure(),
 
             _ => ()
         }
 
+        let changes = self.tb.take_changes();
+        if !changes.is_empty() {
+            if let Some(highlighter) = self.highlighter.as_mut() {
+                highlighter.get_mut().apply_changes(&changes);
+            }
+            let tabstop = self.settings.tabstop;
+            let tb = &self.tb;
+            self.folds.update(self.settings.foldmethod, &changes, tb.line_count(), &|line| LineKey::new(&tb.line_string(line).unwrap_or_default(), tabstop));
+        }
+
+        self.view = self.followed_view();
+
         if let Some(poster) = self.poster.as_mut() {
             poster.post(WindowRequest::Redraw);
         }
     }
 
     fn draw(&self, canvas: &mut Canvas) {
-        // write text and line number
-        canvas.move_to(Plot::new(0,0));
-        // let text = self.tb.wrap_display(self.scroll, canvas.get_dim().col - 3);
-        let (text, mut cursor) = wrap_content(self.tb.string(), *canvas.get_dim(), self.tb.get_cursor());
+        self.dim.set(*canvas.get_dim());
+        let gutter = self.gutter_width();
+        let area = self.text_area();
+        let options = self.settings.display_options();
+        let gutter_options = self.settings.gutter_options();
+        let cursor_line = self.tb.cursor_line();
+        let view = self.followed_view();
 
-        // which lines are shown
+        // highlight up to the last line shown, which may be done in the background
+        if let Some(highlighter) = &self.highlighter {
+            let rows = |line| self.line_rows(line, area.col);
+            let (last, _) = view.position_at(area.row.saturating_sub(1), self.tb.line_count(), &rows);
+            let last = self.folds.closed_at(last).map_or(last, |fold| fold.end);
+            highlighter.borrow_mut().prepare(last, self.tb.line_count(), &|line| self.tb.line_string(line).unwrap_or_default());
+        }
 
-        let mut real_n = 0;
+        // line and segment shown on each screen row
+        let mut shown = Vec::new();
+        let mut line = view.line;
+        let mut skip = view.row;
+        while shown.len() < area.row && line < self.tb.line_count() {
+            // closed fold shown as a single row
+            if let Some(fold) = self.folds.closed_at(line) {
+                let _ = canvas.move_to(Plot::new(shown.len(), 0));
+                for text in self.gutter.render(Some(fold.start), cursor_line, self.tb.line_count(), &gutter_options) {
+                    canvas.write(&text);
+                }
+                let summary = StyledText::new(clip(&self.fold_summary(fold, area.col), 0, area.col, &options))
+                    .with(StyleAttribute::Color(self.settings.fold_color));
+                canvas.write(&summary);
 
-        let lines = text.iter().enumerate();
-        for (n, line) in lines {
-            // line number
-            if self.settings.line_numbers {
-                let line_number = StyledText::new(format!("{:<3}", self.scroll+n+1))
-                    .with(StyleAttribute::Color(self.settings.line_number_color));
-                canvas.write(&line_number);
+                shown.push((fold.start, 0));
+                line = fold.end + 1;
+                skip = 0;
+                continue;
             }
 
-            // text
-            for sub_line in line.iter() {
-                let content = StyledText::new(sub_line.clone());
-                canvas.write(&content);
-                canvas.to_next_line();
+            let text = self.tb.line_string(line).unwrap_or_default();
+            let highlighter = self.highlighter.as_ref().map(|h| h.borrow());
+            let spans = highlighter.as_ref().map(|h| h.spans(line)).unwrap_or_default();
+            let cols = char_columns(&text, options.tabstop);
+            for (i, segment) in layout_line(&text, area.col, &options).into_iter().enumerate().skip(skip) {
+                if shown.len() >= area.row { break; }
+                let _ = canvas.move_to(Plot::new(shown.len(), 0));
 
+                // signs and line number, only on a line's first row
+                let first_row = if i == 0 { Some(line) } else { None };
+                for text in self.gutter.render(first_row, cursor_line, self.tb.line_count(), &gutter_options) {
+                    canvas.write(&text);
+                }
 
-                real_n += 1;
-            }
-            if line.len() == 0 {
-                canvas.to_next_line();
-                real_n += 1;
+                if !segment.prefix.is_empty() {
+                    let prefix = StyledText::new(segment.prefix.clone())
+                        .with(StyleAttribute::Color(self.settings.line_number_color));
+                    canvas.write(&prefix);
+                }
+                canvas.write(&StyledText::new(clip(&segment.text, view.col, area.col, &options)));
+                self.draw_spans(canvas, shown.len(), spans, &cols, &segment, &view);
+                shown.push((line, i));
             }
-
-
-            if real_n > canvas.get_dim().row-2 { break; }
+            skip = 0;
+            line += 1;
         }
 
-
-
         // get header content
         let mode_text;
         let mode_header_color;
@@ -284,6 +1232,18 @@ impl Window for TextWindow {
                 mode_text = "INSERT";
                 mode_header_color = self.settings.insert_color;
             }
+            Mode::Visual(_) => {
+                mode_text = "VISUAL";
+                mode_header_color = self.settings.visual_color;
+            }
+            Mode::VisualLine(_) => {
+                mode_text = "V-LINE";
+                mode_header_color = self.settings.visual_color;
+            }
+            Mode::Replace => {
+                mode_text = "REPLACE";
+                mode_header_color = self.settings.replace_color;
+            }
         }
 
         // create styled text
@@ -312,12 +1272,48 @@ impl Window for TextWindow {
 
 
 
-        // write cursor
-        if self.focused {
-            if self.settings.line_numbers {
-                cursor.col += 3;
+        // screen position of an index, clamped to the end of the view when below or beside it
+        let locate = |index: usize| {
+            let (pos, col) = self.position_of(index, area.col);
+            let col = std::cmp::min(col.saturating_sub(view.col), area.col);
+            match shown.iter().position(|p| *p == pos) {
+                Some(n) => Some(Plot::new(n, col + gutter)),
+                None if shown.first().is_some_and(|first| pos < *first) => None,
+                None => Some(Plot::new(shown.len(), 0)),
+            }
+        };
+        let visible = |index: usize| {
+            let (pos, col) = self.position_of(index, area.col);
+            if col < view.col || col >= view.col + area.col { return None; }
+            shown.iter().position(|p| *p == pos).map(|n| Plot::new(n, col - view.col + gutter))
+        };
+
+        // highlight selection
+        if let Some((start, end)) = self.selection() {
+            let start = locate(start).unwrap_or(Plot::new(0, 0));
+            if let Some(end) = locate(end) {
+                let _ = canvas.set_attribute(
+                    StyleAttribute::BgColor(self.settings.selection_color),
+                    start,
+                    end,
+                );
+            }
+        }
+
+        // highlight bracket matching the one under cursor, searching about as far as the view reaches
+        if self.focused && self.settings.show_match {
+            let lines = area.row + SYNC_LINES;
+            if let Some(plot) = matching_bracket(&self.tb, self.tb.get_cursor(), &self.text_at(), lines).and_then(visible) {
+                let _ = canvas.set_attribute(
+                    StyleAttribute::BgColor(self.settings.match_color),
+                    plot,
+                    plot + Plot::new(0, 1)
+                );
             }
+        }
 
+        // write cursor
+        if let Some(cursor) = visible(self.tb.get_cursor()).filter(|_| self.focused) {
             let _ = canvas.set_attribute(
                 StyleAttribute::BgColor(
                     if self.settings.dynamic_caret_color { mode_header_color } else { ThemeColor::Gray },
@@ -328,7 +1324,233 @@ impl Window for TextWindow {
         }
     }
 
+    fn collect_requests(&mut self) -> Vec<WindowRequest> {
+        let fed = self.keys.timed_out(self.keymap_context());
+        if !fed.is_empty() {
+            // handled like input, to apply changes and redraw
+            self.run_keys(fed);
+            self.event(WindowEvent::None);
+        }
+
+        // redraw once background highlighting is done
+        let highlighted = self.highlighter.as_mut().is_some_and(|h| h.get_mut().receive());
+        if highlighted {
+            if let Some(poster) = self.poster.as_mut() {
+                poster.post(WindowRequest::Redraw);
+            }
+        }
+        Vec::new()
+    }
     fn init(&mut self, poster: EventPoster<WindowRequest, Uuid>) {
-        self.poster = Some(poster)
+        self.poster = Some(poster);
+        for message in std::mem::take(&mut self.alerts) {
+            self.alert(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use crate::event::EventReceiver;
+
+    fn window(text: &str) -> TextWindow {
+        let mut tb = TextBuffer::new();
+        tb.apply(Box::new(InsertString::new(text.to_string())));
+        TextWindow::new(tb)
+    }
+
+    fn type_keys(tw: &mut TextWindow, keys: &str) {
+        for ch in keys.chars() {
+            tw.event(Key::char(ch).into());
+        }
+    }
+
+    #[test]
+    fn runs_motions_and_operators_of_the_keymap() {
+        let mut tw = window("a\nb\nc");
+        type_keys(&mut tw, "gg2>>");
+        assert_eq!(tw.tb.string(), "    a\n    b\nc");
+        type_keys(&mut tw, "G<k");
+        assert_eq!(tw.tb.string(), "    a\nb\nc");
+        type_keys(&mut tw, "g~~");
+        assert_eq!(tw.tb.string(), "    a\nB\nc");
+        type_keys(&mut tw, "uu");
+        assert_eq!(tw.tb.string(), "    a\n    b\nc");
+
+        // the operator waits for a motion or its last key again
+        type_keys(&mut tw, "3gu");
+        let hints = tw.key_hints().unwrap();
+        assert_eq!((hints.typed.as_str(), &hints.next[0]), ("3gu", &("u".to_string(), "Whole lines".to_string())));
+        assert!(hints.next.iter().any(|(keys, _)| keys == "gg"));
+        type_keys(&mut tw, "q");
+        assert!(tw.pending.is_empty());
+    }
+
+    #[test]
+    fn counts_before_and_after_operators() {
+        let mut tw = window("a\nb\nc\nd\ne\nf");
+        type_keys(&mut tw, "gg>2j");
+        assert_eq!(tw.tb.string(), "    a\n    b\n    c\nd\ne\nf");
+        type_keys(&mut tw, "2<2j");
+        assert_eq!(tw.tb.string(), "a\nb\nc\nd\ne\nf");
+        type_keys(&mut tw, "2gU2j");
+        assert_eq!(tw.tb.string(), "A\nB\nC\nD\nE\nf");
+        type_keys(&mut tw, "G2>2k");
+        assert_eq!(tw.tb.string(), "A\n    B\n    C\n    D\n    E\n    f");
+    }
+
+    #[test]
+    fn types_brackets_in_insert_mode() {
+        let mut tw = window("");
+        type_keys(&mut tw, "ia[");
+        assert!(matches!(tw.mode, Mode::Insert));
+        assert!(tw.tb.string().starts_with("a["));
+    }
+
+    fn backspace(tw: &mut TextWindow, times: usize) {
+        for _ in 0..times {
+            tw.event(Key::new(KeyCode::Backspace, KeyModifiers::NONE).into());
+        }
+    }
+
+    #[test]
+    fn replace_restores_on_backspace() {
+        let mut tw = window("abc");
+        type_keys(&mut tw, "0Rxy");
+        assert_eq!(tw.tb.string(), "xyc");
+        assert_eq!(tw.replaced, [Some('a'), Some('b')]);
+        backspace(&mut tw, 1);
+        assert_eq!((tw.tb.string().as_str(), tw.tb.get_cursor()), ("xbc", 1));
+        backspace(&mut tw, 1);
+        assert_eq!((tw.tb.string().as_str(), tw.tb.get_cursor()), ("abc", 0));
+        assert!(tw.replaced.is_empty());
+    }
+
+    #[test]
+    fn replace_past_line_end() {
+        let mut tw = window("ab\ncd");
+        type_keys(&mut tw, "ggRwxyz");
+        assert_eq!(tw.tb.string(), "wxyz\ncd");
+        assert_eq!(tw.replaced, [Some('a'), Some('b'), None, None]);
+        // chars added past the end are deleted, the replaced ones restored
+        backspace(&mut tw, 3);
+        assert_eq!(tw.tb.string(), "wb\ncd");
+        backspace(&mut tw, 1);
+        assert_eq!(tw.tb.string(), "ab\ncd");
+    }
+
+    #[test]
+    fn replace_backspace_stops_at_start() {
+        let mut tw = window("");
+        type_keys(&mut tw, "i12");
+        tw.event(Key::new(KeyCode::Esc, KeyModifiers::NONE).into());
+        type_keys(&mut tw, "0Rx");
+        assert_eq!(tw.tb.string(), "x2");
+
+        // text typed before replace mode is only moved over
+        backspace(&mut tw, 2);
+        assert_eq!((tw.tb.string().as_str(), tw.tb.get_cursor()), ("12", 0));
+
+        // leaving replace mode starts over
+        type_keys(&mut tw, "ab");
+        tw.event(Key::new(KeyCode::Esc, KeyModifiers::NONE).into());
+        assert!(tw.replaced.is_empty());
+        type_keys(&mut tw, "R");
+        backspace(&mut tw, 1);
+        assert_eq!(tw.tb.string(), "ab");
+    }
+
+    #[test]
+    fn pairs_only_in_code() {
+        let mut tw = window("");
+        tw.set_path(PathBuf::from("pairs.rs"));
+        type_keys(&mut tw, "if(\"(\"");
+        assert_eq!(tw.tb.string(), "f(\"(\")");
+
+        // only one half of a pair in the string is deleted
+        tw.tb.apply(Box::new(CursorLeft(1)));
+        type_keys(&mut tw, ")");
+        tw.tb.apply(Box::new(CursorLeft(1)));
+        tw.event(Key::new(KeyCode::Backspace, KeyModifiers::NONE).into());
+        assert_eq!(tw.tb.string(), "f(\")\")");
+
+        let mut tw = window("");
+        tw.set_path(PathBuf::from("pairs.rs"));
+        type_keys(&mut tw, "i// don't (");
+        assert_eq!(tw.tb.string(), "// don't (");
+    }
+
+    #[test]
+    fn unpairs_empty_strings() {
+        let mut tw = window("");
+        tw.set_path(PathBuf::from("quotes.py"));
+        type_keys(&mut tw, "ix = \"");
+        assert_eq!(tw.tb.string(), "x = \"\"");
+        backspace(&mut tw, 1);
+        assert_eq!(tw.tb.string(), "x = ");
+
+        type_keys(&mut tw, "'");
+        assert_eq!(tw.tb.string(), "x = ''");
+        backspace(&mut tw, 1);
+        assert_eq!(tw.tb.string(), "x = ");
+    }
+
+    #[test]
+    fn matches_brackets_outside_strings() {
+        let mut tw = window("f(\")\")");
+        tw.set_path(PathBuf::from("brackets.rs"));
+        type_keys(&mut tw, "0%");
+        assert_eq!(tw.tb.get_cursor(), 5);
+        type_keys(&mut tw, "%");
+        assert_eq!(tw.tb.get_cursor(), 1);
+    }
+
+    #[test]
+    fn alerts_once_initialized() {
+        let mut receiver = EventReceiver::new();
+        let mut tw = window("");
+        tw.alert("settings file broken".into());
+        assert!(receiver.poll().is_empty());
+        tw.init(receiver.new_poster());
+        assert!(matches!(receiver.poll().as_slice(), [(_, WindowRequest::AddPopup(Some(_)))]));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn makefiles_indent_with_tabs() {
+        let mut tw = window("");
+        tw.set_path(PathBuf::from("Makefile"));
+        type_keys(&mut tw, "iall:");
+        tw.event(Key::new(KeyCode::Enter, KeyModifiers::NONE).into());
+        assert_eq!(tw.tb.string(), "all:\n\t");
+    }
+
+    #[test]
+    fn html_indents_after_open_tags() {
+        for (line, indent) in [("<div>", "    "), ("</div>", ""), ("<br>", ""), ("<p>x</p>", "")] {
+            let mut tw = window("");
+            tw.set_path(PathBuf::from("page.html"));
+            type_keys(&mut tw, &format!("i{line}"));
+            tw.event(Key::new(KeyCode::Enter, KeyModifiers::NONE).into());
+            assert_eq!(tw.tb.string(), format!("{line}\n{indent}"));
+        }
+    }
+
+    #[test]
+    fn redraws_once_highlighted_in_background() {
+        let mut receiver = EventReceiver::new();
+        let mut tw = window(&"let x = 1;\n".repeat(SYNC_LINES * 2));
+        tw.set_path(PathBuf::from("long.rs"));
+        tw.init(receiver.new_poster());
+        tw.draw(&mut Canvas::new(Plot::new(SYNC_LINES * 2, 40)));
+
+        // no input comes in, collecting once the worker wakes the main loop is enough
+        let start = Instant::now();
+        while !receiver.poll().iter().any(|(_, request)| matches!(request, WindowRequest::Redraw)) {
+            assert!(start.elapsed() < Duration::from_secs(5), "no redraw");
+            tw.collect_requests();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
@@ -3,6 +3,56 @@ use crate::textedit::fixed_char;
 use crate::textedit::operation::{CursorLeft, CursorRight, TBOperationError, TextBufferOperation};
 use crate::textedit::operation::TBOperationError::MovesOutOfBounds;
 
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Find the bracket matching the one at logical index, accounting for nesting.
+/// Only brackets on the same side of in_text as the one at index count, so ones in strings or comments are passed over.
+/// The search stops lines away from the bracket's line
+pub fn matching_bracket(buffer: &TextBuffer, index: usize, in_text: &dyn Fn(usize) -> bool, lines: usize) -> Option<usize> {
+    let ch = buffer.char_at(index)?;
+    let counts = |i: usize| in_text(i) == in_text(index);
+    let line = buffer.line_of(index);
+    let first = buffer.line_start(line.saturating_sub(lines)).unwrap_or(0);
+    let last = std::cmp::min(line.saturating_add(lines), buffer.line_count().saturating_sub(1));
+    let end = buffer.line_end(last).unwrap_or(buffer.get_length());
+
+    if let Some((open, close)) = BRACKETS.iter().find(|(open, _)| *open == ch) {
+        let mut depth = 0;
+        for i in (index+1)..end {
+            match buffer.char_at(i) {
+                Some(c) if c == *open && counts(i) => depth += 1,
+                Some(c) if c == *close && counts(i) => {
+                    if depth == 0 { return Some(i); }
+                    depth -= 1;
+                }
+                _ => ()
+            }
+        }
+    } else if let Some((open, close)) = BRACKETS.iter().find(|(_, close)| *close == ch) {
+        let mut depth = 0;
+        for i in (first..index).rev() {
+            match buffer.char_at(i) {
+                Some(c) if c == *close && counts(i) => depth += 1,
+                Some(c) if c == *open && counts(i) => {
+                    if depth == 0 { return Some(i); }
+                    depth -= 1;
+                }
+                _ => ()
+            }
+        }
+    }
+
+    None
+}
+
+/// First bracket at or after index on the same line
+pub fn next_bracket_on_line(buffer: &TextBuffer, index: usize) -> Option<usize> {
+    let end = buffer.line_end(buffer.line_of(index))?;
+    (index..end).find(|i| {
+        buffer.char_at(*i).is_some_and(|c| BRACKETS.iter().any(|(o, cl)| c == *o || c == *cl))
+    })
+}
+
 pub fn current_line(cursor: usize, buffer: &mut TextBuffer) -> usize {
     let ordered = buffer.get_new_lines();
     if ordered.len() == 1 { return 0; }
@@ -17,19 +67,26 @@ pub fn current_line(cursor: usize, buffer: &mut TextBuffer) -> usize {
 pub struct LineMovement {
     count: usize,
     op: Option<Box<dyn TextBufferOperation>>,
-    down: bool
+    down: bool,
+    /// Line ranges moved over as a single line
+    folds: Vec<(usize, usize)>,
 }
 impl LineMovement {
     pub fn down(count: usize) -> Self {
         Self {
-            count, op: None, down: true
+            count, op: None, down: true, folds: Vec::new()
         }
     }
     pub fn up(count: usize) -> Self {
         Self {
-            count, op: None, down: false
+            count, op: None, down: false, folds: Vec::new()
         }
     }
+    /// Count each (start, end) range of lines as one line, landing on its start
+    pub fn skipping(mut self, folds: Vec<(usize, usize)>) -> Self {
+        self.folds = folds;
+        self
+    }
 }
 
 impl TextBufferOperation for LineMovement {
@@ -44,12 +101,20 @@ impl TextBufferOperation for LineMovement {
         let current_line_start = ordered[current_line];
 
 
-        let target_line = if self.down {
-            current_line + self.count
-        } else {
-            if current_line == 0 { return Err(MovesOutOfBounds); }
-            current_line - self.count
-        };
+        let fold_of = |line: usize| self.folds.iter().find(|(start, end)| *start <= line && line <= *end).copied();
+        let mut target_line = current_line;
+        for _ in 0..self.count {
+            if self.down {
+                target_line = fold_of(target_line).map_or(target_line, |(_, end)| end) + 1;
+            } else {
+                let start = fold_of(target_line).map_or(target_line, |(start, _)| start);
+                if start == 0 { return Err(MovesOutOfBounds); }
+                target_line = start - 1;
+            }
+        }
+        if let Some((start, _)) = fold_of(target_line) {
+            target_line = start;
+        }
 
 
         if let Some(target_line_start) = ordered.get(target_line) {
@@ -129,4 +194,95 @@ impl TextBufferOperation for EndOfLine {
         if self.0.is_none() { return Err(TBOperationError::LogicError(None)); }
         self.0.as_mut().unwrap().undo(buffer)
     }
-}
\ No newline at end of file
+}
+
+
+/// Move cursor to a logical index
+pub struct CursorTo {
+    target: usize,
+    op: Option<Box<dyn TextBufferOperation>>
+}
+impl CursorTo {
+    pub fn new(target: usize) -> Self { Self { target, op: None } }
+}
+
+impl TextBufferOperation for CursorTo {
+    fn modifies(&self) -> bool {
+        false
+    }
+    fn apply(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        let cursor = buffer.get_cursor();
+        let op: Box<dyn TextBufferOperation> = if self.target >= cursor {
+            Box::new(CursorRight(self.target - cursor))
+        } else {
+            Box::new(CursorLeft(cursor - self.target))
+        };
+        self.op = Some(op);
+        self.op.as_mut().unwrap().apply(buffer)
+    }
+    fn undo(&mut self, buffer: &mut TextBuffer) -> Result<(), TBOperationError> {
+        if self.op.is_none() { return Err(TBOperationError::LogicError(None)); }
+        self.op.as_mut().unwrap().undo(buffer)
+    }
+}
+
+
+
+#[cfg(test)]
+mod test {
+    use crate::textedit::operation::{CursorLeft, InsertString};
+    use super::*;
+
+    #[test]
+    fn matches_nested_brackets() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new("f(a[0], (b)) {\n}".to_string())));
+
+        assert_eq!(matching_bracket(&buf, 1, &|_| false, usize::MAX), Some(11));
+        assert_eq!(matching_bracket(&buf, 11, &|_| false, usize::MAX), Some(1));
+        assert_eq!(matching_bracket(&buf, 8, &|_| false, usize::MAX), Some(10));
+        assert_eq!(matching_bracket(&buf, 13, &|_| false, usize::MAX), Some(15));
+        assert_eq!(matching_bracket(&buf, 0, &|_| false, usize::MAX), None);
+
+        // same result with the gap in the middle
+        buf.apply(Box::new(CursorLeft(10)));
+        assert_eq!(matching_bracket(&buf, 1, &|_| false, usize::MAX), Some(11));
+        assert_eq!(matching_bracket(&buf, 15, &|_| false, usize::MAX), Some(13));
+    }
+
+    #[test]
+    fn stops_lines_away() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new(format!("({}", "x\n".repeat(100)))));
+        buf.apply(Box::new(InsertString::new(")".to_string())));
+
+        // an unmatched bracket only has the lines searched
+        let probed = std::cell::Cell::new(0);
+        let in_text = |i: usize| { probed.set(std::cmp::max(probed.get(), i)); false };
+        assert_eq!(matching_bracket(&buf, 0, &in_text, 2), None);
+        assert!(probed.get() < buf.line_start(3).unwrap());
+
+        assert_eq!(matching_bracket(&buf, 0, &|_| false, 100), Some(201));
+        assert_eq!(matching_bracket(&buf, 201, &|_| false, 99), None);
+    }
+
+    #[test]
+    fn passes_over_brackets_in_text() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new("f(\")\", \"(\")".to_string())));
+        let in_text = |i: usize| (2..5).contains(&i) || (7..10).contains(&i);
+
+        assert_eq!(matching_bracket(&buf, 1, &in_text, usize::MAX), Some(10));
+        assert_eq!(matching_bracket(&buf, 10, &in_text, usize::MAX), Some(1));
+        assert_eq!(matching_bracket(&buf, 3, &in_text, usize::MAX), None);
+    }
+
+    #[test]
+    fn finds_bracket_on_line() {
+        let mut buf = TextBuffer::new();
+        buf.apply(Box::new(InsertString::new("let x = (1);\n[]".to_string())));
+
+        assert_eq!(next_bracket_on_line(&buf, 0), Some(8));
+        assert_eq!(next_bracket_on_line(&buf, 11), None);
+    }
+}
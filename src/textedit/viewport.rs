@@ -0,0 +1,189 @@
+//! Scroll position of a text window
+//! Positions are (line, row) pairs, row being the wrapped row within the logical line.
+//! Functions take rows(line), the number of screen rows a line takes up
+
+/// First visible position, in logical lines and wrapped rows
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Viewport {
+    /// First line on screen
+    pub line: usize,
+    /// Wrapped rows of the first line scrolled past
+    pub row: usize,
+    /// Display columns scrolled past, when not wrapping
+    pub col: usize,
+}
+
+impl Viewport {
+    /// Screen rows from the top of the view to pos, None if pos is above the view.
+    /// Stops counting once past limit
+    pub fn distance(&self, pos: (usize, usize), limit: usize, rows: &dyn Fn(usize) -> usize) -> Option<usize> {
+        let (line, row) = pos;
+        if line < self.line || (line == self.line && row < self.row) { return None; }
+
+        let mut distance = 0;
+        for l in self.line..line {
+            distance += rows(l);
+            if distance > limit + self.row { break; }
+        }
+        Some(distance + row - self.row)
+    }
+
+    /// Position shown at screen row n, clamped to the last row of the buffer
+    pub fn position_at(&self, n: usize, line_count: usize, rows: &dyn Fn(usize) -> usize) -> (usize, usize) {
+        let mut view = *self;
+        view.scroll(n as isize, line_count, rows);
+        (view.line, view.row)
+    }
+
+    /// Move the view by n screen rows, negative moves up.
+    /// Doesn't go past the top, or past the last row being first on screen.
+    /// Lines taking up no rows, like ones hidden in a fold, are skipped
+    pub fn scroll(&mut self, n: isize, line_count: usize, rows: &dyn Fn(usize) -> usize) {
+        for _ in 0..n.unsigned_abs() {
+            if n > 0 {
+                if self.row + 1 < rows(self.line) {
+                    self.row += 1;
+                    continue;
+                }
+                let next = (self.line + 1..line_count).find(|l| rows(*l) > 0);
+                match next {
+                    Some(next) => {
+                        self.line = next;
+                        self.row = 0;
+                    }
+                    None => break,
+                }
+            } else if self.row > 0 {
+                self.row -= 1;
+            } else {
+                let prev = (0..self.line).rev().find(|l| rows(*l) > 0);
+                match prev {
+                    Some(prev) => {
+                        self.line = prev;
+                        self.row = rows(prev) - 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    /// Scroll so pos is shown at screen row n, or as close as the top of the buffer allows
+    pub fn place(&mut self, pos: (usize, usize), n: usize, rows: &dyn Fn(usize) -> usize) {
+        self.line = pos.0;
+        self.row = pos.1;
+        self.scroll(-(n as isize), usize::MAX, rows);
+    }
+
+    /// Scroll as little as possible so pos is at least margin rows from both edges.
+    /// The margin is capped to half the height
+    pub fn follow(&mut self, pos: (usize, usize), height: usize, margin: usize, rows: &dyn Fn(usize) -> usize) {
+        if height == 0 { return; }
+        let margin = std::cmp::min(margin, (height - 1) / 2);
+        let bottom = height - 1 - margin;
+
+        match self.distance(pos, height, rows) {
+            Some(d) if d < margin => self.place(pos, margin, rows),
+            None => self.place(pos, margin, rows),
+            Some(d) if d > bottom => self.place(pos, bottom, rows),
+            _ => ()
+        }
+    }
+
+    /// Scroll sideways as little as possible so display column col is at least margin columns from both edges
+    pub fn follow_col(&mut self, col: usize, width: usize, margin: usize) {
+        if width == 0 { return; }
+        let margin = std::cmp::min(margin, (width - 1) / 2);
+
+        if col < self.col + margin {
+            self.col = col.saturating_sub(margin);
+        } else if col + margin >= self.col + width {
+            self.col = col + margin + 1 - width;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // line 2 wraps to 3 rows, others take 1
+    fn rows(line: usize) -> usize {
+        if line == 2 { 3 } else { 1 }
+    }
+
+    #[test]
+    fn distance_counts_wrapped_rows() {
+        let view = Viewport { line: 1, row: 0, col: 0 };
+        assert_eq!(view.distance((0, 0), 10, &rows), None);
+        assert_eq!(view.distance((2, 2), 10, &rows), Some(3));
+        assert_eq!(view.distance((4, 0), 10, &rows), Some(5));
+
+        let view = Viewport { line: 2, row: 1, col: 0 };
+        assert_eq!(view.distance((2, 0), 10, &rows), None);
+        assert_eq!(view.distance((3, 0), 10, &rows), Some(2));
+    }
+
+    #[test]
+    fn scroll_through_wrapped_line() {
+        let mut view = Viewport::default();
+        view.scroll(3, 10, &rows);
+        assert_eq!(view, Viewport { line: 2, row: 1, col: 0 });
+
+        view.scroll(-2, 10, &rows);
+        assert_eq!(view, Viewport { line: 1, row: 0, col: 0 });
+
+        view.scroll(-5, 10, &rows);
+        assert_eq!(view, Viewport::default());
+
+        view.scroll(50, 10, &rows);
+        assert_eq!(view, Viewport { line: 9, row: 0, col: 0 });
+    }
+
+    #[test]
+    fn scroll_skips_hidden_lines() {
+        // lines 3 to 5 folded into line 3
+        let rows = |line: usize| if (4..=5).contains(&line) { 0 } else { 1 };
+        let mut view = Viewport { line: 3, row: 0, col: 0 };
+        view.scroll(1, 10, &rows);
+        assert_eq!(view.line, 6);
+        view.scroll(-1, 10, &rows);
+        assert_eq!(view.line, 3);
+    }
+
+    #[test]
+    fn follow_keeps_margin() {
+        let mut view = Viewport::default();
+        view.follow((8, 0), 5, 1, &rows);
+        // line 8 is on row 3, with a row of margin below it
+        assert_eq!(view.distance((8, 0), 5, &rows), Some(3));
+        assert_eq!(view, Viewport { line: 5, row: 0, col: 0 });
+
+        view.follow((2, 2), 5, 1, &rows);
+        assert_eq!(view, Viewport { line: 2, row: 1, col: 0 });
+
+        // nothing to do while in view
+        view.follow((3, 0), 5, 1, &rows);
+        assert_eq!(view, Viewport { line: 2, row: 1, col: 0 });
+
+        view.follow((0, 0), 5, 1, &rows);
+        assert_eq!(view, Viewport::default());
+    }
+
+    #[test]
+    fn follow_col_keeps_margin() {
+        let mut view = Viewport::default();
+        view.follow_col(12, 10, 2);
+        assert_eq!(view.col, 5);
+
+        view.follow_col(8, 10, 2);
+        assert_eq!(view.col, 5);
+
+        view.follow_col(6, 10, 2);
+        assert_eq!(view.col, 4);
+
+        view.follow_col(1, 10, 2);
+        assert_eq!(view.col, 0);
+    }
+}
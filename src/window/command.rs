@@ -1,38 +1,203 @@
-use crossterm::event::KeyCode;
+use crate::alert::Alert;
+use crate::cmdline::{complete, CommandSpec};
 use crate::event::{EventPoster, Uuid};
+use crate::keymap::{Action, Feed, Key, KeyMatcher};
+use crate::plot::Plot;
 use crate::popup::{PopUp, PopUpDimension, PopUpDimensionOption, PopUpPosition, PopUpPositionOption};
-use crate::style::Canvas;
+use crate::style::{Canvas, StyleAttribute, StyledText, ThemeColor};
+use crate::window::history::History;
+use crate::window::lineedit::LineEdit;
 use crate::window::{Window, WindowEvent, WindowRequest};
 
-#[derive(Default)]
+/// Keymap of the command prompt
+const PROMPT: &str = "prompt";
+
+/// Rows of completion matches shown at once
+const MENU_ROWS: usize = 10;
+
+/// Matches of the word completed, above the prompt
+struct Menu {
+    /// Byte index in the line where the word starts
+    start: usize,
+    /// Word as typed, put back when cycling past the last match
+    typed: String,
+    matches: Vec<(String, String)>,
+    /// Match put in the line, none for the typed word
+    selected: Option<usize>,
+}
+
+/// Command line typed after `:`, run by the window manager on Enter
 pub struct Command {
-    cmd: String,
+    line: LineEdit,
+    history: History,
+    /// Commands of the focused windows, and their buffers, to complete
+    commands: Vec<&'static CommandSpec>,
+    buffers: Vec<String>,
+    menu: Option<Menu>,
+    /// Keys typed towards a prompt binding
+    keys: KeyMatcher,
     poster: Option<EventPoster<WindowRequest,Uuid>>
 }
 
+impl Command {
+    pub fn new(commands: Vec<&'static CommandSpec>, buffers: Vec<String>) -> Self {
+        Command {
+            line: LineEdit::default(),
+            history: History::load(),
+            commands,
+            buffers,
+            menu: None,
+            keys: KeyMatcher::default(),
+            poster: None,
+        }
+    }
+
+    /// Run the action of typed keys, or type keys bound to nothing
+    fn run_keys(&mut self, fed: Vec<Feed>) {
+        for fed in fed {
+            match fed {
+                Feed::Action(Action::Named(action)) => self.run_action(action),
+                // a command bound in the prompt runs in place of the typed one
+                Feed::Action(Action::Command(command)) => {
+                    self.line.set(&command);
+                    self.run_action("prompt.run");
+                }
+                Feed::Unbound(keys) => {
+                    for ch in keys.iter().filter_map(Key::as_char) {
+                        self.menu = None;
+                        self.history.stop_browsing();
+                        self.line.insert(ch);
+                    }
+                }
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: &str) {
+        if !matches!(action, "prompt.complete" | "prompt.complete_previous" | "prompt.run") {
+            self.menu = None;
+        }
+        if !matches!(action, "prompt.older" | "prompt.newer") {
+            self.history.stop_browsing();
+        }
+
+        match action {
+            "prompt.run" => {
+                self.history.add(self.line.text());
+                let saved = self.history.save();
+                let poster = self.poster.as_mut().unwrap();
+                poster.post(WindowRequest::Command(self.line.text().to_string()));
+                poster.post(WindowRequest::RemoveSelfPopup);
+                if let Err(e) = saved {
+                    poster.post(WindowRequest::AddPopup(Some(Box::new(Alert {
+                        content: StyledText::new(e),
+                        options: vec![(StyledText::new("Ok".into()), vec![])],
+                        ..Default::default()
+                    }))));
+                }
+            }
+            "prompt.cancel" => self.poster.as_mut().unwrap().post(WindowRequest::RemoveSelfPopup),
+            "prompt.left" => self.line.left(),
+            "prompt.right" => self.line.right(),
+            "prompt.word_left" => self.line.word_left(),
+            "prompt.word_right" => self.line.word_right(),
+            "prompt.home" => self.line.home(),
+            "prompt.end" => self.line.end(),
+            "prompt.delete_back" if self.line.text().is_empty() => self.run_action("prompt.cancel"),
+            "prompt.delete_back" => self.line.delete_back(),
+            "prompt.delete" => self.line.delete(),
+            "prompt.delete_word" => self.line.delete_word(),
+            "prompt.delete_line" => self.line.delete_to_start(),
+            "prompt.older" => {
+                if let Some(entry) = self.history.older(self.line.text()) {
+                    self.line.set(entry);
+                }
+            }
+            "prompt.newer" => {
+                if let Some(entry) = self.history.newer() {
+                    self.line.set(entry);
+                }
+            }
+            "prompt.complete" => self.complete(1),
+            "prompt.complete_previous" => self.complete(-1),
+            _ => ()
+        }
+    }
+
+    /// Complete the word before the cursor, or put the next match of the menu in its place
+    fn complete(&mut self, step: isize) {
+        if self.menu.is_none() {
+            let completion = complete(self.line.before_cursor(), &self.commands, &self.buffers);
+            if completion.matches.is_empty() { return; }
+
+            let typed = self.line.before_cursor()[completion.start..].to_string();
+            self.menu = Some(Menu { start: completion.start, typed, matches: completion.matches, selected: None });
+        }
+        let Some(menu) = self.menu.as_mut() else { return };
+
+        // cycle through the matches and the typed word
+        let count = menu.matches.len() as isize + 1;
+        let current = menu.selected.map_or(count - 1, |i| i as isize);
+        let next = (current + step).rem_euclid(count);
+        menu.selected = (next < count - 1).then_some(next as usize);
+
+        let text = menu.selected.map_or(&menu.typed, |i| &menu.matches[i].0);
+        self.line.replace_before_cursor(menu.start, text);
+
+        // a single match is taken right away
+        if menu.matches.len() == 1 {
+            self.menu = None;
+        }
+    }
+
+    /// Rows of the menu shown
+    fn menu_rows(&self) -> usize {
+        self.menu.as_ref().map_or(0, |menu| menu.matches.len().min(MENU_ROWS))
+    }
+}
+
 impl Window for Command {
     fn init(&mut self, poster: EventPoster<WindowRequest, Uuid>) {
         self.poster = Some(poster);
     }
     fn draw(&self, canvas: &mut Canvas) {
-       let text = format!(":{}", self.cmd);
-        canvas.write(&text.into());
-    }
-    fn event(&mut self, event: WindowEvent) {
-        match event {
-            WindowEvent::Input {key, modifiers} => match (key, modifiers) {
-                (KeyCode::Char(ch), _) => self.cmd += &ch.to_string(),
-                (KeyCode::Backspace, _) => { self.cmd.remove(self.cmd.len()-1); },
-                (KeyCode::Enter, _) => {
-                    self.poster.as_mut().unwrap().post(WindowRequest::Command(self.cmd.clone()));
-                    self.poster.as_mut().unwrap().post(WindowRequest::RemoveSelfPopup);
-                }
-                (KeyCode::Esc, _) => {
-                    self.poster.as_mut().unwrap().post(WindowRequest::RemoveSelfPopup);
+        let width = canvas.get_dim().col;
+        let row = self.menu_rows();
+
+        if let Some(menu) = &self.menu {
+            let _ = canvas.set_attribute(StyleAttribute::BgColor(ThemeColor::StatusBar),
+                Plot::new(0, 0),
+                Plot::new(row.saturating_sub(1), width));
+
+            // keep the selected match in view
+            let first = menu.selected.map_or(0, |i| (i + 1).saturating_sub(MENU_ROWS));
+            let name_width = menu.matches.iter().map(|(text, _)| text.chars().count()).max().unwrap_or(0) + 2;
+            for (i, (text, description)) in menu.matches.iter().enumerate().skip(first).take(MENU_ROWS) {
+                let at = Plot::new(i - first, 0);
+                let entry: String = format!(" {:<1$}{2}", text, name_width, description).chars().take(width).collect();
+                let _ = canvas.write_at(&StyledText::new(entry), at);
+                if menu.selected == Some(i) {
+                    let _ = canvas.set_attribute(StyleAttribute::BgColor(ThemeColor::Selection), at, at + Plot::new(0, width));
+                } else if !description.is_empty() {
+                    let col = (name_width + 1).min(width);
+                    let _ = canvas.set_attribute(StyleAttribute::Color(ThemeColor::Comment), at + Plot::new(0, col), at + Plot::new(0, width));
                 }
-                _ => ()
             }
-            _ => ()
+        }
+
+        // scroll sideways to keep the cursor in view
+        let text = format!(":{}", self.line.text());
+        let cursor = self.line.cursor() + 1;
+        let offset = (cursor + 2).saturating_sub(width);
+        let shown: String = text.chars().skip(offset).take(width).collect();
+        let _ = canvas.write_at(&StyledText::new(shown), Plot::new(row, 0));
+        let at = Plot::new(row, cursor - offset);
+        let _ = canvas.set_attribute(StyleAttribute::Reverse(true), at, at + Plot::new(0, 1));
+    }
+    fn event(&mut self, event: WindowEvent) {
+        if let WindowEvent::Input { key, modifiers } = event {
+            let fed = self.keys.feed(PROMPT, Key::new(key, modifiers));
+            self.run_keys(fed);
         }
 
         self.poster.as_mut().unwrap().post(WindowRequest::Redraw);
@@ -42,17 +207,80 @@ impl Window for Command {
 impl PopUp for Command {
     fn position(&self) -> PopUpPosition {
         PopUpPosition {
-            row: PopUpPositionOption::PositiveBound(1),
+            row: PopUpPositionOption::PositiveBound(1 + self.menu_rows() as isize),
             col: PopUpPositionOption::NegativeBound(0)
         }
     }
     fn dimension(&self) -> PopUpDimension {
         PopUpDimension {
-            row: PopUpDimensionOption::Fixed(1),
+            row: PopUpDimensionOption::Fixed(1 + self.menu_rows()),
             col: PopUpDimensionOption::Percent(1.0)
         }
     }
     fn local(&self) -> bool {
         true
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cmdline::ArgKind;
+    use crate::event::EventReceiver;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    const SET: CommandSpec = CommandSpec::new("set", "se", "").arg(ArgKind::Options);
+    const SETFILE: CommandSpec = CommandSpec::new("setfile", "setf", "").required(ArgKind::Options);
+
+    fn prompt(receiver: &mut EventReceiver<WindowRequest, Uuid>) -> Command {
+        let mut prompt = Command::new(vec![&SET, &SETFILE], Vec::new());
+        prompt.history = History::new(vec!["set nu".into(), "w".into()]);
+        prompt.init(receiver.new_poster());
+        prompt
+    }
+    fn type_keys(prompt: &mut Command, keys: &[KeyCode]) {
+        for key in keys {
+            prompt.event(Key::new(*key, KeyModifiers::NONE).into());
+        }
+    }
+
+    #[test]
+    fn completes_with_menu() {
+        let mut receiver = EventReceiver::new();
+        let mut prompt = prompt(&mut receiver);
+        type_keys(&mut prompt, &[KeyCode::Char('s'), KeyCode::Char('e'), KeyCode::Tab]);
+        assert_eq!(prompt.line.text(), "set");
+        assert_eq!(prompt.menu_rows(), 2);
+        type_keys(&mut prompt, &[KeyCode::Tab]);
+        assert_eq!(prompt.line.text(), "setfile");
+        // back to the typed word
+        type_keys(&mut prompt, &[KeyCode::Tab]);
+        assert_eq!(prompt.line.text(), "se");
+        type_keys(&mut prompt, &[KeyCode::BackTab]);
+        assert_eq!(prompt.line.text(), "setfile");
+
+        // typing closes the menu, a single match is taken
+        type_keys(&mut prompt, &[KeyCode::Char(' '), KeyCode::Char('f'), KeyCode::Char('d'), KeyCode::Char('m'), KeyCode::Char('='), KeyCode::Char('i'), KeyCode::Tab]);
+        assert_eq!((prompt.line.text(), prompt.menu_rows()), ("setfile fdm=indent", 0));
+
+        let mut canvas = Canvas::new(Plot::new(3, 20));
+        prompt.draw(&mut canvas);
+    }
+
+    #[test]
+    fn edits_and_browses_history() {
+        let mut receiver = EventReceiver::new();
+        let mut prompt = prompt(&mut receiver);
+        // empty prompt closes on backspace
+        type_keys(&mut prompt, &[KeyCode::Backspace]);
+        assert!(receiver.poll().iter().any(|(_, request)| matches!(request, WindowRequest::RemoveSelfPopup)));
+
+        type_keys(&mut prompt, &[KeyCode::Char('s'), KeyCode::Up]);
+        assert_eq!(prompt.line.text(), "set nu");
+        type_keys(&mut prompt, &[KeyCode::Up, KeyCode::Down]);
+        assert_eq!(prompt.line.text(), "s");
+        type_keys(&mut prompt, &[KeyCode::Home, KeyCode::Delete, KeyCode::Char('w')]);
+        assert_eq!((prompt.line.text(), prompt.line.cursor()), ("w", 1));
+    }
+}
@@ -0,0 +1,108 @@
+//! Command lines run from the prompt, kept between sessions
+use std::path::PathBuf;
+use crate::config::{current_settings, state_dir};
+
+/// File keeping the history, one command line on each line, oldest first
+pub fn history_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("history"))
+}
+
+/// Command lines run, oldest first, and where browsing them is at
+#[derive(Default, Debug)]
+pub struct History {
+    entries: Vec<String>,
+    /// Entry shown and the line typed before browsing, which entries are filtered by
+    browsing: Option<(usize, String)>,
+}
+
+impl History {
+    pub fn new(entries: Vec<String>) -> Self {
+        History { entries, browsing: None }
+    }
+
+    /// History of the history file, empty if there is none
+    pub fn load() -> Self {
+        let source = history_file().and_then(|path| std::fs::read_to_string(path).ok()).unwrap_or_default();
+        History::new(source.lines().filter(|line| !line.is_empty()).map(str::to_string).collect())
+    }
+
+    /// Write the history file, keeping the newest `history` lines
+    pub fn save(&mut self) -> Result<(), String> {
+        let path = history_file().ok_or("no state directory")?;
+        let keep = current_settings().number("history");
+        self.entries.drain(..self.entries.len().saturating_sub(keep));
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        let source: String = self.entries.iter().map(|entry| format!("{}\n", entry)).collect();
+        std::fs::write(&path, source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Add line as the newest entry, removing an older copy of it
+    pub fn add(&mut self, line: &str) {
+        self.browsing = None;
+        if line.trim().is_empty() { return; }
+        self.entries.retain(|entry| entry != line);
+        self.entries.push(line.to_string());
+    }
+
+    /// Entry before the one shown starting with typed, the line typed before browsing.
+    /// None when there is no older one
+    pub fn older(&mut self, typed: &str) -> Option<&str> {
+        let (shown, typed) = self.browsing.take().unwrap_or((self.entries.len(), typed.to_string()));
+        let found = self.entries[..shown].iter().rposition(|entry| entry.starts_with(&typed));
+        self.browsing = Some((found.unwrap_or(shown), typed));
+        found.map(|i| self.entries[i].as_str())
+    }
+
+    /// Entry after the one shown starting with the line typed before browsing,
+    /// or that line once past the newest. None when not browsing
+    pub fn newer(&mut self) -> Option<&str> {
+        let (shown, typed) = self.browsing.take()?;
+        let found = self.entries.iter().skip(shown + 1).position(|entry| entry.starts_with(&typed));
+        match found {
+            Some(i) => {
+                self.browsing = Some((shown + 1 + i, typed));
+                Some(&self.entries[shown + 1 + i])
+            }
+            None => {
+                // back to the typed line, which is kept while not browsing
+                self.browsing = Some((self.entries.len(), typed));
+                self.browsing.as_ref().map(|(_, typed)| typed.as_str())
+            }
+        }
+    }
+
+    /// Editing the line ends browsing
+    pub fn stop_browsing(&mut self) {
+        self.browsing = None;
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn browses_by_prefix() {
+        let mut history = History::new(vec!["set ts=2".into(), "w".into(), "set nu".into()]);
+        assert_eq!(history.newer(), None);
+        assert_eq!(history.older("se"), Some("set nu"));
+        assert_eq!(history.older("ignored while browsing"), Some("set ts=2"));
+        // stays at the oldest match
+        assert_eq!(history.older(""), None);
+        assert_eq!(history.newer(), Some("set nu"));
+        assert_eq!(history.newer(), Some("se"));
+        assert_eq!(history.older("se"), Some("set nu"));
+
+        history.stop_browsing();
+        assert_eq!(history.older(""), Some("set nu"));
+        assert_eq!(history.older(""), Some("w"));
+
+        history.add("set ts=2");
+        assert_eq!(history.entries, ["w", "set nu", "set ts=2"]);
+        assert_eq!(history.older(""), Some("set ts=2"));
+    }
+}
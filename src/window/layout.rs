@@ -193,6 +193,9 @@ impl Layout {
         self.window_space = result.0;
         self.border_space = result.1;
     }
+    pub fn get_dim(&self) -> Plot {
+        self.dim
+    }
     pub fn get_windows(&self) -> &Vec<WindowSpace> {
         &self.window_space
     }
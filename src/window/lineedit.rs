@@ -0,0 +1,161 @@
+//! Single line of text edited at a cursor, like the command prompt's
+
+/// Text and the char index of the cursor in it
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct LineEdit {
+    text: String,
+    cursor: usize,
+}
+
+impl LineEdit {
+    /// Line holding text, the cursor at its end
+    pub fn new(text: &str) -> Self {
+        LineEdit { text: text.to_string(), cursor: text.chars().count() }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+    /// Text left of the cursor
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.byte(self.cursor)]
+    }
+
+    /// Replace the text, the cursor going to its end
+    pub fn set(&mut self, text: &str) {
+        *self = LineEdit::new(text);
+    }
+
+    pub fn insert(&mut self, ch: char) {
+        let at = self.byte(self.cursor);
+        self.text.insert(at, ch);
+        self.cursor += 1;
+    }
+
+    /// Replace the text from byte start to the cursor, the cursor ending after the new text
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        let end = self.byte(self.cursor);
+        self.text.replace_range(start..end, text);
+        self.cursor = self.text[..start + text.len()].chars().count();
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.len());
+    }
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+    pub fn end(&mut self) {
+        self.cursor = self.len();
+    }
+
+    /// Move to the start of the word left of the cursor
+    pub fn word_left(&mut self) {
+        self.cursor = self.word_start();
+    }
+    /// Move past the end of the word right of the cursor
+    pub fn word_right(&mut self) {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut i = self.cursor;
+        while i < chars.len() && chars[i].is_whitespace() { i += 1; }
+        let word = chars.get(i).is_some_and(|ch| is_word(*ch));
+        while i < chars.len() && !chars[i].is_whitespace() && is_word(chars[i]) == word { i += 1; }
+        self.cursor = i;
+    }
+
+    /// Delete the char left of the cursor
+    pub fn delete_back(&mut self) {
+        if self.cursor == 0 { return; }
+        self.cursor -= 1;
+        self.text.remove(self.byte(self.cursor));
+    }
+    /// Delete the char at the cursor
+    pub fn delete(&mut self) {
+        if self.cursor < self.len() {
+            self.text.remove(self.byte(self.cursor));
+        }
+    }
+    /// Delete the word left of the cursor and the spaces after it
+    pub fn delete_word(&mut self) {
+        let start = self.word_start();
+        self.text.replace_range(self.byte(start)..self.byte(self.cursor), "");
+        self.cursor = start;
+    }
+    /// Delete the text left of the cursor
+    pub fn delete_to_start(&mut self) {
+        self.text.replace_range(..self.byte(self.cursor), "");
+        self.cursor = 0;
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+    /// Byte index of the char at index
+    fn byte(&self, index: usize) -> usize {
+        self.text.char_indices().nth(index).map_or(self.text.len(), |(i, _)| i)
+    }
+    /// Char index where the word left of the cursor starts, skipping spaces before the cursor.
+    /// Words are runs of letters, digits and `_`, or runs of other chars that aren't spaces
+    fn word_start(&self) -> usize {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut i = self.cursor;
+        while i > 0 && chars[i - 1].is_whitespace() { i -= 1; }
+        let word = i > 0 && is_word(chars[i - 1]);
+        while i > 0 && !chars[i - 1].is_whitespace() && is_word(chars[i - 1]) == word { i -= 1; }
+        i
+    }
+}
+
+fn is_word(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn edits_at_cursor() {
+        let mut line = LineEdit::default();
+        // nothing to delete
+        line.delete_back();
+        line.delete();
+        for ch in "set ts".chars() { line.insert(ch); }
+        line.home();
+        line.delete();
+        line.insert('S');
+        line.end();
+        line.left();
+        line.insert('é');
+        assert_eq!((line.text(), line.cursor()), ("Set tés", 6));
+        line.delete_back();
+        assert_eq!(line.before_cursor(), "Set t");
+        line.replace_before_cursor(4, "tabstop");
+        assert_eq!((line.text(), line.cursor()), ("Set tabstops", 11));
+    }
+
+    #[test]
+    fn moves_and_deletes_words() {
+        let mut line = LineEdit::new("e src/main.rs  ");
+        line.delete_word();
+        assert_eq!(line.text(), "e src/main.");
+        line.delete_word();
+        assert_eq!(line.text(), "e src/main");
+        line.word_left();
+        assert_eq!(line.cursor(), 6);
+        line.word_left();
+        line.word_left();
+        assert_eq!(line.cursor(), 2);
+        line.word_right();
+        assert_eq!(line.cursor(), 5);
+        line.delete_to_start();
+        assert_eq!((line.text(), line.cursor()), ("/main", 0));
+    }
+}
@@ -3,6 +3,8 @@ mod window;
 mod windowmanager;
 pub mod tab;
 mod command;
+mod history;
+mod lineedit;
 pub mod windowcontainer;
 mod layout;
 
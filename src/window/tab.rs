@@ -1,13 +1,17 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::io::Write;
-use std::path::PathBuf;
-use crossterm::event::{KeyCode, KeyModifiers};
+use std::path::Path;
 use crossterm::QueueableCommand;
+use crate::alert::Alert;
+use crate::cmdline::{Arg, ArgKind, CommandSpec};
+use crate::config::{current_settings, Settings};
 use crate::event::{EventPoster, EventReceiver, Uuid};
+use crate::keymap::{Action, Feed, Key, KeyHints, KeyMatcher};
 use crate::plot::Plot;
 use crate::popup::PopUp;
 use crate::style::{Canvas, StyleAttribute, StyledText, ThemeColor};
+use crate::textedit::textwindow::TextWindow;
 use crate::window::{Window, WindowEvent, WindowManager, WindowRequest};
 use crate::window::command::Command;
 use crate::window::layout::{BorderSpace, Layout};
@@ -16,45 +20,60 @@ use crate::window::windowcontainer::{OrderedWindowContainer, WindowContainer};
 pub struct TabSettings {
     show_tabs: bool,
 }
+impl From<&Settings> for TabSettings {
+    fn from(settings: &Settings) -> Self { Self { show_tabs: settings.bool("showtabs") } }
+}
 impl Default for TabSettings {
-    fn default() -> Self { Self { show_tabs: true } }
+    fn default() -> Self { Self::from(&*current_settings()) }
 }
 
 pub struct TabWindow {
     container: OrderedWindowContainer,
     settings: TabSettings,
+    /// Keys typed towards a tab binding
+    keys: KeyMatcher,
     dim: Plot,
 }
 
+/// Keymap of tab windows
+const TABS: &str = "tabs";
+
+/// Commands of tab windows
+const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("edit", "e", "Open a file in a new tab").required(ArgKind::Path),
+    CommandSpec::new("buffer", "b", "Show the tab of a buffer").required(ArgKind::Buffer),
+];
+
 impl TabWindow {
     pub fn new() -> Self {
         Self {
             container: OrderedWindowContainer::new(),
             settings: TabSettings::default(),
+            keys: KeyMatcher::default(),
             dim: Plot::default(),
         }
     }
 
-}
-
-impl Window for TabWindow {
-    fn init(&mut self, poster: EventPoster<WindowRequest, Uuid>) {
-        self.container.set_poster(poster);
+    /// Run the action of typed keys, or pass keys bound to nothing to the current tab
+    fn run_keys(&mut self, fed: Vec<Feed>) {
+        for fed in fed {
+            match fed {
+                Feed::Action(Action::Named(action)) => self.run_action(action),
+                Feed::Action(Action::Command(command)) => { let _ = self.container.post(WindowRequest::Command(command)); }
+                Feed::Unbound(keys) => {
+                    for key in keys {
+                        self.container.event(key.into());
+                    }
+                }
+            }
+        }
     }
-    fn event(&mut self, mut event: WindowEvent) {
 
-        self.container.distribute_events(&mut event);
-        // event may be none
-
-        // self controls
-        match event {
-            WindowEvent::Input { key:KeyCode::Tab, .. } => {
-                self.container.cycle_current();
-            }
-            WindowEvent::Input { key:KeyCode::Char('\''), .. } => {
-                self.settings.show_tabs = !self.settings.show_tabs;
-            }
-            WindowEvent::Input { key:KeyCode::Right, modifiers:KeyModifiers::CONTROL, .. } => {
+    fn run_action(&mut self, action: &str) {
+        match action {
+            "tab.next" => self.container.cycle_current(),
+            "tab.toggle_bar" => self.settings.show_tabs = !self.settings.show_tabs,
+            "tab.split" => {
                 if let Some(uuid) = self.container.window_order().get(self.container.get_current()) {
                     let window = self.remove_window(uuid.clone());
 
@@ -62,7 +81,7 @@ impl Window for TabWindow {
                         let mut new_tab = TabWindow::new();
                         new_tab.add_window(window);
 
-                        self.container.post(WindowRequest::AddWindow(
+                        let _ = self.container.post(WindowRequest::AddWindow(
                             Some(Box::new(new_tab))
                         ));
 
@@ -70,8 +89,85 @@ impl Window for TabWindow {
                     }
                 }
             }
+            _ => ()
+        }
+    }
+
+    /// Open the file at path in a new tab and show it
+    fn edit(&mut self, path: &Path) {
+        match TextWindow::open(path.to_path_buf()) {
+            Ok(window) => {
+                self.add_window(Box::new(window));
+                self.container.focus(self.container.window_count() - 1);
+            }
+            Err(message) => self.alert(message),
+        }
+    }
+
+    /// Show the tab holding the buffer called name, or the first one starting with it
+    fn show_buffer(&mut self, name: &str) {
+        let buffers: Vec<Vec<String>> = (0..self.container.window_count())
+            .map(|i| self.container.get_from_order(i).map(|window| window.buffers()).unwrap_or_default())
+            .collect();
+        let found = buffers.iter().position(|names| names.iter().any(|n| n == name))
+            .or_else(|| buffers.iter().position(|names| names.iter().any(|n| n.starts_with(name))));
+        match found {
+            Some(i) => self.container.focus(i),
+            None => self.alert(format!("No buffer called {}", name)),
+        }
+    }
+
+    /// Show message in a popup
+    fn alert(&mut self, message: String) {
+        self.add_popup(Box::new(Alert {
+            content: StyledText::new(message),
+            options: vec![(StyledText::new("Ok".into()), vec![])],
+            ..Default::default()
+        }));
+    }
+}
+
+impl Window for TabWindow {
+    fn init(&mut self, poster: EventPoster<WindowRequest, Uuid>) {
+        self.container.set_poster(poster);
+    }
+    fn event(&mut self, mut event: WindowEvent) {
+        // reaches windows behind popups too
+        if let WindowEvent::SettingsChanged = event {
+            self.settings = TabSettings::default();
+            self.container.event(event);
+            return;
+        }
+
+        // popups of the tab don't take commands
+        if let WindowEvent::Command(command) = event {
+            match (command.name(), &command.arg) {
+                ("edit", Some(Arg::Path(path))) => self.edit(path),
+                ("buffer", Some(Arg::Text(name))) => self.show_buffer(name),
+                _ => self.container.event(WindowEvent::Command(command)),
+            }
+            let _ = self.container.post(WindowRequest::Redraw);
+            return;
+        }
 
+        if let WindowEvent::Input { key, modifiers } = event {
+            if self.keys.is_pending() {
+                let fed = self.keys.feed(TABS, Key::new(key, modifiers));
+                self.run_keys(fed);
+                let _ = self.container.post(WindowRequest::Redraw);
+                return;
+            }
+        }
 
+        self.container.distribute_events(&mut event);
+        // event may be none
+
+        // self controls
+        match event {
+            WindowEvent::Input { key, modifiers } => {
+                let fed = self.keys.feed(TABS, Key::new(key, modifiers));
+                self.run_keys(fed);
+            }
 
             event => {
                 self.container.event(event);
@@ -105,9 +201,9 @@ impl Window for TabWindow {
         if self.settings.show_tabs {
             let mut header_canvas = Canvas::new(Plot::new(1,canvas.get_dim().col));
 
-            // gray bar across
+            // status bar across
             header_canvas.set_attribute(
-                StyleAttribute::BgColor(ThemeColor::Gray),
+                StyleAttribute::BgColor(ThemeColor::StatusBar),
                 Plot::new(0, 0),
                 Plot::new(0, header_canvas.last_col() + 1)).unwrap();
 
@@ -137,10 +233,27 @@ impl Window for TabWindow {
         self.container.draw(canvas);
     }
     fn collect_requests(&mut self) -> Vec<WindowRequest> {
+        let fed = self.keys.timed_out(TABS);
+        if !fed.is_empty() {
+            self.run_keys(fed);
+            let _ = self.container.post(WindowRequest::Redraw);
+        }
         self.container.collect_requests()
     }
     fn input_bypass(&self) -> bool {
-        self.container.input_bypass()
+        self.keys.is_pending() || self.container.input_bypass()
+    }
+    fn key_hints(&self) -> Option<KeyHints> {
+        self.keys.hints(TABS).or_else(|| self.container.key_hints())
+    }
+    fn commands(&self) -> Vec<&'static CommandSpec> {
+        COMMANDS.iter().chain(self.container.commands()).collect()
+    }
+    fn buffers(&self) -> Vec<String> {
+        (0..self.container.window_count())
+            .filter_map(|i| self.container.get_from_order(i))
+            .flat_map(|window| window.buffers())
+            .collect()
     }
 }
 
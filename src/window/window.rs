@@ -1,7 +1,9 @@
 use crossterm::event::{KeyCode, KeyModifiers};
 use std::error::Error;
 use std::ops::Range;
+use crate::cmdline::{CommandSpec, ParsedCommand};
 use crate::event::{EventPoster, Uuid};
+use crate::keymap::{Key, KeyHints};
 use crate::plot::Plot;
 use crate::popup::PopUp;
 use crate::style::{Canvas, ThemeColor, StyleAttribute, StyledText};
@@ -19,7 +21,10 @@ pub enum WindowRequest {
     RemoveSelfPopup,
     AddWindow(Option<Box<dyn Window>>),
     AddPopup(Option<Box<dyn PopUp>>),
+    /// Command line run as if typed in the command prompt, passed up to the window manager
     Command(String),
+    /// Current settings were changed, to be applied to every window
+    SettingsChanged,
     None
 }
 
@@ -37,11 +42,20 @@ pub enum WindowEvent {
     Resize(Plot),
     Focus,
     Unfocus,
-    Command(String),
+    /// Command checked against the commands of the window or one of its current windows
+    Command(ParsedCommand),
     TryQuit,
+    /// Current settings were changed, sent to every window
+    SettingsChanged,
     None
 }
 
+impl From<Key> for WindowEvent {
+    fn from(key: Key) -> Self {
+        WindowEvent::Input { key: key.code, modifiers: key.modifiers }
+    }
+}
+
 impl Default for WindowEvent {
     fn default() -> WindowEvent {
         WindowEvent::None
@@ -60,6 +74,18 @@ pub trait Window {
     /// Checks managed by super window.
     fn input_bypass(&self) -> bool { false }
 
+    /// Keys that may follow a half typed sequence.
+    /// Super windows pass on the ones of their current window
+    fn key_hints(&self) -> Option<KeyHints> { None }
+
+    /// Commands run when sent as `WindowEvent::Command`.
+    /// Super windows add the ones of their current window after their own
+    fn commands(&self) -> Vec<&'static CommandSpec> { Vec::new() }
+
+    /// Names of the buffers open in the window.
+    /// Super windows pass on the ones of their current window, the tab window the ones of every tab
+    fn buffers(&self) -> Vec<String> { Vec::new() }
+
     /// Gives a writeable canvas.
     /// Automatically called on
     /// - resize
@@ -91,7 +117,7 @@ pub struct TestWindow {
 impl Window for TestWindow {
     fn event(&mut self, event: WindowEvent) {
         match event {
-            WindowEvent::Command(cmd) => self.command = cmd,
+            WindowEvent::Command(cmd) => self.command = cmd.name().to_string(),
             WindowEvent::Input { key, modifiers } => match key {
                 KeyCode::Char(ch) => self.content += &ch.to_string(),
                 _ => ()
@@ -100,6 +126,7 @@ impl Window for TestWindow {
             WindowEvent::Focus => self.focused = true,
             WindowEvent::Unfocus => self.focused = false,
             WindowEvent::TryQuit => (),
+            WindowEvent::SettingsChanged => (),
             WindowEvent::None => ()
         }
     }
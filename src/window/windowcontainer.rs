@@ -2,7 +2,9 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::future::Future;
 use std::hash::Hash;
+use crate::cmdline::CommandSpec;
 use crate::event::{EventPoster, EventReceiver, Uuid};
+use crate::keymap::KeyHints;
 use crate::popup::PopUp;
 use crate::style::Canvas;
 use crate::window::{Window, WindowEvent, WindowRequest};
@@ -62,11 +64,16 @@ impl OrderedWindowContainer {
     pub fn set_current(&mut self, current: usize) { self.current = current; }
     pub fn cycle_current(&mut self) {
         if self.window_order.len() == 0 { return; }
+        self.focus((self.current + 1) % self.window_order.len());
+    }
+    /// Make the window at i in order current
+    pub fn focus(&mut self, i: usize) {
+        if i >= self.window_order.len() { return; }
         // unfocus old
         if let Some(old) = self.get_from_order_mut(self.current) {
             old.event(WindowEvent::Unfocus);
         }
-        self.current = (self.current + 1) % self.window_order.len();
+        self.current = i;
         if let Some(cur) = self.get_from_order_mut(self.current) {
             cur.event(WindowEvent::Focus);
         }
@@ -156,17 +163,18 @@ impl OrderedWindowContainer {
 
                     processed.push(WindowRequest::RemoveSelfPopup);
                 }
-                WindowRequest::Command(command) => {
-                    if let Some(window) = self.get_from_order_mut(self.get_current()) {
-                        window.event(WindowEvent::Command(command.clone()));
-                    }
-                    self.post(WindowRequest::Redraw);
-                    processed.push(WindowRequest::Command(command));
-                }
                 WindowRequest::Redraw => {
                     self.post(WindowRequest::Redraw);
                     processed.push(WindowRequest::Redraw);
                 }
+                WindowRequest::Command(command) => {
+                    let _ = self.post(WindowRequest::Command(command.clone()));
+                    processed.push(WindowRequest::Command(command));
+                }
+                WindowRequest::SettingsChanged => {
+                    let _ = self.post(WindowRequest::SettingsChanged);
+                    processed.push(WindowRequest::SettingsChanged);
+                }
                 event => self.seen.push((uuid, event)),
             }
         }
@@ -192,6 +200,11 @@ impl Window for OrderedWindowContainer {
                     window.event(WindowEvent::Resize(dim));
                 }
             },
+            WindowEvent::SettingsChanged => {
+                for window in self.windows.values_mut() {
+                    window.event(WindowEvent::SettingsChanged);
+                }
+            },
 
             // apply to current
             event => {
@@ -208,6 +221,17 @@ impl Window for OrderedWindowContainer {
             false
         }
     }
+    fn key_hints(&self) -> Option<KeyHints> {
+        // popups take the keys
+        if !self.popup_order.is_empty() { return None; }
+        self.get_from_order(self.current)?.key_hints()
+    }
+    fn commands(&self) -> Vec<&'static CommandSpec> {
+        self.get_from_order(self.current).map(|window| window.commands()).unwrap_or_default()
+    }
+    fn buffers(&self) -> Vec<String> {
+        self.get_from_order(self.current).map(|window| window.buffers()).unwrap_or_default()
+    }
     fn draw(&self, canvas: &mut Canvas) {
         // draw popups!
         for uuid in self.popup_order.iter() {
@@ -4,16 +4,22 @@ use std::error::Error;
 use std::hash::Hash;
 use std::io::{Stdout, Write};
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use crossterm::cursor::{Hide, MoveTo, Show};
 use crossterm::{queue, QueueableCommand};
-use crossterm::event::{KeyCode, KeyModifiers, ModifierKeyCode};
 use crossterm::terminal::{Clear, ClearType};
+use crate::cmdline::{self, ArgKind, CommandSpec, ParsedCommand};
 use crate::event::{EventPoster, EventReceiver, Uuid};
 use crate::fswindow::FSWindow;
+use crate::keyhint::KeyHintPopup;
+use crate::keymap::{keymaps, Action, Feed, Key, KeyHints, KeyMatcher, Lookup};
+use crate::settingswindow::SettingsWindow;
 use crate::window::layout::{BorderSpace, Layout, WindowSpace};
 use crate::plot::Plot;
 use crate::popup::PopUp;
-use crate::style::{Canvas, StyleAttribute, StyledText};
+use crate::config::{apply_saved_set, current_layers, current_settings, set_layers, Layer};
+use crate::style::{set_color_depth, set_colorscheme, themes, Canvas, ColorDepth, StyleAttribute, StyledText};
+use crate::alert::Alert;
 use crate::style::ThemeColor;
 use crate::window::command::Command;
 use crate::window::tab::TabWindow;
@@ -24,10 +30,28 @@ pub struct WindowManager {
     layout: Layout,
     current_dir: PathBuf,
     active: bool,
+    /// Keys typed towards a global binding
+    keys: KeyMatcher,
+    /// Hints for the sequence being typed, when they were first found and whether they are drawn yet
+    hints: Option<(KeyHintPopup, Instant, bool)>,
 
     require_reset: bool
 }
 
+/// Keymap of the window manager
+const GLOBAL: &str = "global";
+
+/// Commands of the window manager, run whichever window is focused
+const COMMANDS: &[CommandSpec] = &[
+    CommandSpec::new("set", "se", "Change options, or show the ones changed from their default").arg(ArgKind::Options),
+    CommandSpec::new("setglobal", "setg", "Change options and save them to the config file").required(ArgKind::Options),
+    CommandSpec::new("setproject", "setp", "Change options and save them to the project file").required(ArgKind::Options),
+    CommandSpec::new("settings", "setti", "Open the settings page"),
+    CommandSpec::new("colorscheme", "colo", "Switch color theme, or show the current one").arg(ArgKind::Text),
+    CommandSpec::new("x", "x", "Open the explorer in a new window"),
+    CommandSpec::new("qall", "qa", "Ask every window to close, or exit right away with !").bang(),
+];
+
 
 // maps windows to correct shape/position in terminal
 impl Window for WindowManager {
@@ -35,9 +59,15 @@ impl Window for WindowManager {
         self.container.set_poster(poster);
     }
     fn event(&mut self, mut event: WindowEvent) {
-        if let WindowEvent::Input { key:KeyCode::End, modifiers:KeyModifiers::CONTROL }  = event {
-            self.container.post(WindowRequest::RemoveSelfWindow);
-            return;
+        if let WindowEvent::Input { key, modifiers } = event {
+            let key = Key::new(key, modifiers);
+            // finish a sequence, and exit even when the focused window takes keys
+            let exits = matches!(keymaps().get(GLOBAL).lookup(&[key]), Lookup::Exact(Action::Named("app.exit"), _));
+            if self.keys.is_pending() || exits {
+                let fed = self.keys.feed(GLOBAL, key);
+                self.run_keys(fed);
+                return;
+            }
         }
 
         self.container.distribute_events(&mut event);
@@ -46,24 +76,10 @@ impl Window for WindowManager {
 
         // forward events to current
         match event {
-            WindowEvent::Input { key:KeyCode::Esc, .. } => {
-                self.container.event(WindowEvent::TryQuit);
-            }
-            WindowEvent::Input { key:KeyCode::Char('l'), modifiers:KeyModifiers::CONTROL, .. } => {
-                self.container.cycle_current();
-            }
-            WindowEvent::Input { key:KeyCode::Char(':'), .. } => {
-                self.add_popup(Box::new(
-                    Command::default()
-                ));
-                self.container.post(WindowRequest::Redraw);
+            WindowEvent::Input { key, modifiers } => {
+                let fed = self.keys.feed(GLOBAL, Key::new(key, modifiers));
+                self.run_keys(fed);
             }
-            WindowEvent::Input { key:KeyCode::Char('x'), modifiers:KeyModifiers::CONTROL, .. } => {
-                if let Some(window) = self.container.get_from_order_mut(self.container.get_current()) {
-                    window.event(WindowEvent::TryQuit);
-                }
-            }
-
 
             WindowEvent::None => (),
 
@@ -109,7 +125,7 @@ impl Window for WindowManager {
                 s = start;
             }
             let text = StyledText::new(text)
-                .with(StyleAttribute::BgColor(ThemeColor::Black))
+                .with(StyleAttribute::BgColor(ThemeColor::Border))
                 .with(StyleAttribute::Color(ThemeColor::White));
             border_canvas.write_wrap(&text);
 
@@ -117,9 +133,21 @@ impl Window for WindowManager {
         }
 
         self.container.draw(canvas);
+
+        if let Some((popup, _, true)) = &self.hints {
+            let dim = popup.term_dim(canvas.get_dim());
+            let pos = popup.term_pos(canvas.get_dim());
+            let mut popup_canvas = Canvas::new(dim);
+            popup.draw(&mut popup_canvas);
+            canvas.add_child(popup_canvas, pos);
+        }
     }
     fn collect_requests(&mut self) -> Vec<WindowRequest> {
+        let fed = self.keys.timed_out(GLOBAL);
+        self.run_keys(fed);
+
         let requests = self.container.collect_requests();
+        self.update_hints();
         for e in requests.iter() {
             if let WindowRequest::AddWindow(..) = e {
                 if self.container.window_count() > self.layout.get_windows().len() {
@@ -133,21 +161,12 @@ impl Window for WindowManager {
                 self.layout.generate();
             }
 
-            if let WindowRequest::Command(command) = e {
-                if command == "x" {
-                    let mut tab = TabWindow::new();
-                    tab.add_window(Box::new(FSWindow::new(self.current_dir.clone())));
+            if let WindowRequest::SettingsChanged = e {
+                self.apply_settings();
+            }
 
-                    self.container.get_receiver().new_poster().post(
-                        WindowRequest::AddWindow(
-                            Some(Box::new(tab)
-                        )
-                    ));
-                    self.container.post(WindowRequest::Redraw);
-                }
-                if command == "qall" {
-                    self.event(WindowEvent::TryQuit);
-                }
+            if let WindowRequest::Command(line) = e {
+                self.run_command(line);
             }
         }
 
@@ -157,6 +176,15 @@ impl Window for WindowManager {
     fn input_bypass(&self) -> bool {
         self.container.input_bypass()
     }
+    fn key_hints(&self) -> Option<KeyHints> {
+        self.keys.hints(GLOBAL).or_else(|| self.container.key_hints())
+    }
+    fn commands(&self) -> Vec<&'static CommandSpec> {
+        COMMANDS.iter().chain(self.container.commands()).collect()
+    }
+    fn buffers(&self) -> Vec<String> {
+        self.container.buffers()
+    }
 }
 
 impl WindowContainer for WindowManager {
@@ -184,10 +212,191 @@ impl WindowManager {
             layout,
             current_dir: PathBuf::new(),
             active: true,
+            keys: KeyMatcher::default(),
+            hints: None,
             require_reset: false,
         }
     }
 
+    /// Run the action of typed keys, or pass keys bound to nothing to the current window
+    fn run_keys(&mut self, fed: Vec<Feed>) {
+        for fed in fed {
+            match fed {
+                Feed::Action(Action::Named(action)) => self.run_action(action),
+                Feed::Action(Action::Command(command)) => self.run_command(&command),
+                Feed::Unbound(keys) => {
+                    for key in keys {
+                        self.container.event(key.into());
+                    }
+                }
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: &str) {
+        match action {
+            "app.quit" => self.container.event(WindowEvent::TryQuit),
+            "app.exit" => { let _ = self.container.post(WindowRequest::RemoveSelfWindow); }
+            "window.next" => self.container.cycle_current(),
+            "window.close" => {
+                if let Some(window) = self.container.get_from_order_mut(self.container.get_current()) {
+                    window.event(WindowEvent::TryQuit);
+                }
+            }
+            "prompt.command" => {
+                let prompt = Command::new(self.commands(), self.buffers());
+                self.add_popup(Box::new(prompt));
+                let _ = self.container.post(WindowRequest::Redraw);
+            }
+            _ => ()
+        }
+    }
+
+    /// Show the keys that may follow a half typed sequence, once it was pending for `hintdelay`
+    fn update_hints(&mut self) {
+        let settings = current_settings();
+        let hints = if settings.bool("keyhints") { self.key_hints() } else { None };
+        let Some(hints) = hints else {
+            if let Some((_, _, true)) = self.hints.take() {
+                let _ = self.container.post(WindowRequest::Redraw);
+            }
+            return;
+        };
+
+        if self.hints.as_ref().is_none_or(|(popup, _, _)| *popup.hints() != hints) {
+            // hints already shown follow the sequence as it goes on
+            let shown = self.hints.as_ref().is_some_and(|(_, _, shown)| *shown);
+            self.hints = Some((KeyHintPopup::new(hints, self.layout.get_dim()), Instant::now(), shown));
+            if shown {
+                let _ = self.container.post(WindowRequest::Redraw);
+            }
+        }
+
+        let delay = Duration::from_millis(settings.number("hintdelay") as u64);
+        if let Some((_, since, shown @ false)) = self.hints.as_mut() {
+            if since.elapsed() >= delay {
+                *shown = true;
+                let _ = self.container.post(WindowRequest::Redraw);
+            }
+        }
+    }
+
+    /// Run command line as if typed in the command prompt, showing what is wrong with it
+    fn run_command(&mut self, line: &str) {
+        if line.trim().is_empty() { return; }
+        match cmdline::parse(line, &self.commands()) {
+            Ok(command) if COMMANDS.contains(command.spec) => self.global_command(command),
+            Ok(command) => self.container.event(WindowEvent::Command(command)),
+            Err(message) => self.alert(message),
+        }
+        let _ = self.container.post(WindowRequest::Redraw);
+    }
+
+    fn global_command(&mut self, command: ParsedCommand) {
+        match command.name() {
+            "settings" => {
+                let mut tab = TabWindow::new();
+                tab.add_window(Box::new(SettingsWindow::new(self.current_dir.clone())));
+                self.container.get_receiver().new_poster().post(WindowRequest::AddWindow(Some(Box::new(tab))));
+            }
+            "x" => {
+                let mut tab = TabWindow::new();
+                tab.add_window(Box::new(FSWindow::new(self.current_dir.clone())));
+                self.container.get_receiver().new_poster().post(WindowRequest::AddWindow(Some(Box::new(tab))));
+            }
+            "qall" if command.bang => { let _ = self.container.post(WindowRequest::RemoveSelfWindow); }
+            "qall" => self.event(WindowEvent::TryQuit),
+            "set" => self.set_options(command.options()),
+            "setglobal" => self.save_options(Layer::Global, command.options()),
+            "setproject" => self.save_options(Layer::Project, command.options()),
+            "colorscheme" => match command.text() {
+                Some(name) => self.set_options(&[format!("colorscheme={}", name)]),
+                None => self.set_options(&["colorscheme?".to_string()]),
+            }
+            _ => ()
+        }
+    }
+
+    /// Show message in a popup
+    pub fn alert(&mut self, message: String) {
+        self.add_popup(Box::new(Alert {
+            content: StyledText::new(message),
+            options: vec![(StyledText::new("Ok".into()), vec![])],
+            ..Default::default()
+        }));
+    }
+
+    /// Apply the arguments of `:set` for this session, showing queried values and errors.
+    /// Without arguments, shows the options changed from their default
+    fn set_options(&mut self, args: &[String]) {
+        let current = current_layers();
+        let mut layers = current.clone();
+        let mut messages = Vec::new();
+
+        for arg in args {
+            let before = layers.clone();
+            match layers.apply_set(arg) {
+                Ok(Some(message)) | Err(message) => messages.push(message),
+                Ok(None) => (),
+            }
+            let colorscheme = layers.merge().text("colorscheme").to_string();
+            if themes().by_name(&colorscheme).is_none() {
+                messages.push(format!("Unknown color scheme '{}'", colorscheme));
+                layers = before;
+            }
+        }
+        if args.is_empty() {
+            messages.extend(current_settings().changed().into_iter().map(|(info, value)| format!("{}={}", info.name, value)));
+        }
+
+        if layers != current {
+            set_layers(layers);
+            self.apply_settings();
+        }
+        if !messages.is_empty() {
+            self.alert(messages.join("  "));
+        }
+    }
+
+    /// Apply the arguments of `:setglobal` or `:setproject`, and save the options named to the layer's file
+    fn save_options(&mut self, layer: Layer, args: &[String]) {
+        let current = current_layers();
+        let mut layers = current.clone();
+        let mut errors = Vec::new();
+
+        for arg in args {
+            let mut settings = layers.merge();
+            let saved = apply_saved_set(&mut settings, arg)
+                .and_then(|(info, value)| layers.save(layer, &self.current_dir, info, value.as_ref()));
+            if let Err(e) = saved {
+                errors.push(e);
+            }
+        }
+
+        if layers != current {
+            set_layers(layers);
+            self.apply_settings();
+        }
+        if !errors.is_empty() {
+            self.alert(errors.join("  "));
+        }
+    }
+
+    /// Make the current settings take effect in the terminal and every window
+    pub fn apply_settings(&mut self) {
+        let settings = current_settings();
+        match settings.text("termcolors") {
+            "auto" => set_color_depth(ColorDepth::detect()),
+            name => if let Some(depth) = ColorDepth::from_name(name) { set_color_depth(depth) },
+        }
+        if let Err(message) = set_colorscheme(settings.text("colorscheme")) {
+            self.alert(message);
+        }
+
+        self.container.event(WindowEvent::SettingsChanged);
+        let _ = self.container.post(WindowRequest::Redraw);
+    }
+
     pub fn resize(&mut self, dim: Plot) {
         self.layout.set_dim(dim);
         // propagate
@@ -206,4 +415,55 @@ impl WindowManager {
     pub fn set_dir(&mut self, dir: PathBuf) {
         self.current_dir = dir;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use crate::textedit::buffer::TextBuffer;
+    use crate::textedit::textwindow::TextWindow;
+
+    #[test]
+    fn hints_show_after_delay() {
+        let mut receiver = EventReceiver::new();
+        let mut manager = WindowManager::new();
+        manager.init(receiver.new_poster());
+        manager.resize(Plot::new(20, 40));
+        manager.add_window(Box::new(TestWindow::default()));
+
+        // <C-w> starts global bindings
+        manager.event(WindowEvent::Input { key: KeyCode::Char('w'), modifiers: KeyModifiers::CONTROL });
+        manager.collect_requests();
+        receiver.poll();
+        assert!(matches!(manager.hints, Some((_, _, false))));
+
+        // shown without typing anything else
+        std::thread::sleep(Duration::from_millis(current_settings().number("hintdelay") as u64));
+        manager.collect_requests();
+        assert!(matches!(manager.hints, Some((_, _, true))));
+        assert!(receiver.poll().iter().any(|(_, request)| matches!(request, WindowRequest::Redraw)));
+    }
+
+    #[test]
+    fn editor_keys_reach_the_window() {
+        let mut receiver = EventReceiver::new();
+        let mut manager = WindowManager::new();
+        manager.init(receiver.new_poster());
+        manager.add_window(Box::new(TextWindow::new(TextBuffer::new())));
+        manager.resize(Plot::new(5, 20));
+        let keys = "ix = 5".chars().map(|ch| (KeyCode::Char(ch), KeyModifiers::NONE))
+            .chain([(KeyCode::Esc, KeyModifiers::NONE), (KeyCode::Char('0'), KeyModifiers::NONE)]);
+        for (key, modifiers) in keys {
+            manager.event(WindowEvent::Input { key, modifiers });
+        }
+
+        // decrements in normal mode rather than closing the window
+        manager.event(WindowEvent::Input { key: KeyCode::Char('x'), modifiers: KeyModifiers::CONTROL });
+        manager.collect_requests();
+        assert!(!receiver.poll().iter().any(|(_, request)| matches!(request, WindowRequest::RemoveSelfWindow)));
+        let mut canvas = Canvas::new(Plot::new(5, 20));
+        manager.container.get_from_order(0).unwrap().draw(&mut canvas);
+        assert!(canvas.row_text(0).contains("x = 4"));
+    }
+}